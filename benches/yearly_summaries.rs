@@ -0,0 +1,29 @@
+use cic::calculations::{yearly_summaries, Investment};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn scenarios() -> Vec<Investment> {
+    (0..500)
+        .map(|i| Investment {
+            principal: 1000.0 * i as f64,
+            contribution: 100.0,
+            rate: 1.0 + (i % 10) as f64,
+            years: 1000,
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn bench_yearly_summaries(c: &mut Criterion) {
+    let investments = scenarios();
+    let mut group = c.benchmark_group("yearly_summaries");
+    group.bench_function("sequential", |b| {
+        b.iter(|| yearly_summaries(&investments, false).unwrap())
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| yearly_summaries(&investments, true).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_yearly_summaries);
+criterion_main!(benches);
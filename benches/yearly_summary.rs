@@ -0,0 +1,54 @@
+use cic::calculations::{glide_path, CompoundFrequency, Investment};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// The horizons benchmarked, from a typical ten-year plan up to `MAX_YEARS`.
+const HORIZONS: [(&str, i32); 3] = [("small", 10), ("medium", 100), ("large", 1000)];
+
+fn investment(years: i32) -> Investment {
+    Investment {
+        principal: 10000.0,
+        contribution: 500.0,
+        rate: 5.0,
+        years,
+        ..Default::default()
+    }
+}
+
+/// The options that change the work done per year: the default annual loop, twelve periods a
+/// year, and a rate looked up per year.
+fn variants(years: i32) -> [(&'static str, Investment); 3] {
+    [
+        ("annual", investment(years)),
+        (
+            "monthly",
+            Investment {
+                compound_frequency: CompoundFrequency::Monthly,
+                ..investment(years)
+            },
+        ),
+        (
+            "variable_rates",
+            Investment {
+                variable_rates: glide_path(8.0, 4.0, years),
+                ..investment(years)
+            },
+        ),
+    ]
+}
+
+fn bench_yearly_summary(c: &mut Criterion) {
+    let mut group = c.benchmark_group("yearly_summary");
+    for (size, years) in HORIZONS {
+        for (name, investment) in variants(years) {
+            group.bench_with_input(
+                BenchmarkId::new(name, size),
+                &investment,
+                |b, investment| b.iter(|| black_box(investment).yearly_summary().unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_yearly_summary);
+criterion_main!(benches);
@@ -1,4 +1,12 @@
-use clap::{Arg, ArgMatches, Command};
+use crate::calculations::{
+    parse_hex_color, Account, AccountType, Allocation, CompoundFrequency, RateFormat, RateTier,
+};
+use crate::config;
+use crate::output::Emit;
+use crate::params::InvestmentParams;
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+use serde_json::json;
+use std::str::FromStr;
 
 /// Builds the CLI command structure for the Compound Interest Calculator.
 ///
@@ -9,42 +17,182 @@ use clap::{Arg, ArgMatches, Command};
 /// A `Command` instance configured with the necessary arguments and subcommands.
 pub fn build_cli() -> Command {
     Command::new("Compound Interest Calculator")
-        .about("cis - Calculates Compound Interest.\nOutput the results of compound interest calculations as either a line graph image or JSON.")
+        .bin_name("cic")
+        .about("cic - Calculates Compound Interest.\nOutput the results of compound interest calculations as either a line graph image or JSON.")
+        .args(investment_args())
         .arg(
-            Arg::new("principal")
-                .short('p')
-                .long("principal")
-                .value_name("PRINCIPAL")
-                .help("The principal at the time you started investing. Defaults to 0"),
+            Arg::new("json")
+                .short('j')
+                .long("json")
+                .help("Output as JSON. Same as --format json. Defaults to false")
+                .action(clap::ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("contribution")
-                .short('c')
-                .long("contribution")
-                .value_name("CONTRIBUTION")
-                .help("The monthly contribution amount. Defaults to 1"),
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["json", "csv", "table", "xlsx", "png", "svg", "flat-json", "toml"])
+                .help("Output the summary as json, csv, table, flat-json, toml or an xlsx workbook instead of plotting it, or write the chart to stdout as png or svg"),
         )
         .arg(
-            Arg::new("rate")
-                .short('r')
-                .long("rate")
-                .value_name("RATE")
-                .help("The annual interest rate (in %). Defaults to 5"),
+            Arg::new("flat-json")
+                .long("flat-json")
+                .help("Output a flat JSON array with one record per year and a fixed schema for ETL and BI tools. Same as --format flat-json")
+                .conflicts_with_all(["timestamp", "echo-input", "with-options", "with-metrics", "with-crossover"])
+                .action(clap::ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("years")
-                .short('y')
-                .long("years")
-                .value_name("YEARS")
-                .help("The number of years for contributions. Defaults to 5"),
+            Arg::new("toml")
+                .long("toml")
+                .help("Output as TOML with one [[year]] table per year. Same as --format toml")
+                .conflicts_with_all(["timestamp", "echo-input", "with-options", "with-metrics", "with-crossover"])
+                .action(clap::ArgAction::SetTrue),
         )
+        .group(ArgGroup::new("output-format").args(["json", "format", "flat-json", "toml"]))
         .arg(
-            Arg::new("json")
-                .short('j')
-                .long("json")
-                .help("Output as JSON. Defaults to false")
+            Arg::new("final-only")
+                .long("final-only")
+                .requires("output-format")
+                .help("Output only the final year's result. Requires --json or --format")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timestamp")
+                .long("timestamp")
+                .requires("output-format")
+                .help("Wrap the JSON output in an object with a generated_at UTC timestamp. Requires --json")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("echo-input")
+                .long("echo-input")
+                .requires("output-format")
+                .help("Include the input in the JSON output, so it can be checked with `cic verify`. Requires --json")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("with-options")
+                .long("with-options")
+                .requires("output-format")
+                .help("Include the applied calculation options, such as the compounding frequency, fees and taxes, in the JSON output. Requires --json")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("json-case")
+                .long("json-case")
+                .value_name("CASE")
+                .value_parser(["snake", "camel"])
+                .requires("output-format")
+                .help("How the JSON keys are written: snake (total_amount) or camel (totalAmount). Defaults to snake. Requires --json")
+        )
+        .arg(
+            Arg::new("with-metrics")
+                .long("with-metrics")
+                .requires("output-format")
+                .help("Include the headline metrics (final amount, contributions, interest, CAGR, total return and APY) in the JSON output. Requires --json")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("with-crossover")
+                .long("with-crossover")
+                .requires("output-format")
+                .help("Wrap the JSON output in an object with the crossover_year, the first year whose interest exceeds its contribution. Requires --json")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("out-file")
+                .long("out-file")
+                .value_name("PATH")
+                .help("Write the summary to PATH in the --format (json by default) instead of stdout. Parent directories are created"),
+        )
+        .arg(
+            Arg::new("emit")
+                .long("emit")
+                .value_name("FORMAT:PATH")
+                .value_parser(clap::value_parser!(Emit))
+                .action(clap::ArgAction::Append)
+                .help("Also write the summary in FORMAT (e.g. png, svg or csv) to PATH. Can be repeated, e.g. --emit png:chart.png --emit csv:data.csv"),
+        )
+        .args(plot_args())
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("PATH")
+                .help("The path of the chart image, or - for stdout. Defaults to plot.png. Combined with --json or --format, both are written"),
+        )
+        .arg(
+            Arg::new("sqlite")
+                .long("sqlite")
+                .value_name("PATH")
+                .help("Append the summary to the yearly_summary table of the SQLite database at PATH, under a new run_id"),
+        )
+        .arg(
+            Arg::new("composition")
+                .long("composition")
+                .value_name("PATH")
+                .help("Also write a chart of the contribution and interest shares of the total to PATH"),
+        )
+        .arg(
+            Arg::new("rate-range")
+                .long("rate-range")
+                .value_name("LOW,EXPECTED,HIGH")
+                .value_parser(parse_rate_range)
+                .conflicts_with_all(["rate", "glide", "tier", "allocation", "query"])
+                .help("Calculate the summary at the EXPECTED rate (in %), and plot a fan chart of the LOW, EXPECTED and HIGH rates instead of the regular chart, e.g. 4,6,8"),
+        )
+        .arg(
+            Arg::new("sparkline")
+                .long("sparkline")
+                .conflicts_with("output-format")
+                .help("Print a one-line sparkline of the total amount instead of plotting it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("oneline")
+                .long("oneline")
+                .conflicts_with_all(["output-format", "sparkline"])
+                .help("Print a single line with the final balance, contributions, interest and CAGR instead of plotting it")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("human")
+                .long("human")
+                .help("Abbreviate the amounts of the table and --oneline, e.g. $4.9k, $1.2M or $3.4B. JSON and CSV keep the full precision")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-plot")
+                .long("no-plot")
+                .conflicts_with_all(["output", "composition"])
+                .help("Do not plot the chart. Prints the table instead unless another output is requested")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .requires("no-plot")
+                .help("With --no-plot, print nothing instead of the table")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("milestones")
+                .long("milestones")
+                .value_name("AMOUNTS")
+                .value_delimiter(',')
+                .value_parser(parse_finite)
+                .help("Report the first year the total amount reaches each of the comma-separated AMOUNTS, e.g. 100000,250000,1000000"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .global(true)
+                .value_parser(clap::value_parser!(u64))
+                .help("The seed of every randomized calculation, e.g. simulate, so the results can be reproduced. Defaults to a random seed, which is reported"),
+        )
         .subcommand(
             Command::new("server")
                 .about("Starts the server mode")
@@ -54,8 +202,697 @@ pub fn build_cli() -> Command {
                         .long("port")
                         .value_name("PORT")
                         .help("The port to run the server on. Defaults to 8080"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress the startup banner. Errors are still reported")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .value_name("PATH")
+                        .help("A TOML config file (see init-config) with the defaults for omitted request fields"),
+                )
+                .arg(
+                    Arg::new("log-format")
+                        .long("log-format")
+                        .value_name("FORMAT")
+                        .value_parser(["text", "json"])
+                        .help("Write one request log per line to stderr as text or json. Defaults to text"),
+                )
+                .arg(
+                    Arg::new("rate-limit")
+                        .long("rate-limit")
+                        .value_name("PER_MINUTE")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("Allow each client IP at most PER_MINUTE requests a minute, answering 429 Too Many Requests beyond it. Defaults to no limit"),
+                ),
+        )
+        .subcommand(
+            Command::new("portfolio")
+                .about("Calculates the combined trajectory of several accounts as JSON")
+                .arg(
+                    Arg::new("account")
+                        .short('a')
+                        .long("account")
+                        .value_name("NAME:PRINCIPAL:CONTRIBUTION:RATE")
+                        .help("An account to include, e.g. savings:1000:100:2. Can be repeated")
+                        .required(true)
+                        .action(clap::ArgAction::Append)
+                        .value_parser(clap::value_parser!(Account)),
+                )
+                .arg(
+                    Arg::new("years")
+                        .short('y')
+                        .long("years")
+                        .value_name("YEARS")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("The number of years for contributions. Defaults to 5"),
+                )
+                .arg(
+                    Arg::new("parallel")
+                        .long("parallel")
+                        .help("Calculate the accounts in parallel. The output is the same")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(config_arg()),
+        )
+        .subcommand(
+            Command::new("what-if")
+                .about("Shows how much more you end up with by increasing the monthly contribution")
+                .args(investment_args())
+                .arg(
+                    Arg::new("extra-contribution")
+                        .short('e')
+                        .long("extra-contribution")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The amount added to the monthly contribution"),
+                ),
+        )
+        .subcommand(
+            Command::new("cost-of-waiting")
+                .about("Shows how much less you end up with by starting later but stopping at the same time")
+                .args(investment_args())
+                .arg(
+                    Arg::new("delay")
+                        .short('d')
+                        .long("delay")
+                        .value_name("YEARS")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i32).range(0..))
+                        .help("The number of years to wait before starting, within --years"),
+                ),
+        )
+        .subcommand(
+            Command::new("offset-expense")
+                .about("Shows the extra monthly contribution needed to end with the same balance despite a planned expense")
+                .args(investment_args())
+                .arg(
+                    Arg::new("expense")
+                        .short('e')
+                        .long("expense")
+                        .value_name("YEAR:AMOUNT")
+                        .required(true)
+                        .value_parser(parse_expense)
+                        .help("The expense withdrawn at the end of YEAR, e.g. 8:30000"),
+                )
+                .arg(round_contribution_arg()),
+        )
+        .subcommand(
+            Command::new("goal")
+                .about("Shows the monthly contribution needed to reach a target final balance")
+                .args(investment_args())
+                .arg(
+                    Arg::new("target")
+                        .short('t')
+                        .long("target")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The final balance to reach"),
+                )
+                .arg(
+                    Arg::new("target-in-todays-dollars")
+                        .long("target-in-todays-dollars")
+                        .requires("inflation-source")
+                        .help("Treat the target as today's money and inflate it with --inflation or --inflation-file before solving")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(round_contribution_arg())
+                .after_help(
+                    "The contribution is solved for. If --salary is given, the required savings \
+                     rate is shown as well.",
+                ),
+        )
+        .subcommand(
+            Command::new("fire")
+                .about("Shows the FIRE number for the annual expenses and when the investment reaches it")
+                .args(investment_args())
+                .arg(
+                    Arg::new("expenses")
+                        .long("expenses")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The annual expenses to cover in retirement"),
+                )
+                .arg(
+                    Arg::new("withdrawal-rate")
+                        .long("withdrawal-rate")
+                        .value_name("PERCENT")
+                        .value_parser(parse_finite)
+                        .help("The safe withdrawal rate (in %). Defaults to 4"),
+                )
+                .arg(round_contribution_arg())
+                .after_help(
+                    "The FIRE number is the expenses divided by the withdrawal rate. The year it is \
+                     reached is searched beyond --years; the required contribution reaches it \
+                     within --years.",
+                ),
+        )
+        .subcommand(
+            Command::new("time-to-target")
+                .about("Shows how long the investment takes to reach a target balance")
+                .args(investment_args())
+                .arg(
+                    Arg::new("target")
+                        .short('t')
+                        .long("target")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The balance to reach"),
+                )
+                .after_help(
+                    "The time is searched beyond --years and rounded up to whole months, \
+                     interpolating within the year the target is reached.",
+                ),
+        )
+        .subcommand(
+            Command::new("breakeven-fee")
+                .about("Shows the fee at which a fund with a higher rate ends with the same balance as a fee-free benchmark")
+                .args(investment_args())
+                .arg(
+                    Arg::new("high-rate")
+                        .long("high-rate")
+                        .value_name("RATE")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The annual rate of the fund before its fee"),
+                )
+                .arg(
+                    Arg::new("low-rate")
+                        .long("low-rate")
+                        .value_name("RATE")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The annual rate of the fee-free benchmark, e.g. an index fund"),
+                )
+                .after_help(
+                    "Both are calculated with the principal, contributions, years and taxes of the \
+                     investment. --rate and --fee are ignored.",
+                ),
+        )
+        .subcommand(
+            Command::new("annualize")
+                .about("Converts a total return over several years into the equivalent annual rate")
+                .arg(
+                    Arg::new("total-return")
+                        .long("total-return")
+                        .value_name("PERCENT")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(parse_finite)
+                        .help("The total return (in %), e.g. 100 for doubling the money"),
+                )
+                .arg(
+                    Arg::new("years")
+                        .short('y')
+                        .long("years")
+                        .value_name("YEARS")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The number of years over which the total return is earned, e.g. 7.5"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert-rate")
+                .about("Converts between a nominal annual rate (APR), the effective annual yield (APY) and the rate per period")
+                .arg(
+                    Arg::new("apr")
+                        .long("apr")
+                        .value_name("PERCENT")
+                        .value_parser(parse_finite)
+                        .help("The nominal annual rate (in %) to convert"),
+                )
+                .arg(
+                    Arg::new("apy")
+                        .long("apy")
+                        .value_name("PERCENT")
+                        .value_parser(parse_finite)
+                        .help("The effective annual yield (in %) to convert"),
+                )
+                .group(ArgGroup::new("rate").args(["apr", "apy"]).required(true))
+                .arg(
+                    Arg::new("periods")
+                        .short('n')
+                        .long("periods")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("The number of compounding periods per year, e.g. 365 for daily. Defaults to 12"),
                 ),
         )
+        .subcommand(
+            Command::new("save-for")
+                .about("Shows the monthly amount to save for the down payment of a purchase")
+                .arg(
+                    Arg::new("price")
+                        .long("price")
+                        .value_name("PRICE")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The price of the purchase"),
+                )
+                .arg(
+                    Arg::new("down-payment")
+                        .short('d')
+                        .long("down-payment")
+                        .value_name("PERCENT")
+                        .value_parser(parse_finite)
+                        .help("The down payment as a percentage of the price. Defaults to 20"),
+                )
+                .arg(
+                    Arg::new("years")
+                        .short('y')
+                        .long("years")
+                        .value_name("YEARS")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("The number of years until the purchase. Defaults to 5"),
+                )
+                .arg(
+                    Arg::new("savings")
+                        .short('s')
+                        .long("savings")
+                        .value_name("SAVINGS")
+                        .value_parser(parse_finite)
+                        .help("The amount already saved. Defaults to 0"),
+                )
+                .arg(
+                    Arg::new("rate")
+                        .short('r')
+                        .long("rate")
+                        .value_name("RATE")
+                        .value_parser(parse_finite)
+                        .help("The annual interest rate (in %) earned on the savings. Defaults to 5"),
+                )
+                .arg(round_contribution_arg())
+                .arg(config_arg()),
+        )
+        .subcommand(
+            Command::new("solve-principal")
+                .about("Shows the principal needed to reach a target final balance with the given contributions")
+                .arg(
+                    Arg::new("target")
+                        .short('t')
+                        .long("target")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The final balance to reach"),
+                )
+                .arg(
+                    Arg::new("contribution")
+                        .short('c')
+                        .long("contribution")
+                        .value_name("AMOUNT")
+                        .value_parser(parse_finite)
+                        .help("The monthly contribution. Defaults to 1"),
+                )
+                .arg(
+                    Arg::new("rate")
+                        .short('r')
+                        .long("rate")
+                        .value_name("RATE")
+                        .value_parser(parse_finite)
+                        .help("The annual interest rate (in %). Defaults to 5"),
+                )
+                .arg(
+                    Arg::new("years")
+                        .short('y')
+                        .long("years")
+                        .value_name("YEARS")
+                        .value_parser(clap::value_parser!(i32).range(0..))
+                        .help("The number of years. Defaults to 5"),
+                )
+                .arg(config_arg()),
+        )
+        .subcommand(
+            Command::new("lump-vs-dca")
+                .about("Compares investing an amount at once with spreading it over several months")
+                .arg(
+                    Arg::new("amount")
+                        .long("amount")
+                        .value_name("AMOUNT")
+                        .required(true)
+                        .value_parser(parse_finite)
+                        .help("The amount to invest"),
+                )
+                .arg(
+                    Arg::new("months")
+                        .short('m')
+                        .long("months")
+                        .value_name("MONTHS")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("The number of months dollar-cost averaging spreads the amount over. Defaults to 12"),
+                )
+                .arg(
+                    Arg::new("years")
+                        .short('y')
+                        .long("years")
+                        .value_name("YEARS")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("The number of years of the horizon. Defaults to 5"),
+                )
+                .arg(
+                    Arg::new("rate")
+                        .short('r')
+                        .long("rate")
+                        .value_name("RATE")
+                        .value_parser(parse_finite)
+                        .help("The annual interest rate (in %). Defaults to 5"),
+                )
+                .arg(config_arg()),
+        )
+        .subcommand(
+            Command::new("value")
+                .about("Prints a single metric as a bare number, for scripting")
+                .args(investment_args())
+                .arg(
+                    Arg::new("final")
+                        .long("final")
+                        .help("Print the final balance")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("cagr")
+                        .long("cagr")
+                        .help("Print the compound annual growth rate as a decimal fraction, e.g. 0.05")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .group(
+                    ArgGroup::new("metric")
+                        .args(["final", "cagr"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("metrics")
+                .about("Prints the time-weighted and money-weighted returns of the investment")
+                .args(investment_args()),
+        )
+        .subcommand(
+            Command::new("compare-accounts")
+                .about("Compares the final balance after tax in a taxable, tax-deferred and tax-free account")
+                .args(investment_args()),
+        )
+        .subcommand(
+            Command::new("simulate")
+                .about("Simulates the final balance with randomly varying annual returns (Monte Carlo)")
+                .args(investment_args())
+                .arg(
+                    Arg::new("volatility")
+                        .long("volatility")
+                        .value_name("RATE")
+                        .value_parser(parse_finite)
+                        .help("The standard deviation (in %) of the annual rate. Defaults to 15"),
+                )
+                .arg(
+                    Arg::new("runs")
+                        .long("runs")
+                        .value_name("RUNS")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("The number of simulated trajectories. Defaults to 1000"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Recalculates a JSON report written with --echo-input and checks that it matches")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("The JSON report to verify"),
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .long("tolerance")
+                        .value_name("AMOUNT")
+                        .value_parser(parse_finite)
+                        .help("The largest difference accepted for every amount. Defaults to 0.01"),
+                ),
+        )
+        .subcommand(
+            Command::new("datauri")
+                .about("Prints the chart as a data:image/png;base64 URI")
+                .args(investment_args())
+                .args(plot_args()),
+        )
+        .subcommand(
+            Command::new("init-config")
+                .about("Prints a commented example config file with the default values"),
+        )
+}
+
+/// Builds the arguments that describe an investment.
+///
+/// These are shared by the main command and by the subcommands that calculate an investment, so
+/// that `Investment::from_matches` can be used with any of them.
+///
+/// # Returns
+///
+/// A vector of `Arg` instances for the principal, contribution, rate, and duration.
+pub fn investment_args() -> Vec<Arg> {
+    vec![
+        Arg::new("principal")
+            .short('p')
+            .long("principal")
+            .value_name("PRINCIPAL")
+            .allow_negative_numbers(true)
+            .value_parser(parse_finite)
+            .help("The principal at the time you started investing, or the negative balance owed with --debt. Defaults to 0"),
+        Arg::new("contribution")
+            .short('c')
+            .long("contribution")
+            .value_name("CONTRIBUTION")
+            .value_parser(parse_finite)
+            .help("The monthly contribution amount. Defaults to 1")
+            .conflicts_with("salary"),
+        Arg::new("salary")
+            .long("salary")
+            .value_name("SALARY")
+            .value_parser(parse_finite)
+            .help("The annual salary. If set, the monthly contribution is derived from it instead of --contribution"),
+        Arg::new("savings-rate")
+            .long("savings-rate")
+            .value_name("PERCENT")
+            .value_parser(parse_finite)
+            .requires("salary")
+            .help("The percentage of the salary saved each year. Defaults to 10"),
+        Arg::new("salary-growth")
+            .long("salary-growth")
+            .value_name("PERCENT")
+            .value_parser(parse_finite)
+            .requires("salary")
+            .help("The annual salary growth (in %). Defaults to 0"),
+        Arg::new("rate")
+            .short('r')
+            .long("rate")
+            .value_name("RATE")
+            .value_parser(parse_finite)
+            .help("The annual interest rate (in %). Defaults to 5"),
+        Arg::new("rate-format")
+            .long("rate-format")
+            .value_name("FORMAT")
+            .value_parser(["percent", "decimal"])
+            .help("How the rate is written: percent (5 = 5%) or decimal (0.05 = 5%). Defaults to percent"),
+        Arg::new("glide")
+            .long("glide")
+            .value_name("START_RATE,END_RATE")
+            .value_parser(parse_glide)
+            .conflicts_with("rate")
+            .help("Linearly move the rate from START_RATE in the first year to END_RATE in the final year instead of using --rate"),
+        Arg::new("tier")
+            .long("tier")
+            .value_name("THRESHOLD:LOW_RATE:HIGH_RATE")
+            .value_parser(clap::value_parser!(RateTier))
+            .conflicts_with_all(["rate", "glide"])
+            .help("Pay LOW_RATE (in %) on the balance up to THRESHOLD and HIGH_RATE on the part above it instead of using --rate, e.g. 10000:1:4"),
+        Arg::new("allocation")
+            .long("allocation")
+            .value_name("NAME:WEIGHT:RATE,...")
+            .value_parser(clap::value_parser!(Allocation))
+            .conflicts_with_all(["rate", "glide", "tier", "query"])
+            .help("Use the blended rate of an asset allocation instead of --rate. The weights (in %) must add up to 100, e.g. stocks:60:8,bonds:40:3"),
+        Arg::new("inflation")
+            .long("inflation")
+            .value_name("RATE")
+            .value_parser(parse_finite)
+            .group("inflation-source")
+            .help("The annual inflation rate (in %) used for the inflation-adjusted amounts. Defaults to 0"),
+        Arg::new("inflation-file")
+            .long("inflation-file")
+            .value_name("PATH")
+            .group("inflation-source")
+            .conflicts_with("inflation")
+            .help("Read the inflation rate (in %) of every year from a file, one rate per line, e.g. historical CPI figures"),
+        Arg::new("carry-forward-inflation")
+            .long("carry-forward-inflation")
+            .requires("inflation-file")
+            .help("Repeat the last rate of --inflation-file for the remaining years instead of failing when it is too short")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("index-contributions-to-inflation")
+            .long("index-contributions-to-inflation")
+            .requires("inflation-source")
+            .conflicts_with("salary")
+            .help("Grow the monthly contribution with inflation so it stays constant in today's money")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("fee")
+            .long("fee")
+            .value_name("PERCENT")
+            .value_parser(parse_finite)
+            .help("The annual fee (in %), e.g. an expense ratio, subtracted from the rate. Defaults to 0"),
+        Arg::new("rate-is-net")
+            .long("rate-is-net")
+            .help("The rate is already the net return after fees, so --fee is ignored")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("match-rate")
+            .long("match-rate")
+            .value_name("PERCENT")
+            .value_parser(parse_finite)
+            .help("The percentage of the contributions matched by an employer, e.g. 50. Defaults to 0"),
+        Arg::new("match-cap")
+            .long("match-cap")
+            .value_name("AMOUNT")
+            .value_parser(parse_finite)
+            .requires("match-rate")
+            .help("The maximum employer match per year. Defaults to no cap"),
+        Arg::new("account-type")
+            .long("account-type")
+            .value_name("TYPE")
+            .value_parser(["taxable", "tax-deferred", "tax-free"])
+            .help("How the account is taxed: taxable (interest taxed yearly), tax-deferred (withdrawal taxed) or tax-free. Defaults to taxable"),
+        Arg::new("tax-rate")
+            .long("tax-rate")
+            .value_name("PERCENT")
+            .value_parser(parse_tax_rate)
+            .help("The tax rate (in %) applied as described by --account-type. Defaults to 0"),
+        Arg::new("debt")
+            .long("debt")
+            .help("Model paying down a debt: the principal is the negative balance owed and the contributions are the payments")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("skip-months")
+            .long("skip-months")
+            .value_name("MONTHS")
+            .value_delimiter(',')
+            .value_parser(clap::value_parser!(u32).range(1..=12))
+            .help("The comma-separated months of the year (1 to 12) in which no contribution is made, e.g. 6,7,8"),
+        Arg::new("pause-years")
+            .long("pause-years")
+            .value_name("START:END")
+            .value_parser(parse_pause_years)
+            .help("Pause the contributions from year START to year END (inclusive) while the balance keeps earning interest, e.g. 3:4"),
+        Arg::new("compound-frequency")
+            .long("compound-frequency")
+            .value_name("FREQUENCY")
+            .value_parser(["annual", "semi-annual", "monthly"])
+            .help("How often interest is compounded: annual, semi-annual (as with most bonds) or monthly. Defaults to annual"),
+        Arg::new("contribution-frequency")
+            .long("contribution-frequency")
+            .value_name("FREQUENCY")
+            .value_parser(["monthly", "bi-weekly"])
+            .help("How often the contribution is made: monthly or bi-weekly (26 times a year). Defaults to monthly"),
+        Arg::new("mid-year-contribution")
+            .long("mid-year-contribution")
+            .help("Credit the contributions with half of the interest of the compounding period they are made in, i.e. half a year with annual compounding. By default, they earn from the next period")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("simple")
+            .long("simple")
+            .help("Use simple interest, earned only on the principal and contributions. Defaults to compound interest")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("years")
+            .short('y')
+            .long("years")
+            .value_name("YEARS")
+            .value_parser(clap::value_parser!(i32))
+            .help("The number of years for contributions. Defaults to 5"),
+        Arg::new("max-rate")
+            .long("max-rate")
+            .value_name("PERCENT")
+            .value_parser(parse_finite)
+            .help("Warn when the rate (in %) is above this threshold. Defaults to 30"),
+        Arg::new("no-warn")
+            .long("no-warn")
+            .help("Do not warn about an unrealistically high rate")
+            .action(clap::ArgAction::SetTrue),
+        config_arg(),
+        Arg::new("query")
+            .long("query")
+            .value_name("QUERY")
+            .help("Read the scenario from a URL query string, e.g. \"principal=1000&contribution=100&rate=5&years=10\"")
+            .conflicts_with_all(["principal", "contribution", "rate", "years", "salary"]),
+    ]
+}
+
+/// Builds the arguments that control how the chart is rendered.
+///
+/// These are shared by the main command and by the subcommands that render a chart, so that
+/// `PlotOptions::from_matches` can be used with any of them.
+///
+/// # Returns
+///
+/// A vector of `Arg` instances for the caption and the colors.
+pub fn plot_args() -> Vec<Arg> {
+    vec![
+        Arg::new("annotate-caption")
+            .long("annotate-caption")
+            .help("Append the final balance and CAGR to the chart caption. Defaults to false")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("color-total")
+            .long("color-total")
+            .value_name("HEX")
+            .value_parser(parse_hex_color)
+            .help("The hex color of the total amount line, e.g. #1f77b4. Defaults to blue"),
+        Arg::new("color-contribution")
+            .long("color-contribution")
+            .value_name("HEX")
+            .value_parser(parse_hex_color)
+            .help(
+                "The hex color of the principal + contribution line, e.g. #ff7f0e. Defaults to red",
+            ),
+        Arg::new("x-tick-interval")
+            .long("x-tick-interval")
+            .value_name("YEARS")
+            .value_parser(clap::value_parser!(usize))
+            .help(
+                "The number of years between x-axis labels. Defaults to a value based on the years",
+            ),
+        Arg::new("goal")
+            .long("goal")
+            .value_name("AMOUNT")
+            .value_parser(parse_finite)
+            .help("Draw a dashed goal line at AMOUNT and mark the year the total amount reaches it. The calculation also reports how far short of or over AMOUNT the final balance ends"),
+        Arg::new("dpi")
+            .long("dpi")
+            .value_name("DPI")
+            .value_parser(clap::value_parser!(u32).range(48..=600))
+            .help("The resolution of the chart, e.g. 300 for print. The size, fonts and lines scale from 600x400 at 96 DPI. Defaults to 96"),
+        Arg::new("no-legend")
+            .long("no-legend")
+            .help("Do not draw the legend, e.g. for small thumbnails where it would cover the lines")
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
+/// Builds the argument that reads the defaults of the investment parameters from a TOML file.
+fn config_arg() -> Arg {
+    Arg::new("config")
+        .long("config")
+        .value_name("PATH")
+        .help("A TOML config file (see init-config) with defaults for the principal, contribution, rate, years, compound frequency, debt, inflation, fee and tax rate")
+}
+
+/// Builds the argument that rounds solved contributions up to a step, e.g. the nearest $10.
+fn round_contribution_arg() -> Arg {
+    Arg::new("round-contribution")
+        .long("round-contribution")
+        .value_name("STEP")
+        .value_parser(parse_finite)
+        .help("Round the solved contribution up to a multiple of STEP, e.g. 10 or 50, so the target is still met")
 }
 
 /// Retrieves the port number from the CLI matches.
@@ -76,3 +913,458 @@ pub fn get_port(matches: &ArgMatches) -> u16 {
         .and_then(|s| s.parse().ok())
         .unwrap_or(8080)
 }
+
+/// Retrieves and parses the value of an argument from the CLI matches.
+///
+/// Arguments that are missing, not defined for the (sub)command, or that fail to parse are
+/// treated the same way and yield `None`, so callers can fall back to a default.
+///
+/// # Arguments
+///
+/// * `matches` - The `ArgMatches` instance containing the parsed CLI arguments.
+/// * `id` - The id of the argument.
+///
+/// # Returns
+///
+/// The parsed value, or `None`.
+pub fn get_value<T: FromStr>(matches: &ArgMatches, id: &str) -> Option<T> {
+    matches
+        .try_get_one::<String>(id)
+        .ok()
+        .flatten()
+        .and_then(|s| s.parse().ok())
+}
+
+/// Retrieves the value of a numeric argument parsed by clap with `value_parser!(f64)`.
+///
+/// Unlike `get_value`, an invalid number never gets here: clap already rejected it.
+///
+/// # Arguments
+///
+/// * `matches` - The `ArgMatches` instance containing the parsed CLI arguments.
+/// * `id` - The id of the argument.
+///
+/// # Returns
+///
+/// The number, or `None` if the argument is missing or not defined for the (sub)command.
+pub fn get_number(matches: &ArgMatches, id: &str) -> Option<f64> {
+    matches.try_get_one::<f64>(id).ok().flatten().copied()
+}
+
+/// Retrieves `--rate` as a percentage.
+///
+/// With `--rate-format decimal`, the typed rate is a decimal fraction and is converted here, so
+/// every rate past parsing is a percentage, wherever it came from.
+///
+/// # Arguments
+///
+/// * `matches` - The `ArgMatches` instance containing the parsed CLI arguments.
+///
+/// # Returns
+///
+/// The rate in %, or `None` if `--rate` is not given.
+pub fn get_rate(matches: &ArgMatches) -> Option<f64> {
+    let &rate = matches.try_get_one::<f64>("rate").ok().flatten()?;
+    match get_value(matches, "rate-format").unwrap_or_default() {
+        RateFormat::Percent => Some(rate),
+        RateFormat::Decimal => Some(rate * 100.0),
+    }
+}
+
+/// Resolves the `InvestmentParams` from every source, in a single order of precedence.
+///
+/// From the lowest to the highest precedence, the sources are:
+///
+/// 1. the built-in defaults of `InvestmentParams`,
+/// 2. the TOML config file given with `--config` (see `config::load_params`),
+/// 3. the environment variables, e.g. `CIC_RATE` (see `InvestmentParams::with_env`),
+/// 4. the flags, e.g. `--rate`, and the fields of `--query`.
+///
+/// Each source only overrides the fields it sets, so e.g. a config file with only `rate` keeps
+/// the default `years`. Arguments that are not defined for the (sub)command are skipped.
+///
+/// # Arguments
+///
+/// * `matches` - The `ArgMatches` instance containing the parsed CLI arguments.
+/// * `env` - Looks up an environment variable, e.g. `|key| std::env::var(key).ok()`.
+///
+/// # Returns
+///
+/// Returns the resolved `InvestmentParams` together with the keys of `--query` that are not
+/// parameters, or an error message naming the source that could not be read.
+pub fn resolve_params<F: Fn(&str) -> Option<String>>(
+    matches: &ArgMatches,
+    env: F,
+) -> Result<(InvestmentParams, Vec<String>), String> {
+    let params = match matches.try_get_one::<String>("config").ok().flatten() {
+        Some(path) => {
+            config::load_params(path).map_err(|e| format!("Failed to load config: {}", e))?
+        }
+        None => InvestmentParams::default(),
+    };
+    let params = params.with_env(env)?;
+
+    let mut flags = serde_json::Map::new();
+    if let Some(&principal) = matches.try_get_one::<f64>("principal").ok().flatten() {
+        flags.insert("principal".into(), json!(principal));
+    }
+    if let Some(&contribution) = matches.try_get_one::<f64>("contribution").ok().flatten() {
+        flags.insert("contribution".into(), json!(contribution));
+    }
+    if let Some(rate) = get_rate(matches) {
+        flags.insert("rate".into(), json!(rate));
+    }
+    if let Some(allocation) = matches
+        .try_get_one::<Allocation>("allocation")
+        .ok()
+        .flatten()
+    {
+        flags.insert("rate".into(), json!(allocation.blended_rate()));
+    }
+    if let Some(&(_, expected, _)) = matches
+        .try_get_one::<(f64, f64, f64)>("rate-range")
+        .ok()
+        .flatten()
+    {
+        flags.insert("rate".into(), json!(expected));
+    }
+    if let Some(&years) = matches.try_get_one::<i32>("years").ok().flatten() {
+        flags.insert("years".into(), json!(years));
+    }
+    if let Some(frequency) = get_value::<CompoundFrequency>(matches, "compound-frequency") {
+        flags.insert("compound_frequency".into(), json!(frequency));
+    }
+    if get_flag(matches, "debt") {
+        flags.insert("debt".into(), json!(true));
+    }
+    if let Some(account_type) = get_value::<AccountType>(matches, "account-type") {
+        flags.insert("account_type".into(), json!(account_type));
+    }
+    for (id, key) in [
+        ("inflation", "inflation"),
+        ("fee", "fee"),
+        ("tax-rate", "tax_rate"),
+    ] {
+        if let Some(value) = get_number(matches, id) {
+            flags.insert(key.into(), json!(value));
+        }
+    }
+    let params = params.with_overrides(flags).map_err(|e| e.to_string())?;
+
+    match matches.try_get_one::<String>("query").ok().flatten() {
+        Some(query) => params
+            .with_query(query)
+            .map_err(|e| format!("Failed to parse query: {}", e)),
+        None => Ok((params, Vec::new())),
+    }
+}
+
+/// Parses a finite number, e.g. `5` or `-1000`.
+///
+/// `NaN` and `inf` are rejected, since they cannot be calculated with or written as JSON.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The number, or an error message if the string is not a finite number.
+pub fn parse_finite(s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+        .ok_or_else(|| format!("Invalid number: {} (expected a finite number)", s))
+}
+
+/// Parses a glide path given as `START_RATE,END_RATE`, e.g. `8,4`.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The start and end rate, or an error message if the string is malformed.
+pub fn parse_glide(s: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("Invalid glide path: {} (expected START_RATE,END_RATE)", s);
+    let (start, end) = s.split_once(',').ok_or_else(invalid)?;
+    let start = parse_finite(start).map_err(|_| invalid())?;
+    let end = parse_finite(end).map_err(|_| invalid())?;
+    Ok((start, end))
+}
+
+/// Parses a rate range given as `LOW,EXPECTED,HIGH`, e.g. `4,6,8`.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The low, expected and high rate, or an error message if the string is malformed or the rates
+/// are not in ascending order.
+pub fn parse_rate_range(s: &str) -> Result<(f64, f64, f64), String> {
+    let invalid = || format!("Invalid rate range: {} (expected LOW,EXPECTED,HIGH)", s);
+    let rates = s
+        .split(',')
+        .map(|rate| parse_finite(rate).map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let [low, expected, high] = rates[..] else {
+        return Err(invalid());
+    };
+    if !(low <= expected && expected <= high) {
+        return Err(format!(
+            "Invalid rate range: {} (expected LOW <= EXPECTED <= HIGH)",
+            s
+        ));
+    }
+    Ok((low, expected, high))
+}
+
+/// Parses a tax rate, a percentage from 0 to 100.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The tax rate in %, or an error message if the string is not a number from 0 to 100.
+pub fn parse_tax_rate(s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse()
+        .ok()
+        .filter(|rate| (0.0..=100.0).contains(rate))
+        .ok_or_else(|| {
+            format!(
+                "Invalid tax rate: {} (expected a percentage from 0 to 100)",
+                s
+            )
+        })
+}
+
+/// Parses a contribution holiday given as `START:END`, e.g. `3:4`.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The first and last year of the pause, or an error message if the string is malformed or the
+/// range is empty.
+pub fn parse_pause_years(s: &str) -> Result<(i32, i32), String> {
+    let invalid = || format!("Invalid pause: {} (expected START:END, e.g. 3:4)", s);
+    let (start, end) = s.split_once(':').ok_or_else(invalid)?;
+    let start: i32 = start.trim().parse().map_err(|_| invalid())?;
+    let end: i32 = end.trim().parse().map_err(|_| invalid())?;
+    if start < 1 || end < start {
+        return Err(format!(
+            "Invalid pause: {} (START must be at least 1 and END at least START)",
+            s
+        ));
+    }
+    Ok((start, end))
+}
+
+/// Parses a planned expense given as `YEAR:AMOUNT`, e.g. `8:30000`.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Returns
+///
+/// The year and the amount of the expense, or an error message if the string is malformed, the
+/// year is before 1, or the amount is negative.
+pub fn parse_expense(s: &str) -> Result<(i32, f64), String> {
+    let invalid = || {
+        format!(
+            "Invalid expense: {} (expected YEAR:AMOUNT, e.g. 8:30000)",
+            s
+        )
+    };
+    let (year, amount) = s.split_once(':').ok_or_else(invalid)?;
+    let year: i32 = year.trim().parse().map_err(|_| invalid())?;
+    let amount: f64 = amount.trim().parse().map_err(|_| invalid())?;
+    if year < 1 || !(amount >= 0.0 && amount.is_finite()) {
+        return Err(format!(
+            "Invalid expense: {} (YEAR must be at least 1 and AMOUNT not negative)",
+            s
+        ));
+    }
+    Ok((year, amount))
+}
+
+/// Retrieves the value of a flag from the CLI matches.
+///
+/// # Arguments
+///
+/// * `matches` - The `ArgMatches` instance containing the parsed CLI arguments.
+/// * `id` - The id of the flag.
+///
+/// # Returns
+///
+/// `true` if the flag is set, or `false` if it is not set or not defined for the (sub)command.
+pub fn get_flag(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .try_get_one::<bool>(id)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculations::Investment;
+    use crate::error::CicError;
+
+    fn resolve(args: &[&str], env: &[(&str, &str)]) -> InvestmentParams {
+        let matches = build_cli().get_matches_from(args);
+        let env = |key: &str| {
+            env.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        };
+        let (params, unknown) = resolve_params(&matches, env).unwrap();
+        assert!(unknown.is_empty());
+        params
+    }
+
+    #[test]
+    fn test_resolve_params_precedence() {
+        let path = std::env::temp_dir().join("cic_test_resolve_params.toml");
+        std::fs::write(&path, "rate = 7.0\nyears = 10\nprincipal = 500.0\n").unwrap();
+        let config = path.to_str().unwrap();
+
+        // Built-in defaults.
+        let params = resolve(&["cic"], &[]);
+        assert_eq!((params.rate, params.years, params.principal), (5.0, 5, 0.0));
+
+        // The config file overrides the defaults.
+        let params = resolve(&["cic", "--config", config], &[]);
+        assert_eq!(
+            (params.rate, params.years, params.principal),
+            (7.0, 10, 500.0)
+        );
+
+        // The environment overrides the config file.
+        let env = [("CIC_RATE", "8"), ("CIC_DEBT", "true")];
+        let params = resolve(&["cic", "--config", config], &env);
+        assert_eq!(
+            (params.rate, params.years, params.principal),
+            (8.0, 10, 500.0)
+        );
+        assert!(params.debt);
+
+        // The flags override the environment.
+        let params = resolve(&["cic", "--config", config, "-r", "9"], &env);
+        assert_eq!(
+            (params.rate, params.years, params.principal),
+            (9.0, 10, 500.0)
+        );
+
+        // So does the query, which replaces the individual flags.
+        let params = resolve(&["cic", "--config", config, "--query", "rate=9.5"], &env);
+        assert_eq!(
+            (params.rate, params.years, params.principal),
+            (9.5, 10, 500.0)
+        );
+
+        // The inflation, fee and tax rate follow the same order.
+        std::fs::write(&path, "inflation = 3.0\nfee = 1.0\ntax_rate = 20.0\n").unwrap();
+        let params = resolve(
+            &["cic", "--config", config, "--fee", "0.5"],
+            &[("CIC_TAX_RATE", "15")],
+        );
+        assert_eq!(
+            (params.inflation, params.fee, params.tax_rate),
+            (3.0, 0.5, 15.0)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rate_format_only_applies_to_the_rate_flag() {
+        let decimal = ["cic", "--rate-format", "decimal"];
+        assert_eq!(resolve(&decimal, &[]).rate, 5.0);
+        assert_eq!(resolve(&decimal, &[("CIC_RATE", "8")]).rate, 8.0);
+        let rate = [&decimal[..], &["-r", "0.25"]].concat();
+        assert_eq!(resolve(&rate, &[]).rate, 25.0);
+        let allocation = [&decimal[..], &["--allocation", "a:100:5"]].concat();
+        assert_eq!(resolve(&allocation, &[]).rate, 5.0);
+        let range = [&decimal[..], &["--rate-range", "4,6,8"]].concat();
+        assert_eq!(resolve(&range, &[]).rate, 6.0);
+    }
+
+    #[test]
+    fn test_invalid_numbers_are_rejected() {
+        for args in [
+            ["cic", "-r", "abc"],
+            ["cic", "-y", "two"],
+            ["cic", "-p", "1k"],
+            ["cic", "-c", "ten"],
+            ["cic", "--fee", "abc"],
+            ["cic", "--inflation", "abc"],
+            ["cic", "--tax-rate", "20%"],
+            ["cic", "--match-rate", "x"],
+            ["cic", "--match-cap", "x"],
+            ["cic", "--salary", "abc"],
+            ["cic", "--savings-rate", "abc"],
+            ["cic", "--salary-growth", "abc"],
+            ["cic", "--max-rate", "abc"],
+            ["cic", "--tax-rate", "500"],
+            ["cic", "--tax-rate", "101"],
+            ["cic", "-r", "NaN"],
+            ["cic", "-p", "inf"],
+            ["cic", "-p", "NaN"],
+            ["cic", "-c", "inf"],
+            ["cic", "--fee", "NaN"],
+            ["cic", "--inflation", "inf"],
+            ["cic", "--tax-rate", "NaN"],
+            ["cic", "--glide", "inf,4"],
+            ["cic", "--rate-range", "4,6,inf"],
+        ] {
+            let error = build_cli().try_get_matches_from(args).unwrap_err();
+            assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+        }
+    }
+
+    #[test]
+    fn test_resolve_params_errors() {
+        let matches = build_cli().get_matches_from(["cic", "--config", "/nonexistent/cic.toml"]);
+        let error = resolve_params(&matches, |_| None).unwrap_err();
+        assert!(error.starts_with("Failed to load config: "));
+
+        let matches = build_cli().get_matches_from(["cic"]);
+        let env = |key: &str| (key == "CIC_YEARS").then(|| "many".to_string());
+        let error = resolve_params(&matches, env).unwrap_err();
+        assert!(error.starts_with("Invalid environment variable: "));
+    }
+
+    #[test]
+    fn test_tax_rate_out_of_range_is_rejected_from_every_source() {
+        let path = std::env::temp_dir().join("cic_test_tax_rate_range.toml");
+        std::fs::write(&path, "tax_rate = 500.0\n").unwrap();
+        let config = path.to_str().unwrap();
+        let sources = [
+            resolve(&["cic", "--config", config], &[]),
+            resolve(&["cic"], &[("CIC_TAX_RATE", "500")]),
+            resolve(&["cic", "--query", "tax_rate=500"], &[]),
+        ];
+        std::fs::remove_file(&path).unwrap();
+
+        for params in sources {
+            assert_eq!(params.tax_rate, 500.0);
+            assert!(matches!(
+                Investment::from_params(params),
+                Err(CicError::Validation(_))
+            ));
+        }
+    }
+}
@@ -1,9 +1,352 @@
-use crate::server;
+use crate::args;
+use crate::error::CicError;
+use crate::params::InvestmentParams;
+use plotters::coord::Shift;
 use plotters::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of years an `Investment` can be calculated for.
+///
+/// This guards against absurd inputs (e.g. 2 billion years) that would otherwise try to allocate
+/// enormous amounts of memory.
+pub const MAX_YEARS: i32 = 1000;
+
+const YEARS_LIMIT_ERROR: &str = "Years must not exceed 1000";
+
+/// The annual rate (in %) above which `Investment::high_rate_warning` warns by default.
+pub const DEFAULT_MAX_RATE: f64 = 30.0;
+
+/// Describes how the `--rate` flag is written. Every rate of an `Investment` is a percentage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateFormat {
+    /// The rate is a percentage, e.g. `5` means 5%.
+    #[default]
+    Percent,
+    /// The rate is a decimal fraction, e.g. `0.05` means 5%.
+    Decimal,
+}
+
+impl std::str::FromStr for RateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "percent" => Ok(Self::Percent),
+            "decimal" => Ok(Self::Decimal),
+            _ => Err(format!(
+                "Invalid rate format: {} (expected percent or decimal)",
+                s
+            )),
+        }
+    }
+}
+
+/// Describes how interest is earned by an `Investment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterestMode {
+    /// Interest is earned on the whole balance, including the interest earned so far.
+    #[default]
+    Compound,
+    /// Interest is only earned on the principal and the contributions, never on interest.
+    Simple,
+}
+
+/// Describes how often the interest of an `Investment` is compounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompoundFrequency {
+    /// Interest is earned once a year on the balance at the start of the year, and the
+    /// contributions of the year are added at its end.
+    #[default]
+    Annual,
+    /// Interest is earned twice a year at half the annual rate, as with most bonds. The
+    /// contributions of each half are added at its end.
+    #[serde(rename = "semi-annual")]
+    SemiAnnual,
+    /// Interest is earned every month at a twelfth of the annual rate, and every monthly
+    /// contribution is added at the end of its month.
+    Monthly,
+}
+
+impl CompoundFrequency {
+    /// All compound frequencies, from the least to the most frequent.
+    pub const ALL: [CompoundFrequency; 3] = [Self::Annual, Self::SemiAnnual, Self::Monthly];
+
+    /// Returns the number of compounding periods per year, e.g. 12 for `Monthly`.
+    pub fn periods_per_year(&self) -> u32 {
+        match self {
+            Self::Annual => 1,
+            Self::SemiAnnual => 2,
+            Self::Monthly => 12,
+        }
+    }
+}
+
+/// Describes how often the contribution of an `Investment` is made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContributionFrequency {
+    /// The contribution is made once a month, 12 times a year.
+    #[default]
+    Monthly,
+    /// The contribution is made every two weeks, e.g. from every paycheck, 26 times a year.
+    BiWeekly,
+}
+
+impl ContributionFrequency {
+    /// All contribution frequencies, from the least to the most frequent.
+    pub const ALL: [ContributionFrequency; 2] = [Self::Monthly, Self::BiWeekly];
+
+    /// Returns the number of contributions per year, e.g. 26 for `BiWeekly`.
+    pub fn contributions_per_year(&self) -> u32 {
+        match self {
+            Self::Monthly => 12,
+            Self::BiWeekly => 26,
+        }
+    }
+
+    /// Returns the number of contributions made in the given month (1 to 12).
+    ///
+    /// Bi-weekly contributions are spread evenly over the year, so January and July get three
+    /// and the other months two.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::ContributionFrequency;
+    ///
+    /// assert_eq!(ContributionFrequency::BiWeekly.contributions_in_month(1), 3);
+    /// assert_eq!(ContributionFrequency::BiWeekly.contributions_in_month(2), 2);
+    /// ```
+    pub fn contributions_in_month(&self, month: u32) -> u32 {
+        let per_year = self.contributions_per_year();
+        (0..per_year)
+            .filter(|&contribution| contribution * 12 / per_year + 1 == month)
+            .count() as u32
+    }
+}
+
+impl std::str::FromStr for ContributionFrequency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "monthly" => Ok(Self::Monthly),
+            "bi-weekly" => Ok(Self::BiWeekly),
+            _ => Err(format!(
+                "Invalid contribution frequency: {} (expected monthly or bi-weekly)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for CompoundFrequency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "annual" => Ok(Self::Annual),
+            "semi-annual" => Ok(Self::SemiAnnual),
+            "monthly" => Ok(Self::Monthly),
+            _ => Err(format!(
+                "Invalid compound frequency: {} (expected annual, semi-annual or monthly)",
+                s
+            )),
+        }
+    }
+}
+
+/// Describes how an `Investment` is taxed, see `Investment::tax_rate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountType {
+    /// The interest is taxed every year it is earned, e.g. a brokerage account.
+    #[default]
+    Taxable,
+    /// The growth is untaxed, but the whole balance is taxed when withdrawn, e.g. a traditional
+    /// IRA or 401(k).
+    TaxDeferred,
+    /// Neither the growth nor the withdrawal is taxed, e.g. a Roth IRA.
+    TaxFree,
+}
+
+impl AccountType {
+    /// All account types, in the order they are compared.
+    pub const ALL: [AccountType; 3] = [Self::Taxable, Self::TaxDeferred, Self::TaxFree];
+
+    /// Returns the name used on the command line, e.g. `tax-free`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Taxable => "taxable",
+            Self::TaxDeferred => "tax-deferred",
+            Self::TaxFree => "tax-free",
+        }
+    }
+}
+
+impl std::str::FromStr for AccountType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|account_type| account_type.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid account type: {} (expected taxable, tax-deferred or tax-free)",
+                    s
+                )
+            })
+    }
+}
+
+/// A minimum-balance interest tier: the part of the balance up to `threshold` earns `low_rate`,
+/// and the part above it earns `high_rate`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateTier {
+    /// The balance from which the higher rate is paid.
+    pub threshold: f64,
+    /// The annual interest rate of the balance up to the threshold, written like `rate`.
+    pub low_rate: f64,
+    /// The annual interest rate of the balance above the threshold, written like `rate`.
+    pub high_rate: f64,
+}
+
+impl std::str::FromStr for RateTier {
+    type Err = String;
+
+    /// Parses a tier in the form `THRESHOLD:LOW_RATE:HIGH_RATE`, e.g. `10000:1:4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 3 {
+            return Err(format!(
+                "Invalid tier: {} (expected THRESHOLD:LOW_RATE:HIGH_RATE)",
+                s
+            ));
+        }
+        let parse = |field: &str, value: &str| -> Result<f64, String> {
+            match value.trim().parse::<f64>() {
+                Ok(v) if v >= 0.0 => Ok(v),
+                _ => Err(format!("Invalid {} in tier {}: {}", field, s, value)),
+            }
+        };
+        Ok(Self {
+            threshold: parse("threshold", parts[0])?,
+            low_rate: parse("low rate", parts[1])?,
+            high_rate: parse("high rate", parts[2])?,
+        })
+    }
+}
+
+/// One asset class of an `Allocation`, e.g. 60% in stocks expected to return 8%.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetClass {
+    /// The name of the asset class, e.g. `stocks`.
+    pub name: String,
+    /// The share of the investment in this asset class, as a percentage.
+    pub weight: f64,
+    /// The expected annual rate of the asset class, written like `rate`.
+    pub rate: f64,
+}
+
+impl std::fmt::Display for AssetClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}% at {}%", self.name, self.weight, self.rate)
+    }
+}
+
+/// An asset allocation whose blended rate is used as the rate of an investment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Allocation {
+    /// The asset classes. Their weights add up to 100%.
+    pub assets: Vec<AssetClass>,
+}
+
+impl Allocation {
+    /// The amount (in percentage points) by which the weights may miss 100%, e.g. for thirds.
+    pub const WEIGHT_TOLERANCE: f64 = 0.01;
+
+    /// Returns the expected rate of the allocation, the average of the rates weighted by the
+    /// weights.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Allocation;
+    ///
+    /// let allocation: Allocation = "stocks:60:8,bonds:40:3".parse().unwrap();
+    /// assert_eq!(allocation.blended_rate(), 6.0);
+    /// ```
+    pub fn blended_rate(&self) -> f64 {
+        self.assets
+            .iter()
+            .map(|asset| asset.weight * asset.rate)
+            .sum::<f64>()
+            / 100.0
+    }
+}
+
+impl std::fmt::Display for Allocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let assets: Vec<String> = self.assets.iter().map(ToString::to_string).collect();
+        write!(f, "{}", assets.join(", "))
+    }
+}
+
+impl std::str::FromStr for Allocation {
+    type Err = String;
+
+    /// Parses comma-separated asset classes in the form `NAME:WEIGHT:RATE`, e.g.
+    /// `stocks:60:8,bonds:40:3`. The weights must add up to 100.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let assets = s
+            .split(',')
+            .map(|asset| {
+                let parts: Vec<&str> = asset.split(':').map(str::trim).collect();
+                let [name, weight, rate] = parts[..] else {
+                    return Err(format!(
+                        "Invalid asset class: {} (expected NAME:WEIGHT:RATE)",
+                        asset
+                    ));
+                };
+                if name.is_empty() {
+                    return Err(format!("Missing name in asset class {}", asset));
+                }
+                let weight = match weight.parse::<f64>() {
+                    Ok(weight) if weight >= 0.0 => weight,
+                    _ => return Err(format!("Invalid weight in asset class {}", asset)),
+                };
+                let rate = match rate.parse::<f64>() {
+                    Ok(rate) if rate.is_finite() => rate,
+                    _ => return Err(format!("Invalid rate in asset class {}", asset)),
+                };
+                Ok(AssetClass {
+                    name: name.into(),
+                    weight,
+                    rate,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let total: f64 = assets.iter().map(|asset| asset.weight).sum();
+        if (total - 100.0).abs() > Self::WEIGHT_TOLERANCE {
+            return Err(format!(
+                "The weights of the allocation {} add up to {}%, not 100%",
+                s, total
+            ));
+        }
+        Ok(Self { assets })
+    }
+}
 
 /// Represents an investment with principal, contribution, interest rate, and duration.
-#[derive(Debug)]
+///
+/// An `Investment` can be serialized, e.g. to echo the input of a JSON report. Fields missing
+/// when deserializing take their default values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Investment {
     /// The initial amount of money invested.
     pub principal: f64,
@@ -13,6 +356,118 @@ pub struct Investment {
     pub rate: f64,
     /// The number of years the money is invested for.
     pub years: i32,
+    /// The annual salary in the first year. If greater than 0, the monthly contribution is derived
+    /// from the salary and `contribution` is ignored.
+    pub salary: f64,
+    /// The percentage of the salary that is saved each year.
+    pub savings_rate: f64,
+    /// The annual salary growth as a percentage.
+    pub salary_growth: f64,
+    /// The annual interest rate of each year as a percentage. If not empty, year N uses `variable_rates[N - 1]`, and years beyond the end fall back to `rate`.
+    pub variable_rates: Vec<f64>,
+    /// The annual inflation rate as a percentage, used for the inflation-adjusted amounts.
+    pub inflation: f64,
+    /// The annual inflation rate of each year as a percentage, e.g. historical CPI figures. If not
+    /// empty, year N uses `inflation_rates[N - 1]`, and years beyond the end fall back to
+    /// `inflation`.
+    pub inflation_rates: Vec<f64>,
+    /// If `true`, the fixed monthly contribution grows with `inflation` every year, so that it
+    /// stays constant in today's money.
+    pub index_contributions_to_inflation: bool,
+    /// Whether interest is compounded or simple.
+    pub interest_mode: InterestMode,
+    /// How often interest is earned.
+    pub compound_frequency: CompoundFrequency,
+    /// How often the contribution is made. `contribution` is the amount of each contribution.
+    pub contribution_frequency: ContributionFrequency,
+    /// Whether the contributions of a compounding period earn half of its interest, as if they
+    /// were made in its middle. Otherwise, they are added at its end and earn from the next one.
+    pub mid_year_contribution: bool,
+    /// The annual fee, e.g. a fund's expense ratio, as a percentage subtracted from the rate of
+    /// every year.
+    pub fee: f64,
+    /// If `true`, the rate is already the net return after fees, so `fee` is ignored to avoid
+    /// counting it twice.
+    pub rate_is_net: bool,
+    /// How the investment is taxed. Only a `Taxable` account pays tax in `yearly_summary`.
+    pub account_type: AccountType,
+    /// The tax rate as a percentage. A `Taxable` account pays it on the interest of every year, a
+    /// `TaxDeferred` account on the withdrawal, see `after_tax_amount`.
+    pub tax_rate: f64,
+    /// The number of months contributions are made for, counted from the start. `None` means
+    /// contributions are made in every year; afterwards, the balance is held and keeps earning.
+    pub contribution_months: Option<u32>,
+    /// Whether the investment models paying down a debt. The principal is then the negative balance
+    /// owed, interest accrues on it, and the contributions are the payments; see `payoff_year`.
+    pub debt: bool,
+    /// The months of the year (1 to 12) in which no contribution is made, e.g. `[6, 7, 8]` for a
+    /// summer gap every year.
+    pub skip_months: Vec<u32>,
+    /// The first and last year (inclusive) of a contribution holiday, e.g. `(3, 4)` for a pause
+    /// during parental leave. No contributions are made in these years, but interest accrues.
+    pub pause_years: Option<(i32, i32)>,
+    /// Amounts added at the end of the given years, as `(year, amount)`, e.g. `(5, 10000.0)` for an
+    /// inheritance. A negative amount is withdrawn, e.g. for a planned expense. They count as
+    /// contributions of their year, but are not matched by an employer.
+    pub lump_sums: Vec<(i32, f64)>,
+    /// A minimum-balance tier that replaces `rate` and `variable_rates`, see `gross_interest`.
+    pub tier: Option<RateTier>,
+    /// The percentage of the contributions matched by an employer, e.g. `50` for a 50% match.
+    pub match_rate: f64,
+    /// The maximum employer match per year. `f64::INFINITY` means the match is not capped.
+    #[serde(with = "infinity_as_null")]
+    pub match_cap: f64,
+}
+
+/// (De)serializes an unbounded `f64` as `null`, since JSON has no infinity.
+mod infinity_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::INFINITY))
+    }
+}
+
+impl Default for Investment {
+    fn default() -> Self {
+        Self {
+            principal: 0.0,
+            contribution: 1.0,
+            rate: 5.0,
+            years: 5,
+            salary: 0.0,
+            savings_rate: 10.0,
+            salary_growth: 0.0,
+            variable_rates: Vec::new(),
+            inflation: 0.0,
+            inflation_rates: Vec::new(),
+            index_contributions_to_inflation: false,
+            interest_mode: InterestMode::Compound,
+            compound_frequency: CompoundFrequency::Annual,
+            contribution_frequency: ContributionFrequency::Monthly,
+            mid_year_contribution: false,
+            fee: 0.0,
+            rate_is_net: false,
+            account_type: AccountType::Taxable,
+            tax_rate: 0.0,
+            contribution_months: None,
+            debt: false,
+            skip_months: Vec::new(),
+            pause_years: None,
+            lump_sums: Vec::new(),
+            tier: None,
+            match_rate: 0.0,
+            match_cap: f64::INFINITY,
+        }
+    }
 }
 
 impl Investment {
@@ -31,32 +486,84 @@ impl Investment {
     /// ```
     /// use cic::calculations::Investment;
     ///
-    /// let matches = clap::Command::new("investment")
-    ///     .arg(clap::Arg::new("principal").default_value("0"))
-    ///     .arg(clap::Arg::new("contribution").default_value("1"))
-    ///     .arg(clap::Arg::new("rate").default_value("5"))
-    ///     .arg(clap::Arg::new("years").default_value("5"))
-    ///     .get_matches();
+    /// let matches = cic::args::build_cli().get_matches_from(["cic", "--rate", "5", "--years", "5"]);
     /// let investment = Investment::from_matches(&matches);
     /// ```
     pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        let defaults = Self::default();
+        let years = matches
+            .try_get_one("years")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(0);
+        let variable_rates = matches
+            .try_get_one::<(f64, f64)>("glide")
+            .ok()
+            .flatten()
+            .map(|&(start, end)| glide_path(start, end, years))
+            .unwrap_or_default();
         Self {
             principal: matches
-                .get_one::<String>("principal")
-                .and_then(|s| s.parse().ok())
+                .try_get_one("principal")
+                .ok()
+                .flatten()
+                .copied()
                 .unwrap_or(0.0),
             contribution: matches
-                .get_one::<String>("contribution")
-                .and_then(|s| s.parse().ok())
+                .try_get_one("contribution")
+                .ok()
+                .flatten()
+                .copied()
                 .unwrap_or(1.0),
-            rate: matches
-                .get_one::<String>("rate")
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(5.0),
-            years: matches
-                .get_one::<String>("years")
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0),
+            rate: args::get_rate(matches).unwrap_or(5.0),
+            years,
+            salary: args::get_number(matches, "salary").unwrap_or(defaults.salary),
+            savings_rate: args::get_number(matches, "savings-rate")
+                .unwrap_or(defaults.savings_rate),
+            salary_growth: args::get_number(matches, "salary-growth")
+                .unwrap_or(defaults.salary_growth),
+            variable_rates,
+            inflation: args::get_number(matches, "inflation").unwrap_or(defaults.inflation),
+            inflation_rates: defaults.inflation_rates,
+            index_contributions_to_inflation: args::get_flag(
+                matches,
+                "index-contributions-to-inflation",
+            ),
+            interest_mode: if args::get_flag(matches, "simple") {
+                InterestMode::Simple
+            } else {
+                InterestMode::Compound
+            },
+            compound_frequency: args::get_value(matches, "compound-frequency").unwrap_or_default(),
+            contribution_frequency: args::get_value(matches, "contribution-frequency")
+                .unwrap_or_default(),
+            fee: args::get_number(matches, "fee").unwrap_or(defaults.fee),
+            rate_is_net: args::get_flag(matches, "rate-is-net"),
+            mid_year_contribution: args::get_flag(matches, "mid-year-contribution"),
+            account_type: args::get_value(matches, "account-type").unwrap_or_default(),
+            tax_rate: args::get_number(matches, "tax-rate").unwrap_or(defaults.tax_rate),
+            contribution_months: defaults.contribution_months,
+            debt: args::get_flag(matches, "debt"),
+            skip_months: matches
+                .try_get_many::<u32>("skip-months")
+                .ok()
+                .flatten()
+                .map(|months| months.copied().collect())
+                .unwrap_or_default(),
+            pause_years: matches
+                .try_get_one::<(i32, i32)>("pause-years")
+                .ok()
+                .flatten()
+                .copied(),
+            lump_sums: Vec::new(),
+            tier: matches
+                .try_get_one::<RateTier>("tier")
+                .ok()
+                .flatten()
+                .copied(),
+            match_rate: args::get_number(matches, "match-rate").unwrap_or(defaults.match_rate),
+            match_cap: args::get_number(matches, "match-cap").unwrap_or(defaults.match_cap),
         }
     }
 
@@ -69,22 +576,27 @@ impl Investment {
     ///
     /// # Returns
     ///
-    /// Returns a `Result<Self, &'static str>`. On success, returns an `Investment` instance initialized with
-    /// the provided parameters. If any of the values are negative, returns an error with a message indicating
-    /// that negative values are not allowed.
+    /// Returns a `Result<Self, CicError>`. On success, returns an `Investment` instance initialized with
+    /// the provided parameters. If any of the values are negative, returns a `CicError::Validation`
+    /// indicating that negative values are not allowed.
     ///
     /// # Errors
     ///
     /// Returns an error if any of the following conditions are met:
-    /// - `params.principal` is less than 0.0
+    /// - `params.principal`, `params.contribution` or `params.rate` is not finite
+    /// - `params.principal` is less than 0.0, unless `params.debt` is set
     /// - `params.contribution` is less than 0.0
     /// - `params.rate` is less than 0.0
     /// - `params.years` is less than 0
+    /// - `params.years` is greater than `MAX_YEARS`
+    /// - `params.inflation` is -100 or below, or not finite
+    /// - `params.fee` is negative or not finite, which would raise the rate
+    /// - `params.tax_rate` is not between 0 and 100
     ///
     /// # Example
     ///
     /// ```
-    /// use cic::server::InvestmentParams;
+    /// use cic::params::InvestmentParams;
     /// use cic::calculations::Investment;
     ///
     /// let params = InvestmentParams {
@@ -92,6 +604,7 @@ impl Investment {
     ///     contribution: 100.0,
     ///     rate: 5.0,
     ///     years: 10,
+    ///     ..Default::default()
     /// };
     ///
     /// match Investment::from_params(params) {
@@ -103,226 +616,5491 @@ impl Investment {
     /// # Panics
     ///
     /// This function does not panic but returns an error if invalid values are provided.
-    pub fn from_params(params: server::InvestmentParams) -> Result<Self, &'static str> {
-        if params.principal < 0.0
+    pub fn from_params(params: InvestmentParams) -> Result<Self, CicError> {
+        for (name, value) in [
+            ("principal", params.principal),
+            ("contribution", params.contribution),
+            ("rate", params.rate),
+        ] {
+            if !value.is_finite() {
+                return Err(CicError::Validation(format!(
+                    "The {} must be a finite number",
+                    name
+                )));
+            }
+        }
+        if (params.principal < 0.0 && !params.debt)
             || params.contribution < 0.0
             || params.rate < 0.0
             || params.years < 0
         {
-            return Err("Negative values are not allowed");
+            return Err(CicError::Validation(
+                "Negative values are not allowed".into(),
+            ));
+        }
+        if params.years > MAX_YEARS {
+            return Err(CicError::Validation(YEARS_LIMIT_ERROR.into()));
+        }
+        // Prices cannot fall by 100% or more, and the real amounts divide by the inflation factor.
+        if !(params.inflation > -100.0 && params.inflation.is_finite()) {
+            return Err(CicError::Validation(
+                "The inflation rate must be a number above -100".into(),
+            ));
+        }
+        if !(params.fee >= 0.0 && params.fee.is_finite()) {
+            return Err(CicError::Validation(
+                "The fee must be a non-negative number".into(),
+            ));
+        }
+        if !(0.0..=100.0).contains(&params.tax_rate) {
+            return Err(CicError::Validation(
+                "The tax rate must be between 0 and 100".into(),
+            ));
         }
         Ok(Self {
             principal: params.principal,
             contribution: params.contribution,
             rate: params.rate,
             years: params.years,
+            compound_frequency: params.compound_frequency,
+            debt: params.debt,
+            inflation: params.inflation,
+            fee: params.fee,
+            tax_rate: params.tax_rate,
+            account_type: params.account_type,
+            ..Self::default()
         })
     }
 
-    /// Generates a yearly summary of the investment.
+    /// Returns the annual interest rate as a decimal fraction.
     ///
-    /// # Returns
+    /// # Example
     ///
-    /// Returns a vector of `YearlySummary` structs, each representing the investment's status at the end of each year.
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { rate: 5.0, ..Default::default() };
+    /// assert_eq!(investment.rate_per_period(), 0.05);
+    /// ```
+    pub fn rate_per_period(&self) -> f64 {
+        self.to_decimal(self.rate)
+    }
+
+    /// Returns the interest rate of the given year as a decimal fraction, after fees.
+    ///
+    /// The rate is taken from `variable_rates` if it covers the year, otherwise `rate` is used.
+    /// The fee is subtracted from it, see `fee_per_period`.
+    ///
+    /// # Arguments
+    ///
+    /// * `year` - The year of the investment, starting at 1.
+    pub fn rate_for_year(&self, year: i32) -> f64 {
+        let rate = match usize::try_from(year - 1)
+            .ok()
+            .and_then(|i| self.variable_rates.get(i))
+        {
+            Some(&rate) => self.to_decimal(rate),
+            None => self.rate_per_period(),
+        };
+        rate - self.fee_per_period()
+    }
+
+    /// Returns the interest rate of the given year as a decimal fraction, after fees and the
+    /// yearly tax of a `Taxable` account.
+    ///
+    /// With annual compounding and no `tier`, this is the rate the balance grows at in the year.
+    ///
+    /// # Arguments
+    ///
+    /// * `year` - The year of the investment, starting at 1.
     ///
     /// # Example
     ///
     /// ```
-    /// use cic::calculations::YearlySummary;
     /// use cic::calculations::Investment;
     ///
-    /// let investment = Investment {
-    ///     principal: 1000.0,
-    ///     contribution: 100.0,
-    ///     rate: 5.0,
-    ///     years: 10,
-    /// };
-    /// let summary = investment.yearly_summary();
+    /// let investment = Investment { rate: 7.0, fee: 2.0, tax_rate: 20.0, ..Default::default() };
+    /// assert!((investment.net_rate_for_year(1) - 0.04).abs() < 1e-12);
     /// ```
-    pub fn yearly_summary(&self) -> Vec<YearlySummary> {
-        let rate_per_period = self.rate / 100.0;
-        let mut amount = self.principal;
-        let mut total_interest = 0.0;
-        let mut summary = Vec::with_capacity(self.years as usize);
-
-        for year in 1..=self.years {
-            let annual_contribution = self.contribution * 12.0;
-            let annual_interest = amount * rate_per_period;
-            total_interest += annual_interest;
+    pub fn net_rate_for_year(&self, year: i32) -> f64 {
+        let rate = self.rate_for_year(year);
+        rate - self.tax_on_interest(rate)
+    }
 
-            amount += annual_contribution + annual_interest;
+    /// Returns the interest earned on a balance in one of `periods` equal periods of the given year,
+    /// before tax.
+    ///
+    /// Without a `tier`, this is the balance times `rate_for_year`. With a tier, the part of the
+    /// balance up to the threshold earns the low rate and the part above it the high rate, both
+    /// after fees.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::{Investment, RateTier};
+    ///
+    /// let tier = RateTier { threshold: 10000.0, low_rate: 1.0, high_rate: 4.0 };
+    /// let investment = Investment { tier: Some(tier), ..Default::default() };
+    /// assert_eq!(investment.gross_interest(1, 5000.0, 1), 50.0);
+    /// assert_eq!(investment.gross_interest(1, 15000.0, 1), 100.0 + 200.0);
+    /// ```
+    pub fn gross_interest(&self, year: i32, balance: f64, periods: u32) -> f64 {
+        let annual = match self.tier {
+            Some(tier) => {
+                let fee = self.fee_per_period();
+                let below = balance.min(tier.threshold);
+                let above = (balance - tier.threshold).max(0.0);
+                below * (self.to_decimal(tier.low_rate) - fee)
+                    + above * (self.to_decimal(tier.high_rate) - fee)
+            }
+            None => balance * self.rate_for_year(year),
+        };
+        annual / f64::from(periods)
+    }
 
-            summary.push(YearlySummary {
-                year,
-                principal: self.principal,
-                annual_contribution,
-                total_contribution: self.contribution * 12.0 * year as f64,
-                annual_interest,
-                total_interest,
-                total_amount: amount,
-            });
+    /// Returns the annual fee as a decimal fraction, or 0 if `rate_is_net` is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let gross = Investment { rate: 7.0, fee: 1.0, ..Default::default() };
+    /// assert!((gross.rate_for_year(1) - 0.06).abs() < 1e-12);
+    /// let net = Investment { rate_is_net: true, ..gross };
+    /// assert_eq!(net.fee_per_period(), 0.0);
+    /// ```
+    pub fn fee_per_period(&self) -> f64 {
+        if self.rate_is_net {
+            0.0
+        } else {
+            self.fee / 100.0
         }
-        summary
     }
-}
 
-/// Represents a summary of the investment at the end of a given year.
-#[derive(Debug, Serialize)]
-pub struct YearlySummary {
-    /// The year for which the summary is provided.
-    pub year: i32,
-    /// The initial principal amount of the investment.
-    pub principal: f64,
-    /// The total contribution made during the year.
-    pub annual_contribution: f64,
-    /// The cumulative total contribution up to the end of the year.
-    pub total_contribution: f64,
-    /// The interest earned during the year.
-    pub annual_interest: f64,
-    /// The cumulative total interest earned up to the end of the year.
-    pub total_interest: f64,
-    /// The total amount of money at the end of the year.
-    pub total_amount: f64,
-}
+    /// Returns a warning if a fee is given although the rate is already net of fees.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(message)` if the fee is ignored, otherwise `None`.
+    pub fn fee_warning(&self) -> Option<String> {
+        (self.rate_is_net && self.fee != 0.0).then(|| {
+            format!(
+                "Warning: the fee of {}% is ignored because the rate is already net of fees",
+                self.fee
+            )
+        })
+    }
 
-/// Plots the investment summary as a line chart.
-///
-/// # Arguments
-///
-/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
-///
-/// # Returns
-///
-/// Returns `Result<(), Box<dyn std::error::Error>>` indicating success or failure of the plotting process.
-///
-/// # Example
-///
-/// ```no_run
-/// use cic::calculations::plot_summary;
-/// use cic::calculations::YearlySummary;
-///
-/// let summary = vec![
-///     YearlySummary { year: 1, principal: 1000.0, annual_contribution: 1200.0, total_contribution: 1200.0, annual_interest: 50.0, total_interest: 50.0, total_amount: 2150.0 },
-///     // Add more summaries here
-/// ];
-/// plot_summary(&summary).expect("Failed to plot summary");
-/// ```
-pub fn plot_summary(summary: &[YearlySummary]) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::new("plot.png", (600, 400)).into_drawing_area();
-    root.fill(&WHITE)?;
+    fn to_decimal(&self, rate: f64) -> f64 {
+        rate / 100.0
+    }
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Investment Summary", ("sans-serif", 30).into_font())
-        .x_label_area_size(35)
-        .y_label_area_size(100)
-        .margin(20)
-        .build_cartesian_2d(
-            1..summary.len(),
-            0.0..summary
-                .iter()
-                .map(|s| s.total_amount)
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap(),
-        )?;
+    /// Returns the monthly contribution made during the given year.
+    ///
+    /// If a salary is set, the contribution is `savings_rate` percent of the salary, which grows by
+    /// `salary_growth` percent every year. Otherwise, the fixed `contribution` is returned, grown by
+    /// `inflation` every year if `index_contributions_to_inflation` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `year` - The year of the investment, starting at 1.
+    pub fn monthly_contribution(&self, year: i32) -> f64 {
+        if self.salary > 0.0 {
+            let salary = self.salary * (1.0 + self.salary_growth / 100.0).powi(year - 1);
+            return salary * self.savings_rate / 100.0 / 12.0;
+        }
+        if self.index_contributions_to_inflation {
+            return self.contribution * self.inflation_factor(year - 1);
+        }
+        self.contribution
+    }
 
-    chart
-        .configure_mesh()
-        .x_desc("Year")
-        .y_desc("Amount")
-        .draw()?;
+    /// Returns the amount of each contribution made during the given year.
+    ///
+    /// This is the `monthly_contribution`, except that a salary-based contribution is split over
+    /// the contributions of the `contribution_frequency`, so that the year's savings stay the same.
+    pub fn contribution_per_period(&self, year: i32) -> f64 {
+        match self.contribution_frequency {
+            ContributionFrequency::BiWeekly if self.salary > 0.0 => {
+                self.monthly_contribution(year) * 12.0
+                    / f64::from(self.contribution_frequency.contributions_per_year())
+            }
+            _ => self.monthly_contribution(year),
+        }
+    }
 
-    let years: Vec<usize> = summary.iter().map(|s| s.year as usize).collect();
-    let mut principal_and_contribution: Vec<f64> = Vec::new();
-    let mut accumulated_principal_and_contribution = 0.0;
-    for s in summary {
-        accumulated_principal_and_contribution += s.annual_contribution;
-        principal_and_contribution.push(s.principal + accumulated_principal_and_contribution);
+    /// Returns the total contribution made during the given year.
+    ///
+    /// This is 12 monthly or 26 bi-weekly contributions, or fewer for the `skip_months` and once
+    /// `contribution_months` runs out. It is 0 in the `pause_years`. The `lump_sums` of the year
+    /// are added on top.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { contribution: 100.0, contribution_months: Some(18), ..Default::default() };
+    /// assert_eq!(investment.annual_contribution(1), 1200.0);
+    /// assert_eq!(investment.annual_contribution(2), 600.0);
+    /// assert_eq!(investment.annual_contribution(3), 0.0);
+    ///
+    /// let summer_gap = Investment { contribution: 100.0, skip_months: vec![6, 7, 8], ..Default::default() };
+    /// assert_eq!(summer_gap.annual_contribution(1), 900.0);
+    ///
+    /// let holiday = Investment { contribution: 100.0, pause_years: Some((2, 3)), ..Default::default() };
+    /// assert_eq!(holiday.annual_contribution(2), 0.0);
+    /// assert_eq!(holiday.annual_contribution(4), 1200.0);
+    /// ```
+    pub fn annual_contribution(&self, year: i32) -> f64 {
+        self.monthly_deposits(year).iter().sum()
     }
-    let total_amount: Vec<f64> = summary.iter().map(|s| s.total_amount).collect();
 
-    chart
-        .draw_series(LineSeries::new(
-            years
-                .iter()
-                .zip(principal_and_contribution.iter())
-                .map(|(x, y)| (*x, *y)),
-            &RED,
-        ))?
-        .label("Principal + Contribution")
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], &RED));
+    /// Returns the amount deposited in each month of the given year.
+    ///
+    /// This is where all contribution modifiers meet: the `contribution_per_period` (salary,
+    /// inflation indexing) times the contributions made in the month (`contribution_frequency`,
+    /// `contribution_months`, `skip_months`, and `pause_years`), plus the `lump_sums` of the year
+    /// in December.
+    fn monthly_deposits(&self, year: i32) -> [f64; 12] {
+        let mut deposits = self.regular_deposits(year);
+        deposits[11] += self.lump_sum(year);
+        deposits
+    }
 
-    chart
-        .draw_series(LineSeries::new(
-            years.iter().zip(total_amount.iter()).map(|(x, y)| (*x, *y)),
-            &BLUE,
-        ))?
-        .label("Total Amount")
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 10, y)], &BLUE));
+    /// Returns the regular contributions made in each month of the given year, without the
+    /// `lump_sums`.
+    fn regular_deposits(&self, year: i32) -> [f64; 12] {
+        let per_period = self.contribution_per_period(year);
+        std::array::from_fn(|index| {
+            per_period * f64::from(self.contributions_in_month(year, index as u32 + 1))
+        })
+    }
 
-    chart
-        .configure_series_labels()
-        .position(SeriesLabelPosition::UpperLeft)
-        .draw()?;
+    /// Returns the sum of the `lump_sums` of the given year.
+    fn lump_sum(&self, year: i32) -> f64 {
+        self.lump_sums
+            .iter()
+            .filter(|&&(lump_sum_year, _)| lump_sum_year == year)
+            .map(|&(_, amount)| amount)
+            .sum()
+    }
 
-    Ok(())
-}
+    /// Returns the number of contributions made in the given month (1 to 12) of the given year.
+    fn contributions_in_month(&self, year: i32, month: u32) -> u32 {
+        if self.contributes_in_month(year, month) {
+            self.contribution_frequency.contributions_in_month(month)
+        } else {
+            0
+        }
+    }
+
+    /// Returns whether a contribution is made in the given month (1 to 12) of the given year.
+    fn contributes_in_month(&self, year: i32, month: u32) -> bool {
+        let elapsed = 12 * i64::from(year - 1) + i64::from(month);
+        self.contribution_months
+            .is_none_or(|months| elapsed <= i64::from(months))
+            && !self.skip_months.contains(&month)
+            && self
+                .pause_years
+                .is_none_or(|(start, end)| !(start..=end).contains(&year))
+    }
+
+    /// Returns the employer match paid during the given year.
+    ///
+    /// The match is `match_rate` percent of the year's regular contributions (without the
+    /// `lump_sums`), limited to `match_cap`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { contribution: 500.0, match_rate: 50.0, match_cap: 2000.0, ..Default::default() };
+    /// assert_eq!(investment.annual_match(1), 2000.0);
+    /// ```
+    pub fn annual_match(&self, year: i32) -> f64 {
+        (self.regular_deposits(year).iter().sum::<f64>() * self.match_rate / 100.0)
+            .min(self.match_cap)
+            .max(0.0)
+    }
+
+    /// Returns the minimum annual rate (in %) needed just to preserve purchasing power.
+    ///
+    /// Money invested at this rate keeps its value in today's money, so it equals the inflation
+    /// rate plus the fee (unless the rate is net of fees). Any lower rate makes the real value of
+    /// the invested money shrink.
+    pub fn breakeven_rate(&self) -> f64 {
+        self.inflation + self.fee_per_period() * 100.0
+    }
+
+    /// Returns the inflation rate (in %) of the given year.
+    ///
+    /// The rate is taken from `inflation_rates` if it covers the year, otherwise `inflation` is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `year` - The year of the investment, starting at 1.
+    pub fn inflation_for_year(&self, year: i32) -> f64 {
+        usize::try_from(year - 1)
+            .ok()
+            .and_then(|i| self.inflation_rates.get(i))
+            .copied()
+            .unwrap_or(self.inflation)
+    }
+
+    /// Returns the factor by which prices have grown after the given number of years of inflation.
+    ///
+    /// The inflation of every year is applied cumulatively, see `inflation_for_year`.
+    ///
+    /// # Arguments
+    ///
+    /// * `years` - The number of years of inflation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { inflation_rates: vec![10.0, 20.0], ..Default::default() };
+    /// assert!((investment.inflation_factor(2) - 1.1 * 1.2).abs() < 1e-12);
+    /// ```
+    pub fn inflation_factor(&self, years: i32) -> f64 {
+        if self.inflation_rates.is_empty() {
+            return (1.0 + self.inflation / 100.0).powi(years);
+        }
+        (1..=years)
+            .map(|year| 1.0 + self.inflation_for_year(year) / 100.0)
+            .product()
+    }
+
+    /// Converts a target in today's money into the nominal amount needed at the end of the
+    /// investment, by inflating it over `years` at `inflation`.
+    ///
+    /// Goal-seek functions such as `required_contribution` take nominal targets, so a target like
+    /// "$1M in today's money" has to be converted first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { inflation: 2.0, years: 2, ..Default::default() };
+    /// assert!((investment.nominal_target(1000.0) - 1040.4).abs() < 1e-9);
+    /// ```
+    pub fn nominal_target(&self, todays_target: f64) -> f64 {
+        todays_target * self.inflation_factor(self.years)
+    }
+
+    /// Calculates the simple overall return of the investment as a percentage.
+    ///
+    /// The return is `(final - total_invested) / total_invested * 100`, where `total_invested` is the
+    /// principal plus all contributions.
+    ///
+    /// # Returns
+    ///
+    /// Returns the total return in percent, or `0.0` if nothing was invested or the summary cannot be
+    /// calculated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { principal: 1000.0, contribution: 0.0, rate: 10.0, years: 1, ..Default::default() };
+    /// assert!((investment.total_return_pct() - 10.0).abs() < 1e-9);
+    /// ```
+    pub fn total_return_pct(&self) -> f64 {
+        self.yearly_summary()
+            .ok()
+            .and_then(|summary| summary.last().map(YearlySummary::total_return_pct))
+            .unwrap_or(0.0)
+    }
+
+    /// Calculates the annualized time-weighted return (TWR) of the investment.
+    ///
+    /// The TWR chains the return of every compounding period, measured on the balance at its
+    /// start (plus half of its deposits with `mid_year_contribution`), so it only reflects how well
+    /// the money was invested. Contributions and matches are external cash flows and do not change
+    /// it, no matter when or how much is added.
+    ///
+    /// # Returns
+    ///
+    /// Returns the TWR per year as a decimal fraction (e.g. `0.05` for 5%).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no period starts with money invested, or if `years` is greater than
+    /// `MAX_YEARS`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { principal: 1000.0, contribution: 100.0, rate: 5.0, years: 3, ..Default::default() };
+    /// assert!((investment.time_weighted_return().unwrap() - 0.05).abs() < 1e-9);
+    /// ```
+    pub fn time_weighted_return(&self) -> Result<f64, CicError> {
+        if self.years > MAX_YEARS {
+            return Err(CicError::Validation(YEARS_LIMIT_ERROR.into()));
+        }
+        let mut years = self.iter_years();
+        years.by_ref().for_each(drop);
+        if years.invested_periods == 0 {
+            return Err(CicError::Calculation(
+                "The time-weighted return is undefined when nothing is invested".into(),
+            ));
+        }
+        let periods_per_year = self.compound_frequency.periods_per_year();
+        Ok(years
+            .growth
+            .powf(f64::from(periods_per_year) / f64::from(years.invested_periods))
+            - 1.0)
+    }
+
+    /// Calculates the money-weighted return (MWR) of the investment.
+    ///
+    /// The MWR is the internal rate of return of the cash flows: the principal at the start, the
+    /// contributions when they are credited in their compounding period, the matches at the
+    /// end of every year, and the final balance. Unlike the time-weighted return, it gives more
+    /// weight to years with more money invested, so it depends on when the money was added.
+    ///
+    /// # Returns
+    ///
+    /// Returns the MWR per year as a decimal fraction (e.g. `0.05` for 5%).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing is invested before the final period, if the balance is lost
+    /// entirely, or if the summary cannot be calculated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { principal: 1000.0, contribution: 100.0, rate: 5.0, years: 3, ..Default::default() };
+    /// assert!((investment.money_weighted_return().unwrap() - 0.05).abs() < 1e-9);
+    /// ```
+    pub fn money_weighted_return(&self) -> Result<f64, CicError> {
+        let summary = self.yearly_summary()?;
+        let end = f64::from(self.years);
+        let flows = self.cash_flows(&summary);
+        let invested = flows.iter().any(|&(time, flow)| time < end && flow > 0.0);
+        let Some(last) = summary.last().filter(|_| invested) else {
+            return Err(CicError::Calculation(
+                "The money-weighted return is undefined when nothing is invested".into(),
+            ));
+        };
+        let final_amount = last.total_amount;
+
+        // The future value of the cash flows at the end, compounded by `growth` = 1 + rate per
+        // year, minus the final balance. It strictly grows with `growth`, so its root is unique.
+        let excess = |growth: f64| {
+            let invested: f64 = flows
+                .iter()
+                .map(|&(time, flow)| flow * growth.powf(end - time))
+                .sum();
+            invested - final_amount
+        };
+        if excess(0.0) > 0.0 {
+            return Err(CicError::Calculation(
+                "The money-weighted return is undefined when the balance is lost".into(),
+            ));
+        }
+        let mut high = 2.0;
+        while excess(high) < 0.0 {
+            high *= 2.0;
+            if !high.is_finite() {
+                return Err(CicError::Calculation(
+                    "The money-weighted return is too large to calculate".into(),
+                ));
+            }
+        }
+
+        let mut low = 0.0;
+        while high - low > 1e-12 {
+            let middle = (low + high) / 2.0;
+            // A large growth factor has adjacent floats more than 1e-12 apart.
+            if middle <= low || middle >= high {
+                break;
+            }
+            if excess(middle) < 0.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Ok((low + high) / 2.0 - 1.0)
+    }
+
+    /// Returns the cash flows paid into the investment as `(time, amount)`, with the time in years.
+    ///
+    /// The principal is paid at 0, the deposits of every compounding period at its end, or in its
+    /// middle with `mid_year_contribution`, as in `iter_years`, and the match at the end of every
+    /// year.
+    fn cash_flows(&self, summary: &[YearlySummary]) -> Vec<(f64, f64)> {
+        let periods = self.compound_frequency.periods_per_year();
+        let months = (12 / periods) as usize;
+        let offset = if self.mid_year_contribution { 0.5 } else { 1.0 };
+        let mut flows = vec![(0.0, self.principal)];
+        for s in summary {
+            let start = f64::from(s.year - 1);
+            let deposits = self.monthly_deposits(s.year);
+            for (period, deposit) in deposits.chunks(months).enumerate() {
+                let time = start + (period as f64 + offset) / f64::from(periods);
+                flows.push((time, deposit.iter().sum()));
+            }
+            flows.push((f64::from(s.year), s.annual_match));
+        }
+        flows
+    }
+
+    /// Returns the tax paid on the interest earned in a year.
+    ///
+    /// Only a `Taxable` account pays tax while invested, and only on gains.
+    fn tax_on_interest(&self, interest: f64) -> f64 {
+        match self.account_type {
+            AccountType::Taxable if interest > 0.0 => interest * self.tax_rate / 100.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns what is left of a balance after withdrawing it.
+    ///
+    /// A `TaxDeferred` account pays `tax_rate` on the whole withdrawal. The other accounts have
+    /// nothing left to pay: a `Taxable` account already paid tax every year.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::{AccountType, Investment};
+    ///
+    /// let deferred = Investment { account_type: AccountType::TaxDeferred, tax_rate: 25.0, ..Default::default() };
+    /// assert_eq!(deferred.after_tax_amount(1000.0), 750.0);
+    /// ```
+    pub fn after_tax_amount(&self, amount: f64) -> f64 {
+        match self.account_type {
+            AccountType::TaxDeferred => amount * (1.0 - self.tax_rate / 100.0),
+            AccountType::Taxable | AccountType::TaxFree => amount,
+        }
+    }
+
+    /// Returns a warning if the rate looks like it was written in the wrong format.
+    ///
+    /// A percent-mode rate between 0 and 1 (e.g. `0.05`) is most likely meant as a decimal
+    /// fraction, which would make the calculation use 0.05% instead of 5%.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_format` - How `--rate` was written. A decimal rate was already converted.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(message)` if the rate is suspicious, otherwise `None`.
+    pub fn rate_warning(&self, rate_format: RateFormat) -> Option<String> {
+        if rate_format == RateFormat::Percent && self.rate > 0.0 && self.rate < 1.0 {
+            return Some(format!(
+                "Warning: rate {} is interpreted as {}%. Use --rate-format decimal if you meant {}%",
+                self.rate,
+                self.rate,
+                self.rate * 100.0
+            ));
+        }
+        None
+    }
+
+    /// Returns a warning if the rate is above a sanity threshold.
+    ///
+    /// Long-run returns above a few tens of percent a year are unrealistic, so a rate like `50` is
+    /// more likely a typo than an assumption. The calculation still runs with the given rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_rate` - The threshold (in %), e.g. `DEFAULT_MAX_RATE`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(message)` if the rate exceeds `max_rate`, otherwise `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::{Investment, DEFAULT_MAX_RATE};
+    ///
+    /// let investment = Investment { rate: 50.0, ..Default::default() };
+    /// assert!(investment.high_rate_warning(DEFAULT_MAX_RATE).is_some());
+    /// ```
+    pub fn high_rate_warning(&self, max_rate: f64) -> Option<String> {
+        (self.rate > max_rate).then(|| {
+            format!(
+                "Warning: a rate of {}% a year is above {}%. Double-check it, or pass --no-warn",
+                self.rate, max_rate
+            )
+        })
+    }
+
+    /// Generates a yearly summary of the investment.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `YearlySummary` structs, each representing the investment's status at the end of each year.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `years` is greater than `MAX_YEARS`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::YearlySummary;
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment {
+    ///     principal: 1000.0,
+    ///     contribution: 100.0,
+    ///     rate: 5.0,
+    ///     years: 10,
+    ///     ..Default::default()
+    /// };
+    /// let summary = investment.yearly_summary().expect("Failed to calculate summary");
+    /// ```
+    pub fn yearly_summary(&self) -> Result<Vec<YearlySummary>, CicError> {
+        if self.years > MAX_YEARS {
+            return Err(CicError::Validation(YEARS_LIMIT_ERROR.into()));
+        }
+        Ok(self.iter_years().collect())
+    }
+
+    /// Returns an iterator that calculates the yearly summaries lazily, one year at a time.
+    ///
+    /// Unlike `yearly_summary`, nothing is allocated up front, so the iterator suits streaming and
+    /// early termination. It is not limited to `MAX_YEARS`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    ///
+    /// let investment = Investment { principal: 1000.0, years: 30, ..Default::default() };
+    /// let first_year_over_2000 = investment
+    ///     .iter_years()
+    ///     .find(|s| s.total_amount > 2000.0)
+    ///     .map(|s| s.year);
+    /// ```
+    pub fn iter_years(&self) -> YearlySummaryIter<'_> {
+        YearlySummaryIter {
+            investment: self,
+            year: 0,
+            amount: self.principal,
+            real_amount: self.principal,
+            total_interest: 0.0,
+            total_contribution: 0.0,
+            total_match: 0.0,
+            growth: 1.0,
+            invested_periods: 0,
+        }
+    }
+}
+
+/// An iterator over the yearly summaries of an `Investment`, created by `Investment::iter_years`.
+#[derive(Debug, Clone)]
+pub struct YearlySummaryIter<'a> {
+    investment: &'a Investment,
+    year: i32,
+    amount: f64,
+    real_amount: f64,
+    total_interest: f64,
+    total_contribution: f64,
+    total_match: f64,
+    /// The product of the growth factors of the compounding periods that started with money
+    /// invested, see `Investment::time_weighted_return`.
+    growth: f64,
+    /// The number of compounding periods that started with money invested.
+    invested_periods: u32,
+}
+
+impl Iterator for YearlySummaryIter<'_> {
+    type Item = YearlySummary;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let investment = self.investment;
+        if self.year >= investment.years {
+            return None;
+        }
+        self.year += 1;
+        let year = self.year;
+
+        let deposits = investment.monthly_deposits(year);
+        let annual_contribution: f64 = deposits.iter().sum();
+        let annual_match = investment.annual_match(year);
+        let periods = investment.compound_frequency.periods_per_year();
+        let (mut balance, mut contributed, mut annual_interest) = (self.amount, 0.0, 0.0);
+        for period in 0..periods {
+            let months = (12 / periods) as usize;
+            let period = period as usize;
+            let deposit: f64 = deposits[period * months..(period + 1) * months]
+                .iter()
+                .sum();
+            let interest_base = match investment.interest_mode {
+                InterestMode::Compound => balance,
+                InterestMode::Simple => {
+                    investment.principal + self.total_contribution + self.total_match + contributed
+                }
+            };
+            // The contributions of a period are added at its end, so they earn from the next one,
+            // unless they are credited with half of the period's interest.
+            let interest_base = if investment.mid_year_contribution {
+                interest_base + deposit / 2.0
+            } else {
+                interest_base
+            };
+            let gross_interest = investment.gross_interest(year, interest_base, periods);
+            let interest = gross_interest - investment.tax_on_interest(gross_interest);
+            // Deposits credited with half of the period's interest were invested for half of it.
+            let invested = if investment.mid_year_contribution {
+                balance + deposit / 2.0
+            } else {
+                balance
+            };
+            if invested > 0.0 {
+                self.growth *= 1.0 + interest / invested;
+                self.invested_periods += 1;
+            }
+            balance += interest + deposit;
+            contributed += deposit;
+            annual_interest += interest;
+        }
+        self.total_interest += annual_interest;
+        self.total_contribution += annual_contribution;
+        self.total_match += annual_match;
+
+        self.amount += annual_contribution + annual_match + annual_interest;
+        let real_total_amount = self.amount / investment.inflation_factor(year);
+        let real_growth_flag = real_total_amount > self.real_amount;
+        self.real_amount = real_total_amount;
+
+        Some(YearlySummary {
+            year,
+            principal: investment.principal,
+            annual_contribution,
+            total_contribution: self.total_contribution,
+            annual_match,
+            total_match: self.total_match,
+            annual_interest,
+            total_interest: self.total_interest,
+            total_amount: self.amount,
+            real_total_amount,
+            real_growth_flag,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.investment.years - self.year).max(0) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for YearlySummaryIter<'_> {}
+
+/// Represents a summary of the investment at the end of a given year.
+///
+/// The JSON Schema of its serialized form is derived with `schemars`, see
+/// `output::summary_schema`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct YearlySummary {
+    /// The year for which the summary is provided.
+    pub year: i32,
+    /// The initial principal amount of the investment.
+    pub principal: f64,
+    /// The total contribution made during the year.
+    pub annual_contribution: f64,
+    /// The cumulative total contribution up to the end of the year.
+    pub total_contribution: f64,
+    /// The employer match paid during the year, on top of `annual_contribution`.
+    pub annual_match: f64,
+    /// The cumulative employer match up to the end of the year.
+    pub total_match: f64,
+    /// The interest earned during the year, after the tax of a `Taxable` account.
+    pub annual_interest: f64,
+    /// The cumulative total interest earned up to the end of the year.
+    pub total_interest: f64,
+    /// The total amount of money at the end of the year.
+    pub total_amount: f64,
+    /// The total amount of money at the end of the year, adjusted for inflation to today's money.
+    pub real_total_amount: f64,
+    /// Whether `real_total_amount` grew compared to the previous year (or to the principal in the
+    /// first year).
+    pub real_growth_flag: bool,
+}
+
+impl YearlySummary {
+    /// Calculates the overall return up to the end of this year as a percentage.
+    ///
+    /// # Returns
+    ///
+    /// Returns `(total_amount - invested) / invested * 100`, where `invested` is the principal plus
+    /// the total contribution, or `0.0` if nothing was invested.
+    pub fn total_return_pct(&self) -> f64 {
+        let invested = self.principal + self.total_contribution;
+        if invested == 0.0 {
+            return 0.0;
+        }
+        (self.total_amount - invested) / invested * 100.0
+    }
+
+    /// Splits the total amount at the end of this year into what was paid in and what was earned.
+    ///
+    /// # Returns
+    ///
+    /// Returns `(contribution_pct, interest_pct)`, which add up to 100. The contribution share
+    /// includes the principal and the employer match. Both are `0.0` if the total amount is not
+    /// positive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::YearlySummary;
+    ///
+    /// let summary = YearlySummary { principal: 750.0, total_interest: 250.0, total_amount: 1000.0, ..Default::default() };
+    /// assert_eq!(summary.composition_pct(), (75.0, 25.0));
+    /// ```
+    pub fn composition_pct(&self) -> (f64, f64) {
+        if self.total_amount <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let interest_pct = (self.total_interest / self.total_amount * 100.0).clamp(0.0, 100.0);
+        (100.0 - interest_pct, interest_pct)
+    }
+}
+
+/// Returns the amount deposited in every month of an investment, composing all contribution
+/// modifiers.
+///
+/// The vector holds 12 entries per year, starting with January of the first year. It is what
+/// `Investment::yearly_summary` deposits: a compounding period adds the entries of its months, and
+/// the yearly `annual_contribution` is the sum of the year's 12 entries.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{effective_contributions, Investment};
+///
+/// let investment = Investment { contribution: 100.0, years: 2, pause_years: Some((2, 2)), ..Default::default() };
+/// let contributions = effective_contributions(&investment);
+/// assert_eq!(contributions.len(), 24);
+/// assert_eq!(contributions[11], 100.0);
+/// assert_eq!(contributions[12], 0.0);
+/// ```
+pub fn effective_contributions(investment: &Investment) -> Vec<f64> {
+    (1..=investment.years.max(0))
+        .flat_map(|year| investment.monthly_deposits(year))
+        .collect()
+}
+
+/// Builds a glide path that linearly moves the rate from `start_rate` to `end_rate`.
+///
+/// Target-date funds reduce their expected return as retirement approaches. The returned rates are
+/// meant for `Investment::variable_rates`.
+///
+/// # Arguments
+///
+/// * `start_rate` - The rate of the first year.
+/// * `end_rate` - The rate of the final year.
+/// * `years` - The number of years of the investment.
+///
+/// # Returns
+///
+/// Returns one rate per year. A single-year path only contains `start_rate`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::glide_path;
+///
+/// assert_eq!(glide_path(8.0, 4.0, 5), vec![8.0, 7.0, 6.0, 5.0, 4.0]);
+/// ```
+pub fn glide_path(start_rate: f64, end_rate: f64, years: i32) -> Vec<f64> {
+    if years <= 1 {
+        return vec![start_rate; years.max(0) as usize];
+    }
+    let step = (end_rate - start_rate) / (years - 1) as f64;
+    (0..years).map(|i| start_rate + step * i as f64).collect()
+}
+
+/// Parses a series of annual inflation rates, one rate (in %) per line.
+///
+/// Blank lines and lines starting with `#` are ignored, so a CPI file can carry a header comment.
+/// The returned rates are meant for `Investment::inflation_rates`.
+///
+/// # Arguments
+///
+/// * `text` - The contents of the series.
+/// * `years` - The number of years the series has to cover.
+/// * `carry_forward` - If `true`, a series shorter than `years` is extended by repeating its last
+///   rate. Otherwise, a short series is an error.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` if a line is not a number, if the series is empty, or if it is
+/// shorter than `years` and `carry_forward` is `false`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::parse_inflation_series;
+///
+/// assert_eq!(parse_inflation_series("# CPI\n3.0\n2.5\n", 3, true).unwrap(), vec![3.0, 2.5, 2.5]);
+/// assert!(parse_inflation_series("3.0\n2.5\n", 3, false).is_err());
+/// ```
+pub fn parse_inflation_series(
+    text: &str,
+    years: i32,
+    carry_forward: bool,
+) -> Result<Vec<f64>, CicError> {
+    let mut rates = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse::<f64>()
+                .map_err(|_| CicError::Validation(format!("Invalid inflation rate: {}", line)))
+        })
+        .collect::<Result<Vec<f64>, CicError>>()?;
+    let years = years.max(0) as usize;
+    let Some(&last) = rates.last() else {
+        return Err(CicError::Validation("The inflation series is empty".into()));
+    };
+    if rates.len() < years {
+        if !carry_forward {
+            return Err(CicError::Validation(format!(
+                "The inflation series covers {} of {} years",
+                rates.len(),
+                years
+            )));
+        }
+        rates.resize(years, last);
+    }
+    Ok(rates)
+}
+
+/// Reads a series of annual inflation rates from a file, see `parse_inflation_series`.
+///
+/// # Errors
+///
+/// Returns a `CicError::Io` if the file cannot be read, or the errors of `parse_inflation_series`.
+pub fn load_inflation_series(
+    path: &std::path::Path,
+    years: i32,
+    carry_forward: bool,
+) -> Result<Vec<f64>, CicError> {
+    parse_inflation_series(&std::fs::read_to_string(path)?, years, carry_forward)
+}
+
+/// Represents one account of a `Portfolio`, such as a savings account or an index fund.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    /// The name used to identify the account in the breakdown.
+    pub name: String,
+    /// The initial amount of money in the account.
+    pub principal: f64,
+    /// The monthly contribution added to the account.
+    pub contribution: f64,
+    /// The annual interest rate of the account as a percentage.
+    pub rate: f64,
+}
+
+impl Account {
+    /// Creates an `Investment` for this account over the given number of years.
+    pub fn to_investment(&self, years: i32) -> Investment {
+        Investment {
+            principal: self.principal,
+            contribution: self.contribution,
+            rate: self.rate,
+            years,
+            ..Default::default()
+        }
+    }
+}
+
+impl std::str::FromStr for Account {
+    type Err = String;
+
+    /// Parses an account spec in the form `NAME:PRINCIPAL:CONTRIBUTION:RATE`, e.g. `savings:1000:100:2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 4 || parts[0].is_empty() {
+            return Err(format!(
+                "Invalid account: {} (expected NAME:PRINCIPAL:CONTRIBUTION:RATE)",
+                s
+            ));
+        }
+        let parse = |field: &str, value: &str| -> Result<f64, String> {
+            match value.parse::<f64>() {
+                Ok(v) if v >= 0.0 => Ok(v),
+                _ => Err(format!("Invalid {} in account {}: {}", field, s, value)),
+            }
+        };
+        Ok(Self {
+            name: parts[0].to_string(),
+            principal: parse("principal", parts[1])?,
+            contribution: parse("contribution", parts[2])?,
+            rate: parse("rate", parts[3])?,
+        })
+    }
+}
+
+/// Represents a set of accounts that are invested side by side.
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    /// The accounts that make up the portfolio.
+    pub accounts: Vec<Account>,
+}
+
+impl Portfolio {
+    /// Generates a yearly summary of the whole portfolio.
+    ///
+    /// # Arguments
+    ///
+    /// * `years` - The number of years the accounts are invested for.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `PortfolioYearlySummary` structs. Each one holds the combined balances of all
+    /// accounts at the end of the year, together with the per-account breakdown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `years` is negative or greater than `MAX_YEARS`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Portfolio;
+    ///
+    /// let portfolio = Portfolio {
+    ///     accounts: vec!["savings:1000:100:2".parse().unwrap(), "index:1000:100:7".parse().unwrap()],
+    /// };
+    /// let summary = portfolio.yearly_summary(10).expect("Failed to calculate summary");
+    /// ```
+    pub fn yearly_summary(&self, years: i32) -> Result<Vec<PortfolioYearlySummary>, CicError> {
+        self.combined_summary(years, false)
+    }
+
+    /// Generates a yearly summary of the whole portfolio, calculating the accounts in parallel.
+    ///
+    /// The result is identical to `yearly_summary`.
+    pub fn par_yearly_summary(&self, years: i32) -> Result<Vec<PortfolioYearlySummary>, CicError> {
+        self.combined_summary(years, true)
+    }
+
+    fn combined_summary(
+        &self,
+        years: i32,
+        parallel: bool,
+    ) -> Result<Vec<PortfolioYearlySummary>, CicError> {
+        // Checked here too, since a portfolio without accounts has no investment to check it.
+        if years < 0 {
+            return Err(CicError::Validation(
+                "Negative values are not allowed".into(),
+            ));
+        }
+        if years > MAX_YEARS {
+            return Err(CicError::Validation(YEARS_LIMIT_ERROR.into()));
+        }
+        let investments: Vec<Investment> = self
+            .accounts
+            .iter()
+            .map(|account| account.to_investment(years))
+            .collect();
+        let account_summaries = yearly_summaries(&investments, parallel)?;
+
+        let mut real_amount: f64 = self.accounts.iter().map(|a| a.principal).sum();
+        let summary = (0..years as usize)
+            .map(|i| {
+                let mut combined = YearlySummary {
+                    year: i as i32 + 1,
+                    ..Default::default()
+                };
+                let mut accounts = Vec::with_capacity(self.accounts.len());
+                for (account, summary) in self.accounts.iter().zip(account_summaries.iter()) {
+                    let s = &summary[i];
+                    combined.principal += s.principal;
+                    combined.annual_contribution += s.annual_contribution;
+                    combined.total_contribution += s.total_contribution;
+                    combined.annual_match += s.annual_match;
+                    combined.total_match += s.total_match;
+                    combined.annual_interest += s.annual_interest;
+                    combined.total_interest += s.total_interest;
+                    combined.total_amount += s.total_amount;
+                    combined.real_total_amount += s.real_total_amount;
+                    accounts.push(AccountYearlySummary {
+                        name: account.name.clone(),
+                        summary: s.clone(),
+                    });
+                }
+                combined.real_growth_flag = combined.real_total_amount > real_amount;
+                real_amount = combined.real_total_amount;
+                PortfolioYearlySummary { combined, accounts }
+            })
+            .collect();
+        Ok(summary)
+    }
+}
+
+/// Generates the yearly summaries of many independent investments.
+///
+/// # Arguments
+///
+/// * `investments` - The investments to calculate.
+/// * `parallel` - If `true`, the investments are calculated in parallel on the rayon thread pool.
+///
+/// # Returns
+///
+/// Returns the summary of every investment, in the order of `investments` either way.
+///
+/// # Errors
+///
+/// Returns the first error of an investment whose summary cannot be calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{yearly_summaries, Investment};
+///
+/// let investments: Vec<Investment> =
+///     (1..=3).map(|years| Investment { years, ..Default::default() }).collect();
+/// let summaries = yearly_summaries(&investments, true).unwrap();
+/// assert_eq!(summaries[2].len(), 3);
+/// ```
+pub fn yearly_summaries(
+    investments: &[Investment],
+    parallel: bool,
+) -> Result<Vec<Vec<YearlySummary>>, CicError> {
+    if parallel {
+        use rayon::prelude::*;
+        investments
+            .par_iter()
+            .map(Investment::yearly_summary)
+            .collect()
+    } else {
+        investments.iter().map(Investment::yearly_summary).collect()
+    }
+}
+
+/// Represents the summary of a single account within a `PortfolioYearlySummary`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountYearlySummary {
+    /// The name of the account.
+    pub name: String,
+    /// The summary of the account at the end of the year.
+    #[serde(flatten)]
+    pub summary: YearlySummary,
+}
+
+/// Represents a summary of the portfolio at the end of a given year.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioYearlySummary {
+    /// The combined summary of all accounts.
+    #[serde(flatten)]
+    pub combined: YearlySummary,
+    /// The summary of each account.
+    pub accounts: Vec<AccountYearlySummary>,
+}
+
+/// Represents the difference between two summaries at the end of a given year.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct YearlyDifference {
+    /// The year for which the difference is provided.
+    pub year: i32,
+    /// The difference in the cumulative total contribution.
+    pub total_contribution: f64,
+    /// The difference in the cumulative total interest.
+    pub total_interest: f64,
+    /// The difference in the total amount of money.
+    pub total_amount: f64,
+}
+
+/// Compares two summaries year by year.
+///
+/// # Arguments
+///
+/// * `base` - The summary used as the reference.
+/// * `other` - The summary compared against `base`.
+///
+/// # Returns
+///
+/// Returns a vector of `YearlyDifference` structs holding `other - base` for each year present in
+/// both summaries.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{diff_summaries, Investment};
+///
+/// let base = Investment { contribution: 100.0, ..Default::default() }.yearly_summary().unwrap();
+/// let other = Investment { contribution: 150.0, ..Default::default() }.yearly_summary().unwrap();
+/// let diff = diff_summaries(&base, &other);
+/// assert_eq!(diff[0].total_contribution, 600.0);
+/// ```
+pub fn diff_summaries(base: &[YearlySummary], other: &[YearlySummary]) -> Vec<YearlyDifference> {
+    base.iter()
+        .zip(other.iter())
+        .map(|(b, o)| YearlyDifference {
+            year: o.year,
+            total_contribution: o.total_contribution - b.total_contribution,
+            total_interest: o.total_interest - b.total_interest,
+            total_amount: o.total_amount - b.total_amount,
+        })
+        .collect()
+}
+
+/// Represents the effect of increasing the monthly contribution of an investment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WhatIf {
+    /// The final balance of the investment as it is.
+    pub baseline: f64,
+    /// The final balance with the increased contribution.
+    pub boosted: f64,
+    /// The difference between the boosted and the baseline final balance.
+    pub difference: f64,
+    /// The difference as a percentage of the baseline final balance, or `None` if the baseline is 0.
+    pub uplift_pct: Option<f64>,
+}
+
+/// Calculates how much more an investment ends up with if the monthly contribution is increased.
+///
+/// # Arguments
+///
+/// * `investment` - The investment as it is.
+/// * `extra_contribution` - The amount added to the monthly contribution.
+///
+/// # Returns
+///
+/// Returns a `WhatIf` comparing the final balances of both scenarios.
+///
+/// # Errors
+///
+/// Returns an error if the contribution is derived from a salary, or if the summary cannot be
+/// calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{what_if_extra_contribution, Investment};
+///
+/// let what_if = what_if_extra_contribution(&Investment::default(), 50.0).unwrap();
+/// assert!(what_if.difference > 0.0);
+/// ```
+pub fn what_if_extra_contribution(
+    investment: &Investment,
+    extra_contribution: f64,
+) -> Result<WhatIf, CicError> {
+    if investment.salary > 0.0 {
+        return Err(CicError::Validation(
+            "An extra contribution cannot be combined with a salary-based contribution".into(),
+        ));
+    }
+    let boosted = Investment {
+        contribution: investment.contribution + extra_contribution,
+        ..investment.clone()
+    };
+    let base_summary = investment.yearly_summary()?;
+    let boosted_summary = boosted.yearly_summary()?;
+
+    let baseline = base_summary
+        .last()
+        .map_or(investment.principal, |s| s.total_amount);
+    let difference = diff_summaries(&base_summary, &boosted_summary)
+        .last()
+        .map_or(0.0, |d| d.total_amount);
+    Ok(WhatIf {
+        baseline,
+        boosted: baseline + difference,
+        difference,
+        uplift_pct: (baseline != 0.0).then(|| difference / baseline * 100.0),
+    })
+}
+
+/// Represents how far the final balance of an investment is from a goal.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalGap {
+    /// The goal.
+    pub target: f64,
+    /// The final balance of the investment.
+    pub final_amount: f64,
+    /// The final balance minus the goal: a surplus if positive, a shortfall if negative.
+    pub gap: f64,
+    /// The gap as a percentage of the goal, or `None` if the goal is 0.
+    pub gap_pct: Option<f64>,
+    /// Whether the final balance reaches the goal.
+    pub met: bool,
+}
+
+/// Calculates how far short of (or over) a goal the investment ends at its current trajectory.
+///
+/// # Arguments
+///
+/// * `investment` - The investment as it is.
+/// * `target` - The final balance to reach.
+///
+/// # Errors
+///
+/// Returns an error if the summary cannot be calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{goal_gap, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() };
+/// let gap = goal_gap(&investment, 5000.0).unwrap();
+/// assert!(!gap.met);
+/// assert_eq!(gap.gap, 4940.625 - 5000.0);
+/// ```
+pub fn goal_gap(investment: &Investment, target: f64) -> Result<GoalGap, CicError> {
+    let final_amount = investment
+        .yearly_summary()?
+        .last()
+        .map_or(investment.principal, |s| s.total_amount);
+    let gap = final_amount - target;
+    Ok(GoalGap {
+        target,
+        final_amount,
+        gap,
+        gap_pct: (target != 0.0).then(|| gap / target * 100.0),
+        met: gap >= 0.0,
+    })
+}
+
+/// Represents the cost of starting an investment later but ending it at the same time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CostOfWaiting {
+    /// The final balance when starting now.
+    pub start_now: f64,
+    /// The final balance when starting after the delay.
+    pub start_later: f64,
+    /// The difference between both final balances, i.e. what the delay costs.
+    pub cost: f64,
+    /// The cost as a percentage of the final balance when starting now, or `None` if it is 0.
+    pub cost_pct: Option<f64>,
+}
+
+/// Calculates how much less an investment ends up with if it starts `delay` years later.
+///
+/// Both end at the same time, e.g. at retirement, so the delayed investment runs for `years -
+/// delay` years: its principal is invested and its contributions start only after the delay.
+/// Every year-keyed field keeps its calendar, so the delayed investment starts with the rates,
+/// inflation and salary of year `delay + 1`. A pause, lump sum or end of the contributions that
+/// falls within the delay is dropped, and indexed contributions start at their inflated amount.
+///
+/// # Arguments
+///
+/// * `investment` - The investment when starting now.
+/// * `delay` - The number of years to wait before starting.
+///
+/// # Errors
+///
+/// Returns an error if the delay is negative or longer than `years`, or if the summary cannot be
+/// calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{cost_of_waiting, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 30, ..Default::default() };
+/// let cost = cost_of_waiting(&investment, 5).unwrap();
+/// assert!(cost.start_later < cost.start_now);
+/// assert_eq!(cost.cost, cost.start_now - cost.start_later);
+/// ```
+pub fn cost_of_waiting(investment: &Investment, delay: i32) -> Result<CostOfWaiting, CicError> {
+    if !(0..=investment.years).contains(&delay) {
+        return Err(CicError::Validation(format!(
+            "The delay must be between 0 and the {} years of the investment",
+            investment.years
+        )));
+    }
+    // Year N of the delayed investment is year N + delay of the calendar.
+    let delay_months = delay as u32 * 12;
+    let delayed = Investment {
+        years: investment.years - delay,
+        salary: investment.salary * (1.0 + investment.salary_growth / 100.0).powi(delay),
+        contribution: if investment.index_contributions_to_inflation {
+            investment.contribution * investment.inflation_factor(delay)
+        } else {
+            investment.contribution
+        },
+        variable_rates: investment
+            .variable_rates
+            .iter()
+            .skip(delay as usize)
+            .copied()
+            .collect(),
+        inflation_rates: investment
+            .inflation_rates
+            .iter()
+            .skip(delay as usize)
+            .copied()
+            .collect(),
+        contribution_months: investment
+            .contribution_months
+            .map(|months| months.saturating_sub(delay_months)),
+        pause_years: investment
+            .pause_years
+            .filter(|&(_, last)| last > delay)
+            .map(|(first, last)| ((first - delay).max(1), last - delay)),
+        lump_sums: investment
+            .lump_sums
+            .iter()
+            .filter(|&&(year, _)| year > delay)
+            .map(|&(year, amount)| (year - delay, amount))
+            .collect(),
+        ..investment.clone()
+    };
+    let final_amount = |investment: &Investment| -> Result<f64, CicError> {
+        Ok(investment
+            .yearly_summary()?
+            .last()
+            .map_or(investment.principal, |s| s.total_amount))
+    };
+    let start_now = final_amount(investment)?;
+    let start_later = final_amount(&delayed)?;
+    let cost = start_now - start_later;
+    Ok(CostOfWaiting {
+        start_now,
+        start_later,
+        cost,
+        cost_pct: (start_now != 0.0).then(|| cost / start_now * 100.0),
+    })
+}
+
+/// Calculates the monthly contribution needed for an investment to reach a target final balance.
+///
+/// The contribution of `investment` is ignored and solved for; everything else (rate, years,
+/// inflation indexing, ...) is used as is. A salary-based contribution is replaced by a fixed one.
+///
+/// The target is the amount left after everything `yearly_summary` applies: fees and the yearly
+/// tax of a taxable account reduce the balance, and the withdrawal tax of a tax-deferred account
+/// is deducted from it (see `Investment::after_tax_amount`). For a target in today's money, convert
+/// it with `Investment::nominal_target` first.
+///
+/// # Arguments
+///
+/// * `investment` - The investment to solve the contribution for.
+/// * `target` - The final balance to reach.
+///
+/// # Returns
+///
+/// Returns the smallest monthly contribution (to within a cent) whose final balance after tax
+/// reaches `target`, or 0 if the principal alone reaches it.
+///
+/// # Errors
+///
+/// Returns an error if the target cannot be reached by contributing, e.g. because `years` is 0, or
+/// if the summary cannot be calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{required_contribution, Investment};
+///
+/// let investment = Investment { principal: 1000.0, rate: 5.0, years: 3, ..Default::default() };
+/// let contribution = required_contribution(&investment, 4940.625).unwrap();
+/// assert!((contribution - 100.0).abs() < 0.01);
+/// ```
+pub fn required_contribution(investment: &Investment, target: f64) -> Result<f64, CicError> {
+    solve_contribution(target, |contribution| {
+        let investment = Investment {
+            contribution,
+            salary: 0.0,
+            ..investment.clone()
+        };
+        let final_amount = investment
+            .yearly_summary()?
+            .last()
+            .map_or(investment.principal, |s| s.total_amount);
+        Ok(investment.after_tax_amount(final_amount))
+    })
+}
+
+/// Searches the smallest monthly contribution for which `final_amount` reaches `target`.
+///
+/// `final_amount` must not decrease as the contribution grows.
+fn solve_contribution<F>(target: f64, final_amount: F) -> Result<f64, CicError>
+where
+    F: Fn(f64) -> Result<f64, CicError>,
+{
+    if final_amount(0.0)? >= target {
+        return Ok(0.0);
+    }
+    let mut high = target.max(1.0);
+    while final_amount(high)? < target {
+        high *= 2.0;
+        if !high.is_finite() {
+            return Err(CicError::Calculation(
+                "The target cannot be reached by contributing".into(),
+            ));
+        }
+    }
+
+    let mut low = 0.0;
+    while high - low > 0.001 {
+        let middle = (low + high) / 2.0;
+        // Above about 1e13, adjacent floats are more than a tenth of a cent apart.
+        if middle <= low || middle >= high {
+            break;
+        }
+        if final_amount(middle)? >= target {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    Ok(high)
+}
+
+/// Calculates the extra monthly contribution needed to end with the same balance despite a
+/// planned expense.
+///
+/// The expense is withdrawn at the end of `year` as a negative lump sum. The extra contribution
+/// is then searched like `required_contribution`, with the final balance after tax of the
+/// investment without the expense as the target.
+///
+/// # Arguments
+///
+/// * `investment` - The investment as it is planned.
+/// * `year` - The year the expense is paid in, within `years`.
+/// * `amount` - The amount of the expense.
+///
+/// # Returns
+///
+/// Returns the amount to add to the monthly contribution from the start, to within a cent.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` if the contribution is derived from a salary, if the year is
+/// not within `years`, or if the amount is negative. Returns a `CicError::Calculation` if the
+/// expense cannot be offset by contributing.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{offset_expense, Investment};
+///
+/// let investment = Investment { principal: 10000.0, contribution: 200.0, years: 10, ..Default::default() };
+/// let extra = offset_expense(&investment, 8, 3000.0).unwrap();
+/// assert!(extra > 0.0);
+/// ```
+pub fn offset_expense(investment: &Investment, year: i32, amount: f64) -> Result<f64, CicError> {
+    if investment.salary > 0.0 {
+        return Err(CicError::Validation(
+            "An expense cannot be offset with a salary-based contribution".into(),
+        ));
+    }
+    if !(1..=investment.years).contains(&year) {
+        return Err(CicError::Validation(format!(
+            "The year of the expense must be between 1 and {}",
+            investment.years
+        )));
+    }
+    if !(amount >= 0.0 && amount.is_finite()) {
+        return Err(CicError::Validation(
+            "The expense must be a non-negative amount".into(),
+        ));
+    }
+    let final_amount = |investment: &Investment| -> Result<f64, CicError> {
+        let final_amount = investment
+            .yearly_summary()?
+            .last()
+            .map_or(investment.principal, |s| s.total_amount);
+        Ok(investment.after_tax_amount(final_amount))
+    };
+    let target = final_amount(investment)?;
+    let mut lump_sums = investment.lump_sums.clone();
+    lump_sums.push((year, -amount));
+    solve_contribution(target, |extra| {
+        final_amount(&Investment {
+            contribution: investment.contribution + extra,
+            lump_sums: lump_sums.clone(),
+            ..investment.clone()
+        })
+    })
+}
+
+/// Simulates the final balance of an investment with randomly varying annual returns.
+///
+/// The rate of every year is drawn from a normal distribution centered on the rate of that year
+/// (`rate_for_year`) with a standard deviation of `volatility`, in %.
+///
+/// # Arguments
+///
+/// * `investment` - The investment to simulate.
+/// * `volatility` - The standard deviation of the annual rate.
+/// * `runs` - The number of simulated trajectories.
+/// * `seed` - The seed of the random number generator. The same seed yields the same results.
+///
+/// # Returns
+///
+/// Returns the final balance of every run, in the order they were simulated.
+///
+/// # Errors
+///
+/// Returns an error if `volatility` is negative or not finite, if `runs` is 0, or if the years
+/// exceed `MAX_YEARS`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{monte_carlo, Investment};
+///
+/// let finals = monte_carlo(&Investment::default(), 10.0, 100, 42).unwrap();
+/// assert_eq!(finals.len(), 100);
+/// ```
+pub fn monte_carlo(
+    investment: &Investment,
+    volatility: f64,
+    runs: usize,
+    seed: u64,
+) -> Result<Vec<f64>, CicError> {
+    use rand::SeedableRng;
+    use rand_distr::Distribution;
+
+    if investment.years > MAX_YEARS {
+        return Err(CicError::Validation(YEARS_LIMIT_ERROR.into()));
+    }
+    if runs == 0 {
+        return Err(CicError::Validation(
+            "The number of runs must be positive".into(),
+        ));
+    }
+    if !(volatility >= 0.0 && volatility.is_finite()) {
+        return Err(CicError::Validation(
+            "Volatility must be non-negative".into(),
+        ));
+    }
+    let distribution = rand_distr::Normal::new(0.0, volatility)
+        .map_err(|_| CicError::Validation("Volatility must be non-negative".into()))?;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+
+    let finals = (0..runs)
+        .map(|_| {
+            let variable_rates = (0..investment.years.max(0) as usize)
+                .map(|i| {
+                    let rate = investment
+                        .variable_rates
+                        .get(i)
+                        .copied()
+                        .unwrap_or(investment.rate);
+                    rate + distribution.sample(&mut rng)
+                })
+                .collect();
+            let run = Investment {
+                variable_rates,
+                ..investment.clone()
+            };
+            run.iter_years()
+                .last()
+                .map_or(run.principal, |s| s.total_amount)
+        })
+        .collect();
+    Ok(finals)
+}
+
+/// Returns the given percentile of the values, interpolating linearly between the closest ranks.
+///
+/// # Arguments
+///
+/// * `values` - The values, in any order.
+/// * `percentile` - The percentile between 0 and 100, e.g. 10 for the pessimistic p10 outcome.
+///
+/// # Returns
+///
+/// Returns the percentile, or `None` if `values` is empty.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::percentile;
+///
+/// assert_eq!(percentile(&[3.0, 1.0, 2.0], 50.0), Some(2.0));
+/// assert_eq!(percentile(&[1.0, 2.0], 25.0), Some(1.25));
+/// ```
+pub fn percentile(values: &[f64], percentile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let rank = percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64))
+}
+
+/// Calculates the monthly contribution needed for the given percentile of simulated outcomes to
+/// reach a target final balance.
+///
+/// Every candidate contribution is simulated with the same seed, so the outcomes only differ by
+/// the contribution. A low percentile such as 10 gives a "safe" contribution that still reaches
+/// the target in 90% of the runs.
+///
+/// # Arguments
+///
+/// * `investment` - The investment to solve the contribution for. See `required_contribution`.
+/// * `target` - The final balance to reach.
+/// * `percentile` - The percentile of the final balances that has to reach `target`.
+/// * `volatility` - The standard deviation of the annual rate. See `monte_carlo`.
+/// * `runs` - The number of simulated trajectories.
+/// * `seed` - The seed of the random number generator.
+///
+/// # Errors
+///
+/// Returns an error if the simulation fails or the target cannot be reached by contributing.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{required_contribution_for_percentile, Investment};
+///
+/// let investment = Investment { years: 10, ..Default::default() };
+/// let contribution =
+///     required_contribution_for_percentile(&investment, 20000.0, 10.0, 15.0, 200, 42).unwrap();
+/// assert!(contribution > 0.0);
+/// ```
+pub fn required_contribution_for_percentile(
+    investment: &Investment,
+    target: f64,
+    percentile: f64,
+    volatility: f64,
+    runs: usize,
+    seed: u64,
+) -> Result<f64, CicError> {
+    solve_contribution(target, |contribution| {
+        let investment = Investment {
+            contribution,
+            salary: 0.0,
+            ..investment.clone()
+        };
+        let finals = monte_carlo(&investment, volatility, runs, seed)?;
+        self::percentile(&finals, percentile)
+            .map(|amount| investment.after_tax_amount(amount))
+            .ok_or_else(|| CicError::Calculation("The simulation has no results".into()))
+    })
+}
+
+/// Calculates the fee at which a fund with a higher rate ends with the same balance as a fee-free
+/// benchmark with a lower rate, e.g. to judge whether a managed fund is worth its fee.
+///
+/// Both are calculated with the principal, contributions, years and taxes of `investment`; its
+/// `rate`, `variable_rates` and `fee` are replaced. Any fee above the break-even fee makes the fund
+/// end below the benchmark.
+///
+/// # Arguments
+///
+/// * `investment` - The investment to compare the fund and the benchmark for.
+/// * `high_rate` - The annual rate of the fund before its fee, in %.
+/// * `low_rate` - The annual rate of the fee-free benchmark, in %.
+///
+/// # Returns
+///
+/// Returns the break-even fee as a percentage, like `Investment::fee`.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` if the fund does not beat the benchmark even without a fee,
+/// and an error if the summary cannot be calculated, or if no fee erases the benefit.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{breakeven_fee, Investment};
+///
+/// let investment = Investment { principal: 10000.0, years: 10, ..Default::default() };
+/// let fee = breakeven_fee(&investment, 7.0, 5.0).unwrap();
+/// assert!((fee - 2.0).abs() < 1e-6);
+/// ```
+pub fn breakeven_fee(
+    investment: &Investment,
+    high_rate: f64,
+    low_rate: f64,
+) -> Result<f64, CicError> {
+    let final_amount = |rate: f64, fee: f64| -> Result<f64, CicError> {
+        let investment = Investment {
+            rate,
+            fee,
+            variable_rates: Vec::new(),
+            rate_is_net: false,
+            ..investment.clone()
+        };
+        let final_amount = investment
+            .yearly_summary()?
+            .last()
+            .map_or(investment.principal, |s| s.total_amount);
+        Ok(investment.after_tax_amount(final_amount))
+    };
+    let benchmark = final_amount(low_rate, 0.0)?;
+    if final_amount(high_rate, 0.0)? <= benchmark {
+        return Err(CicError::Validation(format!(
+            "The high rate of {}% does not beat the benchmark at {}%, so no fee breaks even",
+            high_rate, low_rate
+        )));
+    }
+
+    // The balance falls as the fee grows, so the break-even fee is bisected.
+    let mut high = 1.0;
+    while final_amount(high_rate, high)? > benchmark {
+        high *= 2.0;
+        if high > 1e6 {
+            return Err(CicError::Calculation(
+                "No fee erases the benefit of the higher rate".into(),
+            ));
+        }
+    }
+    let mut low = 0.0;
+    while high - low > 1e-9 {
+        let middle = (low + high) / 2.0;
+        // Stop if floats no longer split the interval, as in `solve_contribution`.
+        if middle <= low || middle >= high {
+            break;
+        }
+        if final_amount(high_rate, middle)? > benchmark {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(high)
+}
+
+/// Calculates the monthly amount to save for the down payment of a purchase.
+///
+/// # Arguments
+///
+/// * `price` - The price of the purchase.
+/// * `down_payment_pct` - The down payment as a percentage of the price.
+/// * `years` - The number of years until the purchase.
+/// * `savings` - The amount already saved.
+/// * `rate` - The annual interest rate (in %) earned on the savings.
+/// * `compound_frequency` - How often the interest on the savings is compounded.
+///
+/// # Returns
+///
+/// Returns the monthly contribution needed, or 0 if the savings already grow to the down payment.
+///
+/// # Errors
+///
+/// Returns an error if the down payment cannot be reached, e.g. because `years` is 0.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{save_for_down_payment, CompoundFrequency};
+///
+/// let monthly =
+///     save_for_down_payment(400000.0, 20.0, 5, 0.0, 4.0, CompoundFrequency::Annual).unwrap();
+/// assert!(monthly > 1000.0);
+/// ```
+pub fn save_for_down_payment(
+    price: f64,
+    down_payment_pct: f64,
+    years: i32,
+    savings: f64,
+    rate: f64,
+    compound_frequency: CompoundFrequency,
+) -> Result<f64, CicError> {
+    let investment = Investment {
+        principal: savings,
+        rate,
+        years,
+        compound_frequency,
+        ..Default::default()
+    };
+    required_contribution(&investment, price * down_payment_pct / 100.0)
+}
+
+/// Calculates the principal needed to reach a target final balance with the given contributions.
+///
+/// This is the closed form of `Investment::yearly_summary` with annual compounding: the principal
+/// grows by `(1 + r)^years`, and the contributions of every year are added at its end, so they
+/// grow as an annuity.
+///
+/// # Arguments
+///
+/// * `contribution` - The monthly contribution.
+/// * `rate` - The annual interest rate (in %).
+/// * `years` - The number of years.
+/// * `target` - The final balance to reach.
+///
+/// # Returns
+///
+/// Returns the principal, or 0 if the contributions alone reach the target.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::required_principal;
+///
+/// assert!((required_principal(0.0, 10.0, 2, 12100.0) - 10000.0).abs() < 1e-9);
+/// assert_eq!(required_principal(1000.0, 5.0, 10, 1000.0), 0.0);
+/// ```
+pub fn required_principal(contribution: f64, rate: f64, years: i32, target: f64) -> f64 {
+    let r = rate / 100.0;
+    let years = years.max(0);
+    let growth = (1.0 + r).powi(years);
+    let annuity = if r == 0.0 {
+        f64::from(years)
+    } else {
+        (growth - 1.0) / r
+    };
+    ((target - contribution * 12.0 * annuity) / growth).max(0.0)
+}
+
+/// Converts a nominal annual rate (APR) to the effective annual yield (APY).
+///
+/// # Arguments
+///
+/// * `apr` - The nominal annual rate (in %), as quoted by a bank.
+/// * `n` - The number of compounding periods per year, e.g. 12 for monthly. Must be at least 1.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::apr_to_apy;
+///
+/// assert!((apr_to_apy(12.0, 12) - 12.6825).abs() < 1e-4);
+/// ```
+pub fn apr_to_apy(apr: f64, n: u32) -> f64 {
+    let n = f64::from(n);
+    ((apr / 100.0 / n).ln_1p() * n).exp_m1() * 100.0
+}
+
+/// Converts an effective annual yield (APY) to the nominal annual rate (APR), the inverse of
+/// `apr_to_apy`.
+///
+/// # Arguments
+///
+/// * `apy` - The effective annual yield (in %).
+/// * `n` - The number of compounding periods per year, e.g. 12 for monthly. Must be at least 1.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::apy_to_apr;
+///
+/// assert!((apy_to_apr(12.6825, 12) - 12.0).abs() < 1e-3);
+/// ```
+pub fn apy_to_apr(apy: f64, n: u32) -> f64 {
+    let n = f64::from(n);
+    ((1.0 + apy / 100.0).powf(1.0 / n) - 1.0) * n * 100.0
+}
+
+/// Returns the rate (in %) earned in each of the `n` compounding periods of a year at a nominal
+/// annual rate, e.g. 1% a month at 12% APR.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::periodic_rate;
+///
+/// assert_eq!(periodic_rate(12.0, 12), 1.0);
+/// ```
+pub fn periodic_rate(apr: f64, n: u32) -> f64 {
+    apr / f64::from(n)
+}
+
+/// Calculates the FIRE (financial independence, retire early) number: the nest egg whose safe
+/// withdrawal covers the annual expenses.
+///
+/// # Arguments
+///
+/// * `annual_expenses` - The expenses per year to cover.
+/// * `withdrawal_rate` - The safe withdrawal rate (in %), e.g. `4` for the 4% rule.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` if the expenses are negative or the withdrawal rate is not
+/// positive.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::fire_number;
+///
+/// assert_eq!(fire_number(40000.0, 4.0).unwrap(), 1000000.0);
+/// ```
+pub fn fire_number(annual_expenses: f64, withdrawal_rate: f64) -> Result<f64, CicError> {
+    if annual_expenses < 0.0 {
+        return Err(CicError::Validation(
+            "Negative values are not allowed".into(),
+        ));
+    }
+    if withdrawal_rate.is_nan() || withdrawal_rate <= 0.0 {
+        return Err(CicError::Validation(
+            "The withdrawal rate must be positive".into(),
+        ));
+    }
+    Ok(annual_expenses / (withdrawal_rate / 100.0))
+}
+
+/// Returns the first year in which the balance of an investment reaches a target.
+///
+/// The search is not limited to `years`: the investment keeps going as configured for up to
+/// `MAX_YEARS`. Like `required_contribution`, the balance is compared after tax.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{years_to_target, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 1, ..Default::default() };
+/// assert_eq!(years_to_target(&investment, 3000.0), Some(2));
+/// ```
+pub fn years_to_target(investment: &Investment, target: f64) -> Option<i32> {
+    let investment = Investment {
+        years: MAX_YEARS,
+        ..investment.clone()
+    };
+    investment
+        .iter_years()
+        .find(|s| investment.after_tax_amount(s.total_amount) >= target)
+        .map(|s| s.year)
+}
+
+/// Returns the time in years, with a fraction, until the balance of an investment reaches a target.
+///
+/// Like `years_to_target`, the search runs for up to `MAX_YEARS` and compares the balance after
+/// tax. Within the year the target is reached, the balance is interpolated linearly between the
+/// start and the end of the year. Returns 0 if the principal already reaches the target.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{fractional_years_to_target, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 1, ..Default::default() };
+/// let years = fractional_years_to_target(&investment, 2500.0).unwrap();
+/// assert!(years > 1.0 && years < 2.0);
+/// ```
+pub fn fractional_years_to_target(investment: &Investment, target: f64) -> Option<f64> {
+    let investment = Investment {
+        years: MAX_YEARS,
+        ..investment.clone()
+    };
+    let mut previous = investment.after_tax_amount(investment.principal);
+    if previous >= target {
+        return Some(0.0);
+    }
+    for s in investment.iter_years() {
+        let amount = investment.after_tax_amount(s.total_amount);
+        if amount >= target {
+            let fraction = (target - previous) / (amount - previous);
+            return Some(f64::from(s.year - 1) + fraction);
+        }
+        previous = amount;
+    }
+    None
+}
+
+/// Returns the time until the balance of an investment reaches a target as "X years Y months".
+///
+/// The time is `fractional_years_to_target`, rounded up to whole months so that the target is
+/// reached by the end of the last month. A unit of 1 is singular and a unit of 0 is left out,
+/// e.g. "1 year" or "3 months", unless the target is already reached ("0 months").
+///
+/// # Errors
+///
+/// Returns a `CicError::Calculation` if the target is not reached within `MAX_YEARS`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{years_and_months_to_target, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 1, ..Default::default() };
+/// assert_eq!(years_and_months_to_target(&investment, 2500.0).unwrap(), "1 year 3 months");
+/// ```
+pub fn years_and_months_to_target(
+    investment: &Investment,
+    target: f64,
+) -> Result<String, CicError> {
+    let years = fractional_years_to_target(investment, target).ok_or_else(|| {
+        CicError::Calculation(format!(
+            "The target {} is not reached within {} years",
+            format_currency(target),
+            MAX_YEARS
+        ))
+    })?;
+    // The tolerance keeps an exact number of months, e.g. 1.5 years, from rounding up.
+    let months = (years * 12.0 - 1e-9).ceil().max(0.0) as i64;
+    let unit = |n: i64, name: &str| match n {
+        0 => None,
+        1 => Some(format!("1 {}", name)),
+        n => Some(format!("{} {}s", n, name)),
+    };
+    let parts: Vec<String> = [unit(months / 12, "year"), unit(months % 12, "month")]
+        .into_iter()
+        .flatten()
+        .collect();
+    if parts.is_empty() {
+        return Ok("0 months".into());
+    }
+    Ok(parts.join(" "))
+}
+
+/// The trajectories of investing an amount at once versus spreading it over several months.
+#[derive(Debug, Clone)]
+pub struct LumpVsDca {
+    /// The yearly summary of investing the whole amount at the start.
+    pub lump_sum: Vec<YearlySummary>,
+    /// The yearly summary of dollar-cost averaging: investing equal monthly parts, then holding.
+    pub dca: Vec<YearlySummary>,
+}
+
+impl LumpVsDca {
+    /// Returns the final balance of the lump sum, or 0 if the horizon is empty.
+    pub fn lump_sum_final(&self) -> f64 {
+        self.lump_sum.last().map_or(0.0, |s| s.total_amount)
+    }
+
+    /// Returns the final balance of dollar-cost averaging, or 0 if the horizon is empty.
+    pub fn dca_final(&self) -> f64 {
+        self.dca.last().map_or(0.0, |s| s.total_amount)
+    }
+
+    /// Returns by how much the lump sum beats dollar-cost averaging. Negative if it loses.
+    pub fn difference(&self) -> f64 {
+        self.lump_sum_final() - self.dca_final()
+    }
+}
+
+/// Compares investing an amount at once (lump sum) with investing it in equal monthly parts
+/// (dollar-cost averaging, DCA) and holding it afterwards.
+///
+/// # Arguments
+///
+/// * `amount` - The amount to invest, e.g. 12000.
+/// * `months` - The number of months DCA spreads the amount over, e.g. 12 for $1000/month.
+/// * `years` - The number of years of the horizon.
+/// * `rate` - The annual interest rate (in %).
+/// * `compound_frequency` - How often the interest is compounded.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` if `amount` is negative, `months` is 0, or `years` is out of
+/// range.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{lump_vs_dca, CompoundFrequency};
+///
+/// let comparison = lump_vs_dca(12000.0, 12, 10, 7.0, CompoundFrequency::Annual).unwrap();
+/// assert!(comparison.difference() > 0.0);
+/// ```
+pub fn lump_vs_dca(
+    amount: f64,
+    months: u32,
+    years: i32,
+    rate: f64,
+    compound_frequency: CompoundFrequency,
+) -> Result<LumpVsDca, CicError> {
+    if amount < 0.0 || years < 0 {
+        return Err(CicError::Validation(
+            "Negative values are not allowed".into(),
+        ));
+    }
+    if months == 0 {
+        return Err(CicError::Validation(
+            "The number of months must be positive".into(),
+        ));
+    }
+    let lump_sum = Investment {
+        principal: amount,
+        contribution: 0.0,
+        rate,
+        years,
+        compound_frequency,
+        ..Default::default()
+    };
+    let dca = Investment {
+        principal: 0.0,
+        contribution: amount / f64::from(months),
+        contribution_months: Some(months),
+        ..lump_sum.clone()
+    };
+    Ok(LumpVsDca {
+        lump_sum: lump_sum.yearly_summary()?,
+        dca: dca.yearly_summary()?,
+    })
+}
+
+/// Calculates the final balance after tax of the investment in every account type.
+///
+/// The contributions are the same in every account, so the results show the effect of the taxes
+/// alone.
+///
+/// # Returns
+///
+/// Returns the account types with their final balance after withdrawal, see
+/// `Investment::after_tax_amount`, in the order of `AccountType::ALL`.
+///
+/// # Errors
+///
+/// Returns an error if the summary cannot be calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{compare_account_types, Investment};
+///
+/// let investment = Investment { principal: 1000.0, tax_rate: 25.0, ..Default::default() };
+/// let results = compare_account_types(&investment).unwrap();
+/// assert!(results[2].1 > results[0].1);
+/// ```
+pub fn compare_account_types(investment: &Investment) -> Result<Vec<(AccountType, f64)>, CicError> {
+    AccountType::ALL
+        .into_iter()
+        .map(|account_type| {
+            let investment = Investment {
+                account_type,
+                ..investment.clone()
+            };
+            let final_amount = investment
+                .yearly_summary()?
+                .last()
+                .map_or(investment.principal, |s| s.total_amount);
+            Ok((account_type, investment.after_tax_amount(final_amount)))
+        })
+        .collect()
+}
+
+/// Rounds a contribution up to the next multiple of `step`, e.g. $473 to $480 with a step of 10.
+///
+/// Rounding up keeps a solved contribution from falling short of its target. A `step` that is not
+/// positive leaves the contribution unchanged.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::round_up_to_step;
+///
+/// assert_eq!(round_up_to_step(473.0, 10.0), 480.0);
+/// assert_eq!(round_up_to_step(480.0, 50.0), 500.0);
+/// assert_eq!(round_up_to_step(473.0, 0.0), 473.0);
+/// ```
+pub fn round_up_to_step(contribution: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return contribution;
+    }
+    (contribution / step).ceil() * step
+}
+
+/// Expresses a monthly contribution as a percentage of an annual salary.
+///
+/// # Returns
+///
+/// Returns the savings rate in percent, or `None` if the salary is not positive.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::required_savings_rate;
+///
+/// assert_eq!(required_savings_rate(500.0, 60000.0), Some(10.0));
+/// assert_eq!(required_savings_rate(500.0, 0.0), None);
+/// ```
+pub fn required_savings_rate(monthly_contribution: f64, salary: f64) -> Option<f64> {
+    (salary > 0.0).then(|| monthly_contribution * 12.0 / salary * 100.0)
+}
+
+/// Recalculates an investment and compares the result with a stored summary, e.g. from an
+/// archived JSON report.
+///
+/// # Arguments
+///
+/// * `investment` - The input the summary was calculated from.
+/// * `stored` - The stored summary.
+/// * `tolerance` - The largest absolute difference accepted for every amount.
+///
+/// # Returns
+///
+/// Returns the years whose amounts differ by more than `tolerance`, or that are missing or
+/// unexpected. An empty list means the summary matches.
+///
+/// # Errors
+///
+/// Returns an error if the summary cannot be recalculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{verify_summary, Investment};
+///
+/// let investment = Investment::default();
+/// let summary = investment.yearly_summary().unwrap();
+/// assert!(verify_summary(&investment, &summary, 0.01).unwrap().is_empty());
+/// ```
+pub fn verify_summary(
+    investment: &Investment,
+    stored: &[YearlySummary],
+    tolerance: f64,
+) -> Result<Vec<i32>, CicError> {
+    let recalculated = investment.yearly_summary()?;
+    let differs = |a: &YearlySummary, b: &YearlySummary| {
+        [
+            (a.principal, b.principal),
+            (a.annual_contribution, b.annual_contribution),
+            (a.total_contribution, b.total_contribution),
+            (a.annual_match, b.annual_match),
+            (a.total_match, b.total_match),
+            (a.annual_interest, b.annual_interest),
+            (a.total_interest, b.total_interest),
+            (a.total_amount, b.total_amount),
+            (a.real_total_amount, b.real_total_amount),
+        ]
+        .iter()
+        .any(|(a, b)| (a - b).abs() > tolerance || a.is_nan() != b.is_nan())
+            || a.year != b.year
+            || a.real_growth_flag != b.real_growth_flag
+    };
+
+    let years = recalculated.len().max(stored.len());
+    let mismatched = (0..years)
+        .filter(|&i| match (recalculated.get(i), stored.get(i)) {
+            (Some(a), Some(b)) => differs(a, b),
+            _ => true,
+        })
+        .map(|i| i as i32 + 1)
+        .collect();
+    Ok(mismatched)
+}
+
+/// Calculates the compound annual growth rate of the money invested.
+///
+/// Every contribution grows only from the time it is added, so the CAGR is the annual rate that
+/// grows the principal and the contributions to the final balance: the money-weighted return, see
+/// `Investment::money_weighted_return`.
+///
+/// # Arguments
+///
+/// * `investment` - The investment to calculate the CAGR of.
+///
+/// # Returns
+///
+/// Returns the CAGR as a decimal fraction (e.g. `0.05` for 5%), or `None` if nothing was invested
+/// or the summary cannot be calculated.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{cagr, Investment};
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, rate: 5.0, years: 3, ..Default::default() };
+/// assert!((cagr(&investment).unwrap() - 0.05).abs() < 1e-9);
+/// ```
+pub fn cagr(investment: &Investment) -> Option<f64> {
+    investment.money_weighted_return().ok()
+}
+
+/// Converts a total return over several years into the equivalent annual rate.
+///
+/// This is the inverse of compounding an annual rate into a total return, and gives the rate
+/// `cagr` would report for an investment that grows by `total_return` without contributions.
+///
+/// # Arguments
+///
+/// * `total_return` - The total return as a percentage, e.g. `100.0` for doubling the money.
+/// * `years` - The number of years, which may be fractional. Must be positive.
+///
+/// # Returns
+///
+/// Returns the annual rate as a percentage. It is `NaN` if `total_return` is below -100% or
+/// `years` is not positive.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::annualize_return;
+///
+/// assert!((annualize_return(100.0, 10.0) - 7.177).abs() < 0.001);
+/// assert_eq!(annualize_return(21.0, 2.0), 10.0);
+/// ```
+pub fn annualize_return(total_return: f64, years: f64) -> f64 {
+    if years <= 0.0 {
+        return f64::NAN;
+    }
+    ((total_return / 100.0).ln_1p() / years).exp_m1() * 100.0
+}
+
+/// Formats an amount as whole dollars with thousands separators, e.g. `$4,941`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::format_currency;
+///
+/// assert_eq!(format_currency(4940.625), "$4,941");
+/// assert_eq!(format_currency(-1234567.0), "-$1,234,567");
+/// ```
+pub fn format_currency(value: f64) -> String {
+    let digits = format!("{:.0}", value.abs());
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if value < 0.0 && digits != "0" {
+        "-"
+    } else {
+        ""
+    };
+    format!("{}${}", sign, grouped)
+}
+
+/// Formats an amount as dollars abbreviated with a unit, e.g. `$4.9k`, `$1.2M` or `$3.4B`.
+///
+/// Amounts below $1,000 are formatted as whole dollars like `format_currency`. An amount that
+/// rounds up to the next unit uses that unit, e.g. `$1.0M` rather than `$1000.0k`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::humanize;
+///
+/// assert_eq!(humanize(4_940_000.0), "$4.9M");
+/// assert_eq!(humanize(940.0), "$940");
+/// assert_eq!(humanize(-3_400_000_000.0), "-$3.4B");
+/// ```
+pub fn humanize(value: f64) -> String {
+    let abs = value.abs();
+    for (scale, unit) in [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "k")] {
+        // The lowest amount that is still shown as 1.0 of the unit (or $1,000 for thousands).
+        let threshold = if unit == "k" { 999.5 } else { scale * 0.99995 };
+        if abs >= threshold {
+            let sign = if value < 0.0 { "-" } else { "" };
+            return format!("{}${:.1}{}", sign, abs / scale, unit);
+        }
+    }
+    format_currency(value)
+}
+
+/// An error that occurs while plotting a chart.
+///
+/// The plotting functions return it as `CicError::Plot`, so callers can still match on the cause.
+#[derive(Debug, thiserror::Error)]
+pub enum PlotError {
+    /// The chart could not be read or written, e.g. because the output directory does not exist.
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    /// There is nothing to plot because the summary is empty.
+    #[error("There is no data to plot")]
+    EmptyData,
+    /// The drawing backend or the image encoder failed.
+    #[error("Drawing error: {0}")]
+    BackendError(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<image::ImageError> for PlotError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => PlotError::IoError(e),
+            e => PlotError::BackendError(Box::new(e)),
+        }
+    }
+}
+
+impl<E: std::error::Error + Send + Sync + 'static> From<DrawingAreaErrorKind<E>> for PlotError {
+    fn from(e: DrawingAreaErrorKind<E>) -> Self {
+        let e: Box<dyn std::error::Error + Send + Sync> = match e {
+            DrawingAreaErrorKind::BackendError(
+                plotters_backend::DrawingErrorKind::DrawingError(e),
+            ) => Box::new(e),
+            e => return PlotError::BackendError(e.to_string().into()),
+        };
+        // Surface I/O failures of the bitmap backend, e.g. a missing output directory.
+        match e.downcast::<plotters_bitmap::BitMapBackendError>() {
+            Ok(e) => match *e {
+                plotters_bitmap::BitMapBackendError::IOError(e) => PlotError::IoError(e),
+                plotters_bitmap::BitMapBackendError::ImageError(e) => e.into(),
+                e => PlotError::BackendError(Box::new(e)),
+            },
+            Err(e) => PlotError::BackendError(e),
+        }
+    }
+}
+
+/// Options that control how `plot_summary` renders the chart.
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    /// The path of the PNG file the chart is written to.
+    pub path: std::path::PathBuf,
+    /// If `true`, the caption is annotated with the final balance and the CAGR.
+    pub annotate_caption: bool,
+    /// The CAGR of the investment shown in the annotated caption, see `cagr`. It is not derived
+    /// from the summary, so the caption leaves it out if it is `None`.
+    pub cagr: Option<f64>,
+    /// The color of the total amount line.
+    pub color_total: RGBColor,
+    /// The color of the principal + contribution line.
+    pub color_contribution: RGBColor,
+    /// The number of years between two x-axis labels. If `None`, it is derived from the number
+    /// of years with `x_tick_interval`.
+    pub x_tick_interval: Option<usize>,
+    /// The goal amount. If set, a dashed horizontal line is drawn at the goal and the area above
+    /// it is shaded from the year the total amount reaches it.
+    pub goal: Option<f64>,
+    /// The resolution of the chart. The default of `SCREEN_DPI` renders 600x400 pixels; higher
+    /// values scale the size, the fonts and the lines proportionally, e.g. 300 for print.
+    pub dpi: u32,
+    /// If `false`, the legend is not drawn, which keeps small charts such as thumbnails clean.
+    pub legend: bool,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        Self {
+            path: std::path::PathBuf::from("plot.png"),
+            annotate_caption: false,
+            cagr: None,
+            color_total: BLUE,
+            color_contribution: RED,
+            x_tick_interval: None,
+            goal: None,
+            dpi: SCREEN_DPI,
+            legend: true,
+        }
+    }
+}
+
+impl PlotOptions {
+    /// Creates `PlotOptions` from command line arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - The command line argument matches containing the plot options.
+    ///
+    /// # Returns
+    ///
+    /// Returns `PlotOptions` with values from the command line arguments, falling back to the
+    /// defaults. The `cagr` is left to the caller, who has the investment.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        let defaults = Self::default();
+        let color = |id: &str, default: RGBColor| {
+            matches
+                .try_get_one::<RGBColor>(id)
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(default)
+        };
+        Self {
+            annotate_caption: args::get_flag(matches, "annotate-caption"),
+            color_total: color("color-total", defaults.color_total),
+            color_contribution: color("color-contribution", defaults.color_contribution),
+            x_tick_interval: matches
+                .try_get_one::<usize>("x-tick-interval")
+                .ok()
+                .flatten()
+                .copied(),
+            goal: matches.try_get_one::<f64>("goal").ok().flatten().copied(),
+            dpi: matches
+                .try_get_one::<u32>("dpi")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(defaults.dpi),
+            legend: !args::get_flag(matches, "no-legend"),
+            ..defaults
+        }
+    }
+
+    /// Returns the size of the chart in pixels, `CHART_SIZE` scaled by `dpi`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::PlotOptions;
+    ///
+    /// assert_eq!(PlotOptions::default().chart_size(), (600, 400));
+    /// assert_eq!(PlotOptions { dpi: 192, ..Default::default() }.chart_size(), (1200, 800));
+    /// ```
+    pub fn chart_size(&self) -> (u32, u32) {
+        let (width, height) = CHART_SIZE;
+        (self.scaled(width), self.scaled(height))
+    }
+
+    /// Scales a length in pixels at `SCREEN_DPI` to `dpi`.
+    fn scaled(&self, pixels: u32) -> u32 {
+        (u64::from(pixels) * u64::from(self.dpi.max(1)) / u64::from(SCREEN_DPI)).max(1) as u32
+    }
+}
+
+/// Parses a hex color code such as `#1f77b4` into an `RGBColor`.
+///
+/// The leading `#` is optional.
+///
+/// # Arguments
+///
+/// * `s` - The hex color code.
+///
+/// # Returns
+///
+/// Returns the parsed `RGBColor`, or an error message if the code is not six hex digits.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::parse_hex_color;
+/// use plotters::style::RGBColor;
+///
+/// assert_eq!(parse_hex_color("#1f77b4").unwrap(), RGBColor(0x1f, 0x77, 0xb4));
+/// assert!(parse_hex_color("#12345").is_err());
+/// ```
+pub fn parse_hex_color(s: &str) -> Result<RGBColor, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Invalid color: {} (expected a hex code like #1f77b4)",
+            s
+        ));
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string());
+    Ok(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Builds the caption of the chart.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `options` - If `annotate_caption` is set, the final balance and the `cagr` are appended to the
+///   caption.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{chart_caption, PlotOptions, YearlySummary};
+///
+/// let summary = vec![YearlySummary { year: 1, principal: 1000.0, total_contribution: 1200.0, total_amount: 2250.0, ..Default::default() }];
+/// assert_eq!(chart_caption(&summary, &PlotOptions::default()), "Investment Summary");
+/// let annotated = PlotOptions { annotate_caption: true, cagr: Some(0.023), ..Default::default() };
+/// assert_eq!(chart_caption(&summary, &annotated), "Investment Summary — Final: $2,250 (CAGR 2.3%)");
+/// ```
+pub fn chart_caption(summary: &[YearlySummary], options: &PlotOptions) -> String {
+    let caption = String::from("Investment Summary");
+    if !options.annotate_caption {
+        return caption;
+    }
+    match (summary.last(), options.cagr) {
+        (Some(last), Some(cagr)) => format!(
+            "{} — Final: {} (CAGR {:.1}%)",
+            caption,
+            format_currency(last.total_amount),
+            cagr * 100.0
+        ),
+        (Some(last), None) => format!(
+            "{} — Final: {}",
+            caption,
+            format_currency(last.total_amount)
+        ),
+        _ => caption,
+    }
+}
+
+/// Represents a summary as one column per field, e.g. for plotting libraries and numeric tools.
+///
+/// The columns are as long as the summary, and the value at index `i` of every column belongs to
+/// the `i`-th `YearlySummary`. See the fields of `YearlySummary` for their meaning.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SummaryColumns {
+    pub years: Vec<i32>,
+    pub principal: Vec<f64>,
+    pub annual_contribution: Vec<f64>,
+    pub total_contribution: Vec<f64>,
+    pub annual_match: Vec<f64>,
+    pub total_match: Vec<f64>,
+    pub annual_interest: Vec<f64>,
+    pub total_interest: Vec<f64>,
+    pub total_amount: Vec<f64>,
+    pub real_total_amount: Vec<f64>,
+    pub real_growth_flag: Vec<bool>,
+}
+
+/// Returns the summary as column-major arrays, with one column per field.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{summary_columns, Investment};
+///
+/// let summary = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() }.yearly_summary().unwrap();
+/// let columns = summary_columns(&summary);
+/// assert_eq!(columns.years, vec![1, 2, 3]);
+/// assert_eq!(columns.total_contribution, vec![1200.0, 2400.0, 3600.0]);
+/// ```
+pub fn summary_columns(summary: &[YearlySummary]) -> SummaryColumns {
+    let mut columns = SummaryColumns::default();
+    for s in summary {
+        columns.years.push(s.year);
+        columns.principal.push(s.principal);
+        columns.annual_contribution.push(s.annual_contribution);
+        columns.total_contribution.push(s.total_contribution);
+        columns.annual_match.push(s.annual_match);
+        columns.total_match.push(s.total_match);
+        columns.annual_interest.push(s.annual_interest);
+        columns.total_interest.push(s.total_interest);
+        columns.total_amount.push(s.total_amount);
+        columns.real_total_amount.push(s.real_total_amount);
+        columns.real_growth_flag.push(s.real_growth_flag);
+    }
+    columns
+}
+
+/// Returns the first year in which the total amount reaches the goal.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{goal_year, Investment};
+///
+/// let summary = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() }.yearly_summary().unwrap();
+/// assert_eq!(goal_year(&summary, 3000.0), Some(2));
+/// assert_eq!(goal_year(&summary, 10000.0), None);
+/// ```
+pub fn goal_year(summary: &[YearlySummary], goal: f64) -> Option<i32> {
+    summary
+        .iter()
+        .find(|s| s.total_amount >= goal)
+        .map(|s| s.year)
+}
+
+/// Returns the first year in which the interest earned exceeds the contribution of that year, the
+/// point from which the money works harder than the saver.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{crossover_year, Investment};
+///
+/// let summary = Investment { contribution: 100.0, rate: 10.0, years: 15, ..Default::default() }.yearly_summary().unwrap();
+/// assert_eq!(crossover_year(&summary), Some(9));
+/// ```
+pub fn crossover_year(summary: &[YearlySummary]) -> Option<i32> {
+    summary
+        .iter()
+        .find(|s| s.annual_interest > s.annual_contribution)
+        .map(|s| s.year)
+}
+
+/// Returns the year in which a debt is paid off, i.e. the first year the balance is no longer
+/// negative.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{payoff_year, Investment};
+///
+/// let debt = Investment { principal: -5000.0, contribution: 200.0, rate: 18.0, years: 5, debt: true, ..Default::default() };
+/// assert_eq!(payoff_year(&debt.yearly_summary().unwrap()), Some(3));
+/// ```
+pub fn payoff_year(summary: &[YearlySummary]) -> Option<i32> {
+    goal_year(summary, 0.0)
+}
+
+/// Returns the first year in which the total amount reaches each milestone.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `milestones` - The amounts to look for, e.g. `[100000.0, 250000.0, 500000.0, 1000000.0]`.
+///
+/// # Returns
+///
+/// Returns every milestone with the year it is first reached, or `None` if it is never reached,
+/// in the order of `milestones`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{milestone_years, Investment};
+///
+/// let summary = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() }.yearly_summary().unwrap();
+/// assert_eq!(milestone_years(&summary, &[3000.0, 10000.0]), vec![(3000.0, Some(2)), (10000.0, None)]);
+/// ```
+pub fn milestone_years(summary: &[YearlySummary], milestones: &[f64]) -> Vec<(f64, Option<i32>)> {
+    milestones
+        .iter()
+        .map(|&milestone| (milestone, goal_year(summary, milestone)))
+        .collect()
+}
+
+/// Builds the caption of the chart, noting a goal that is never reached.
+fn caption_with_goal(summary: &[YearlySummary], options: &PlotOptions) -> String {
+    let caption = chart_caption(summary, options);
+    match options.goal {
+        Some(goal) if goal_year(summary, goal).is_none() => {
+            format!("{} — Goal {} not reached", caption, format_currency(goal))
+        }
+        _ => caption,
+    }
+}
+
+/// Plots the investment summary as a line chart.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `options` - The `PlotOptions` controlling the output path, the caption, and the colors.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a `CicError::Plot` holding `PlotError::EmptyData` if the summary
+/// is empty, or another `PlotError` if the chart cannot be drawn or written.
+///
+/// # Example
+///
+/// ```no_run
+/// use cic::calculations::{plot_summary, PlotOptions, YearlySummary};
+///
+/// let summary = vec![
+///     YearlySummary { year: 1, principal: 1000.0, annual_contribution: 1200.0, total_contribution: 1200.0, annual_match: 0.0, total_match: 0.0, annual_interest: 50.0, total_interest: 50.0, total_amount: 2150.0, real_total_amount: 2150.0, real_growth_flag: true },
+///     // Add more summaries here
+/// ];
+/// plot_summary(&summary, &PlotOptions::default()).expect("Failed to plot summary");
+/// ```
+pub fn plot_summary(summary: &[YearlySummary], options: &PlotOptions) -> Result<(), CicError> {
+    // The backend writes the file when dropped, so bail out before creating it.
+    if summary.is_empty() {
+        return Err(PlotError::EmptyData.into());
+    }
+    let root = BitMapBackend::new(&options.path, options.chart_size()).into_drawing_area();
+    draw_summary(&root, summary, options)?;
+    root.present().map_err(PlotError::from)?;
+    Ok(())
+}
+
+/// Renders the investment summary as a line chart into an in-memory PNG image.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `options` - The `PlotOptions` controlling the caption and the colors. The path is ignored.
+///
+/// # Returns
+///
+/// Returns the PNG-encoded bytes of the chart, or a `CicError::Plot` holding
+/// `PlotError::EmptyData` if the summary is empty, or another `PlotError` if rendering or encoding
+/// fails.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{render_png, Investment, PlotOptions};
+///
+/// let summary = Investment::default().yearly_summary().unwrap();
+/// let png = render_png(&summary, &PlotOptions::default()).expect("Failed to render chart");
+/// assert!(png.starts_with(b"\x89PNG"));
+/// ```
+pub fn render_png(summary: &[YearlySummary], options: &PlotOptions) -> Result<Vec<u8>, CicError> {
+    let (width, height) = options.chart_size();
+    let mut buffer = vec![0; width as usize * height as usize * 3];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+        draw_summary(&root, summary, options)?;
+        root.present().map_err(PlotError::from)?;
+    }
+
+    let image = image::RgbImage::from_raw(width, height, buffer).ok_or_else(|| {
+        PlotError::BackendError("The chart buffer does not match the chart size".into())
+    })?;
+    let mut png = Vec::new();
+    image
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .map_err(PlotError::from)?;
+    Ok(png)
+}
+
+/// Renders the chart of `plot_summary` as an SVG document instead of writing it to a file.
+///
+/// `options.path` is ignored. The size and the styling are the same as for the PNG, so the SVG can
+/// be inlined in a web page.
+///
+/// # Returns
+///
+/// Returns the SVG document, or a `CicError::Plot` holding `PlotError::EmptyData` if the summary is
+/// empty, or another `PlotError` if rendering fails.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{render_svg, Investment, PlotOptions};
+///
+/// let summary = Investment::default().yearly_summary().unwrap();
+/// let svg = render_svg(&summary, &PlotOptions::default()).expect("Failed to render chart");
+/// assert!(svg.starts_with("<svg"));
+/// ```
+pub fn render_svg(summary: &[YearlySummary], options: &PlotOptions) -> Result<String, CicError> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, options.chart_size()).into_drawing_area();
+        draw_summary(&root, summary, options)?;
+        root.present().map_err(PlotError::from)?;
+    }
+    Ok(svg)
+}
+
+/// Returns a sensible number of years between two x-axis labels for the given horizon.
+///
+/// Every year is labeled up to 10 years; longer horizons get roughly 10 labels at a round interval.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::x_tick_interval;
+///
+/// assert_eq!(x_tick_interval(10), 1);
+/// assert_eq!(x_tick_interval(30), 5);
+/// assert_eq!(x_tick_interval(100), 10);
+/// ```
+pub fn x_tick_interval(years: usize) -> usize {
+    match years {
+        0..=10 => 1,
+        11..=20 => 2,
+        21..=50 => 5,
+        _ => years.div_ceil(100) * 10,
+    }
+}
+
+/// Plots how the total amount splits into contributions and interest as a 100%-stacked area chart.
+///
+/// The interest share growing over the years shows compounding taking over.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `path` - The path of the PNG file the chart is written to.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a `CicError::Plot` if the summary is empty or the chart cannot
+/// be drawn or written.
+///
+/// # Example
+///
+/// ```no_run
+/// use cic::calculations::{plot_composition_pct, Investment};
+///
+/// let summary = Investment { years: 30, ..Default::default() }.yearly_summary().unwrap();
+/// plot_composition_pct(&summary, "composition.png".as_ref()).expect("Failed to plot composition");
+/// ```
+pub fn plot_composition_pct(
+    summary: &[YearlySummary],
+    path: &std::path::Path,
+) -> Result<(), CicError> {
+    if summary.is_empty() {
+        return Err(PlotError::EmptyData.into());
+    }
+    let root = BitMapBackend::new(path, CHART_SIZE).into_drawing_area();
+    draw_composition(&root, summary)?;
+    root.present().map_err(PlotError::from)?;
+    Ok(())
+}
+
+fn draw_composition<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    summary: &[YearlySummary],
+) -> Result<(), PlotError>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    // Opaque colors, so that the contribution area does not blend with the interest area below.
+    let interest_color = RGBColor(150, 150, 255);
+    let contribution_color = RGBColor(255, 150, 150);
+    let first_year = summary[0].year;
+    let last_year = summary[summary.len() - 1].year;
+    let mut chart = ChartBuilder::on(root)
+        .caption("Contributions vs Interest", ("sans-serif", 30).into_font())
+        .x_label_area_size(35)
+        .y_label_area_size(60)
+        .margin(20)
+        .build_cartesian_2d(first_year..last_year.max(first_year + 1), 0.0..100.0)?;
+    chart
+        .configure_mesh()
+        .x_desc("Year")
+        .y_desc("Share of total (%)")
+        .draw()?;
+
+    // The interest area fills everything above the contribution area, so it is drawn as 100%.
+    chart
+        .draw_series(AreaSeries::new(
+            summary.iter().map(|s| (s.year, 100.0)),
+            0.0,
+            interest_color,
+        ))?
+        .label("Interest")
+        .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], interest_color.filled()));
+    chart
+        .draw_series(AreaSeries::new(
+            summary.iter().map(|s| (s.year, s.composition_pct().0)),
+            0.0,
+            contribution_color,
+        ))?
+        .label("Principal + Contribution")
+        .legend(move |(x, y)| {
+            Rectangle::new([(x, y - 5), (x + 10, y + 5)], contribution_color.filled())
+        });
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(WHITE)
+        .draw()?;
+    Ok(())
+}
+
+/// Plots a fan chart of the total amount at a low, an expected and a high rate.
+///
+/// The three trajectories are drawn as lines, and the range between the low and the high one is
+/// shaded, to show the uncertainty of the rate without a full simulation.
+///
+/// # Arguments
+///
+/// * `summaries` - The yearly summaries at the low, the expected and the high rate, in this order.
+/// * `path` - The path of the PNG file the chart is written to.
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or a `CicError::Plot` if a summary is empty or the chart cannot be
+/// drawn or written.
+///
+/// # Example
+///
+/// ```no_run
+/// use cic::calculations::{plot_fan, Investment};
+///
+/// let summary = |rate| Investment { rate, years: 30, ..Default::default() }.yearly_summary().unwrap();
+/// let (low, expected, high) = (summary(4.0), summary(6.0), summary(8.0));
+/// plot_fan([&low, &expected, &high], "fan.png".as_ref()).expect("Failed to plot fan chart");
+/// ```
+pub fn plot_fan(summaries: [&[YearlySummary]; 3], path: &std::path::Path) -> Result<(), CicError> {
+    if summaries.iter().any(|summary| summary.is_empty()) {
+        return Err(PlotError::EmptyData.into());
+    }
+    let root = BitMapBackend::new(path, CHART_SIZE).into_drawing_area();
+    draw_fan(&root, summaries)?;
+    root.present().map_err(PlotError::from)?;
+    Ok(())
+}
+
+/// The colors of the low, the expected and the high trajectory of a fan chart.
+const FAN_COLORS: [RGBColor; 3] = [RED, BLUE, GREEN];
+
+fn draw_fan<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    summaries: [&[YearlySummary]; 3],
+) -> Result<(), PlotError>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+
+    let [low, _, high] = summaries;
+    let amounts = summaries
+        .iter()
+        .flat_map(|summary| summary.iter().map(|s| s.total_amount));
+    let max = amounts.clone().fold(0.0, f64::max);
+    let min = amounts.fold(0.0, f64::min);
+    let first_year = low[0].year;
+    let last_year = summaries
+        .iter()
+        .map(|summary| summary[summary.len() - 1].year)
+        .max()
+        .unwrap_or(first_year);
+    let mut chart = ChartBuilder::on(root)
+        .caption("Rate Range", ("sans-serif", 30).into_font())
+        .x_label_area_size(35)
+        .y_label_area_size(60)
+        .margin(20)
+        .build_cartesian_2d(first_year..last_year.max(first_year + 1), min..max.max(1.0))?;
+    chart
+        .configure_mesh()
+        .x_desc("Year")
+        .y_desc("Total Amount")
+        .draw()?;
+
+    // The shading runs along the high trajectory and back along the low one.
+    let band = high
+        .iter()
+        .map(|s| (s.year, s.total_amount))
+        .chain(low.iter().rev().map(|s| (s.year, s.total_amount)));
+    chart.draw_series(std::iter::once(Polygon::new(
+        band.collect::<Vec<_>>(),
+        BLUE.mix(0.15),
+    )))?;
+
+    for (summary, (label, color)) in summaries
+        .into_iter()
+        .zip(["Low", "Expected", "High"].into_iter().zip(FAN_COLORS))
+    {
+        chart
+            .draw_series(LineSeries::new(
+                summary.iter().map(|s| (s.year, s.total_amount)),
+                color.stroke_width(2),
+            ))?
+            .label(label)
+            .legend(move |(x, y)| {
+                PathElement::new(vec![(x, y), (x + 10, y)], color.stroke_width(2))
+            });
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE)
+        .draw()?;
+    Ok(())
+}
+
+/// The size of the chart in pixels at `SCREEN_DPI`.
+const CHART_SIZE: (u32, u32) = (600, 400);
+
+/// The resolution `CHART_SIZE` and the font sizes of the chart are designed for.
+pub const SCREEN_DPI: u32 = 96;
+
+/// Draws the chart of the investment summary onto an existing drawing area.
+///
+/// This is what `plot_summary`, `render_png` and `render_svg` draw onto their whole canvas. An area
+/// of a larger canvas can be passed instead, e.g. a half from `DrawingArea::split_evenly`, to
+/// combine several charts into a dashboard. The area is filled with white first, and presenting
+/// the canvas is left to the caller.
+///
+/// # Arguments
+///
+/// * `area` - The drawing area to draw onto.
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `options` - The `PlotOptions` controlling the caption and the colors. The path and the size
+///   are ignored.
+///
+/// # Returns
+///
+/// Returns `PlotError::EmptyData` if the summary is empty, or another `PlotError` if the backend
+/// fails.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::{draw_summary, Investment, PlotOptions};
+/// use plotters::prelude::*;
+///
+/// let summary = Investment::default().yearly_summary().unwrap();
+/// let mut svg = String::new();
+/// {
+///     let root = SVGBackend::with_string(&mut svg, (1200, 400)).into_drawing_area();
+///     let halves = root.split_evenly((1, 2));
+///     draw_summary(&halves[0], &summary, &PlotOptions::default()).unwrap();
+///     draw_summary(&halves[1], &summary, &PlotOptions::default()).unwrap();
+///     root.present().unwrap();
+/// }
+/// assert!(svg.starts_with("<svg"));
+/// ```
+pub fn draw_summary<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    summary: &[YearlySummary],
+    options: &PlotOptions,
+) -> Result<(), PlotError>
+where
+    DB::ErrorType: 'static,
+{
+    // Interest is negative while a debt is paid down, so the contributions can exceed the total.
+    let max_amount = summary
+        .iter()
+        .map(|s| s.total_amount.max(s.principal + s.total_contribution))
+        .max_by(|a, b| a.total_cmp(b))
+        .ok_or(PlotError::EmptyData)?;
+    area.fill(&WHITE)?;
+
+    // Keep the goal line inside the chart even if it is never reached.
+    let max_amount = max_amount.max(options.goal.unwrap_or(0.0));
+    // A debt starts below zero, so the axis has to reach down to the lowest balance.
+    let min_amount = summary
+        .iter()
+        .map(|s| s.total_amount.min(s.principal))
+        .fold(0.0, f64::min);
+    let caption = caption_with_goal(summary, options);
+    // Every size is designed at `SCREEN_DPI` and scaled, so a high-DPI chart looks the same.
+    let px = |pixels: u32| options.scaled(pixels);
+    // Annotations make the caption longer, so it gets a smaller font to fit the chart.
+    let font_size = if caption.chars().count() > 20 { 20 } else { 30 };
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, ("sans-serif", px(font_size)).into_font())
+        .x_label_area_size(px(35))
+        .y_label_area_size(px(100))
+        .margin(px(20))
+        .build_cartesian_2d(1..summary.len(), min_amount..max_amount)?;
+
+    let interval = options
+        .x_tick_interval
+        .unwrap_or_else(|| x_tick_interval(summary.len()))
+        .max(1);
+    chart
+        .configure_mesh()
+        .x_labels(summary.len().saturating_sub(1) / interval + 1)
+        .label_style(("sans-serif", px(12)))
+        .set_all_tick_mark_size(px(5))
+        .axis_style(BLACK.stroke_width(px(1)))
+        .bold_line_style(BLACK.mix(0.2).stroke_width(px(1)))
+        .light_line_style(BLACK.mix(0.1).stroke_width(px(1)))
+        .x_desc("Year")
+        .y_desc("Amount")
+        .draw()?;
+    let line_width = px(1);
+    let legend_length = px(10) as i32;
+
+    let columns = summary_columns(summary);
+    let years: Vec<usize> = columns.years.iter().map(|&year| year as usize).collect();
+    let principal_and_contribution: Vec<f64> = columns
+        .principal
+        .iter()
+        .zip(&columns.total_contribution)
+        .map(|(principal, contribution)| principal + contribution)
+        .collect();
+    let total_amount = columns.total_amount;
+
+    chart
+        .draw_series(LineSeries::new(
+            years
+                .iter()
+                .zip(principal_and_contribution.iter())
+                .map(|(x, y)| (*x, *y)),
+            options.color_contribution.stroke_width(line_width),
+        ))?
+        .label("Principal + Contribution")
+        .legend(move |(x, y)| {
+            PathElement::new(
+                vec![(x, y), (x + legend_length, y)],
+                options.color_contribution.stroke_width(line_width),
+            )
+        });
+
+    chart
+        .draw_series(LineSeries::new(
+            years.iter().zip(total_amount.iter()).map(|(x, y)| (*x, *y)),
+            options.color_total.stroke_width(line_width),
+        ))?
+        .label("Total Amount")
+        .legend(move |(x, y)| {
+            PathElement::new(
+                vec![(x, y), (x + legend_length, y)],
+                options.color_total.stroke_width(line_width),
+            )
+        });
+
+    if let Some(goal) = options.goal {
+        let goal_color = BLACK.mix(0.6);
+        chart
+            .draw_series(DashedLineSeries::new(
+                [(1, goal), (summary.len(), goal)],
+                px(10),
+                px(5),
+                goal_color.stroke_width(line_width),
+            ))?
+            .label(format!("Goal {}", format_currency(goal)))
+            .legend(move |(x, y)| {
+                PathElement::new(
+                    vec![(x, y), (x + legend_length, y)],
+                    goal_color.stroke_width(line_width),
+                )
+            });
+
+        if let Some(year) = goal_year(summary, goal) {
+            chart.draw_series(AreaSeries::new(
+                years
+                    .iter()
+                    .zip(total_amount.iter())
+                    .filter(|(x, _)| **x >= year as usize)
+                    .map(|(x, y)| (*x, *y)),
+                goal,
+                options.color_total.mix(0.2),
+            ))?;
+            chart.draw_series(std::iter::once(Text::new(
+                format!("Goal reached in year {}", year),
+                (year as usize, goal),
+                ("sans-serif", px(15)).into_font(),
+            )))?;
+        }
+    }
+
+    if options.legend {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .label_font(("sans-serif", px(12)))
+            .legend_area_size(px(30))
+            .margin(px(10))
+            .draw()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_investment_initialization() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(investment.principal, 1000.0);
+        assert_eq!(investment.contribution, 100.0);
+        assert_eq!(investment.rate, 5.0);
+        assert_eq!(investment.years, 10);
+    }
+
+    #[test]
+    fn test_yearly_summary() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 3,
+            ..Default::default()
+        };
+
+        let summary = investment.yearly_summary().unwrap();
+        assert_eq!(summary.len(), 3);
+
+        // Year 1
+        assert_eq!(summary[0].year, 1);
+        assert_eq!(summary[0].principal, 1000.0);
+        assert_eq!(summary[0].annual_contribution, 1200.0);
+        assert!((summary[0].annual_interest - 50.0).abs() < 1e-2);
+        assert_eq!(summary[0].total_contribution, 1200.0);
+        assert!((summary[0].total_interest - 50.0).abs() < 1e-2);
+        assert!((summary[0].total_amount - 2250.0).abs() < 1e-2);
+
+        // Year 2
+        assert_eq!(summary[1].year, 2);
+        assert_eq!(summary[1].principal, 1000.0);
+        assert_eq!(summary[1].annual_contribution, 1200.0);
+        assert!((summary[1].annual_interest - 112.5).abs() < 1e-2);
+        assert_eq!(summary[1].total_contribution, 2400.0);
+        assert!((summary[1].total_interest - 162.5).abs() < 1e-2);
+        assert!((summary[1].total_amount - 3562.5).abs() < 1e-2);
+
+        // Year 3
+        assert_eq!(summary[2].year, 3);
+        assert_eq!(summary[2].principal, 1000.0);
+        assert_eq!(summary[2].annual_contribution, 1200.0);
+        assert!((summary[2].annual_interest - 178.125).abs() < 1e-2);
+        assert_eq!(summary[2].total_contribution, 3600.0);
+        assert!((summary[2].total_interest - 340.625).abs() < 1e-2);
+        assert!((summary[2].total_amount - 4940.625).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_rate_format_percent_and_decimal_are_equivalent() {
+        let cli = crate::args::build_cli();
+        let percent = cli
+            .clone()
+            .get_matches_from(["cic", "-p", "1000", "-c", "100", "-r", "5", "-y", "3"]);
+        let decimal = cli.get_matches_from([
+            "cic",
+            "-p",
+            "1000",
+            "-c",
+            "100",
+            "-r",
+            "0.05",
+            "-y",
+            "3",
+            "--rate-format",
+            "decimal",
+        ]);
+
+        let percent = Investment::from_matches(&percent);
+        let decimal = Investment::from_matches(&decimal);
+        assert_eq!(decimal.rate, 5.0);
+        let percent_summary = percent.yearly_summary().unwrap();
+        let decimal_summary = decimal.yearly_summary().unwrap();
+        assert_eq!(percent_summary.len(), decimal_summary.len());
+        for (p, d) in percent_summary.iter().zip(decimal_summary.iter()) {
+            assert!((p.total_amount - d.total_amount).abs() < 1e-9);
+            assert!((p.total_interest - d.total_interest).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_rate_warning() {
+        let suspicious = Investment {
+            rate: 0.05,
+            ..Default::default()
+        };
+        assert!(suspicious.rate_warning(RateFormat::Percent).is_some());
+        assert!(suspicious.rate_warning(RateFormat::Decimal).is_none());
+
+        let percent = Investment {
+            rate: 5.0,
+            ..Default::default()
+        };
+        assert!(percent.rate_warning(RateFormat::Percent).is_none());
+    }
+
+    #[test]
+    fn test_rate_tier() {
+        let tier = RateTier {
+            threshold: 20000.0,
+            low_rate: 1.0,
+            high_rate: 5.0,
+        };
+        let investment = Investment {
+            principal: 0.0,
+            contribution: 1000.0,
+            years: 4,
+            tier: Some(tier),
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        // Below the threshold, only the low rate is paid.
+        assert!((summary[1].annual_interest - 120.0).abs() < 1e-9);
+        // In year 3 the balance of 24,120 crosses the threshold: 200 + 4,120 at 5%.
+        assert!((summary[2].annual_interest - 406.0).abs() < 1e-9);
+        let effective = |i: usize| summary[i].annual_interest / summary[i - 1].total_amount;
+        assert!((effective(1) - 0.01).abs() < 1e-12);
+        assert!(effective(2) > 0.01 && effective(3) > effective(2));
+
+        let low_only = Investment {
+            tier: None,
+            rate: 1.0,
+            ..investment.clone()
+        };
+        assert!(summary[3].total_interest > low_only.yearly_summary().unwrap()[3].total_interest);
+
+        assert_eq!("20000:1:5".parse::<RateTier>(), Ok(tier));
+        assert!("20000:1".parse::<RateTier>().is_err());
+        assert!("20000:-1:5".parse::<RateTier>().is_err());
+        let matches = crate::args::build_cli().get_matches_from(["cic", "--tier", "20000:1:5"]);
+        assert_eq!(Investment::from_matches(&matches).tier, Some(tier));
+    }
+
+    #[test]
+    fn test_effective_contributions() {
+        // Contributions indexed to 10% inflation, paused in the second year.
+        let investment = Investment {
+            contribution: 100.0,
+            inflation: 10.0,
+            index_contributions_to_inflation: true,
+            pause_years: Some((2, 2)),
+            years: 3,
+            ..Default::default()
+        };
+        let contributions = effective_contributions(&investment);
+        assert_eq!(contributions.len(), 36);
+        assert!(contributions[..12].iter().all(|&c| c == 100.0));
+        assert!(contributions[12..24].iter().all(|&c| c == 0.0));
+        assert!(contributions[24..]
+            .iter()
+            .all(|&c| (c - 121.0).abs() < 1e-9));
+
+        // Summer skips on top of a contribution window that ends in the second year.
+        let investment = Investment {
+            contribution: 50.0,
+            skip_months: vec![7, 8],
+            contribution_months: Some(15),
+            years: 2,
+            ..Default::default()
+        };
+        let contributions = effective_contributions(&investment);
+        let expected: Vec<f64> = (1..=24)
+            .map(|month| match month {
+                7 | 8 => 0.0,
+                m if m > 15 => 0.0,
+                _ => 50.0,
+            })
+            .collect();
+        assert_eq!(contributions, expected);
+
+        // The summary deposits exactly the vector.
+        let summary = investment.yearly_summary().unwrap();
+        let deposited: f64 = summary.iter().map(|s| s.annual_contribution).sum();
+        assert_eq!(deposited, contributions.iter().sum::<f64>());
+    }
+
+    #[test]
+    fn test_offset_expense() {
+        let investment = Investment {
+            principal: 10000.0,
+            contribution: 500.0,
+            rate: 6.0,
+            years: 15,
+            ..Default::default()
+        };
+        let original = investment
+            .yearly_summary()
+            .unwrap()
+            .last()
+            .unwrap()
+            .total_amount;
+        let extra = offset_expense(&investment, 8, 30000.0).unwrap();
+        assert!(extra > 0.0);
+
+        let offset = Investment {
+            contribution: investment.contribution + extra,
+            lump_sums: vec![(8, -30000.0)],
+            ..investment.clone()
+        };
+        let summary = offset.yearly_summary().unwrap();
+        let expected = (500.0 + extra) * 12.0 - 30000.0;
+        assert!((summary[7].annual_contribution - expected).abs() < 1e-6);
+        let restored = summary.last().unwrap().total_amount;
+        assert!(restored >= original);
+        assert!(restored - original < 1.0);
+
+        // Without the extra contribution, the expense derails the plan.
+        let derailed = Investment {
+            lump_sums: vec![(8, -30000.0)],
+            ..investment.clone()
+        };
+        assert!(
+            derailed
+                .yearly_summary()
+                .unwrap()
+                .last()
+                .unwrap()
+                .total_amount
+                < original
+        );
+
+        assert_eq!(offset_expense(&investment, 8, 0.0).unwrap(), 0.0);
+        assert!(offset_expense(&investment, 16, 1000.0).is_err());
+        assert!(offset_expense(&investment, 8, -1000.0).is_err());
+    }
+
+    #[test]
+    fn test_humanize() {
+        assert_eq!(humanize(4_940_000.0), "$4.9M");
+        assert_eq!(humanize(940.0), "$940");
+        assert_eq!(humanize(4_940.0), "$4.9k");
+        assert_eq!(humanize(3_400_000_000.0), "$3.4B");
+        assert_eq!(humanize(2_500_000_000_000.0), "$2.5T");
+        assert_eq!(humanize(-1_250_000.0), "-$1.2M");
+        assert_eq!(humanize(0.0), "$0");
+        // Amounts that round up use the next unit.
+        assert_eq!(humanize(999.6), "$1.0k");
+        assert_eq!(humanize(999_960.0), "$1.0M");
+        assert_eq!(humanize(999_940.0), "$999.9k");
+    }
+
+    #[test]
+    fn test_required_principal() {
+        for (contribution, rate, years, target) in [
+            (100.0, 5.0, 10, 50000.0),
+            (0.0, 7.0, 30, 1_000_000.0),
+            (250.0, 0.0, 5, 20000.0),
+        ] {
+            let principal = required_principal(contribution, rate, years, target);
+            assert!(principal > 0.0);
+            let final_amount = Investment {
+                principal,
+                contribution,
+                rate,
+                years,
+                ..Default::default()
+            }
+            .yearly_summary()
+            .unwrap()
+            .last()
+            .unwrap()
+            .total_amount;
+            assert!((final_amount - target).abs() < 1e-6, "{}", final_amount);
+        }
+
+        // A fee and the yearly tax lower the rate the principal has to grow at.
+        let investment = Investment {
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            fee: 2.0,
+            tax_rate: 20.0,
+            ..Default::default()
+        };
+        let net_rate = investment.net_rate_for_year(1) * 100.0;
+        let principal = required_principal(100.0, net_rate, 10, 50000.0);
+        assert!(principal > required_principal(100.0, 5.0, 10, 50000.0));
+        let summary = Investment {
+            principal,
+            ..investment
+        }
+        .yearly_summary()
+        .unwrap();
+        let final_amount = summary.last().unwrap().total_amount;
+        assert!((final_amount - 50000.0).abs() < 1e-6, "{}", final_amount);
+
+        // The contributions alone reach the target.
+        assert_eq!(required_principal(1000.0, 5.0, 10, 50000.0), 0.0);
+        assert_eq!(required_principal(100.0, 5.0, 0, 5000.0), 5000.0);
+    }
+
+    #[test]
+    fn test_mid_year_contribution() {
+        let end_of_year = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 10.0,
+            years: 2,
+            ..Default::default()
+        };
+        let mid_year = Investment {
+            mid_year_contribution: true,
+            ..end_of_year.clone()
+        };
+        let end_of_year = end_of_year.yearly_summary().unwrap();
+        let mid_year = mid_year.yearly_summary().unwrap();
+
+        // Half a year of interest on the first year's $1,200.
+        assert!((end_of_year[0].annual_interest - 100.0).abs() < 1e-9);
+        assert!((mid_year[0].annual_interest - 160.0).abs() < 1e-9);
+        for (end, mid) in end_of_year.iter().zip(&mid_year) {
+            assert!(mid.annual_interest > end.annual_interest);
+            assert!(mid.total_amount > end.total_amount);
+            assert_eq!(mid.total_contribution, end.total_contribution);
+        }
+        // The advantage is small compared to the balance.
+        let last = (mid_year[1].total_amount, end_of_year[1].total_amount);
+        assert!(last.0 / last.1 - 1.0 < 0.05);
+    }
+
+    #[test]
+    fn test_bi_weekly_contributions() {
+        let monthly = Investment {
+            contribution: 1000.0,
+            rate: 5.0,
+            years: 1,
+            compound_frequency: CompoundFrequency::Monthly,
+            ..Default::default()
+        };
+        // Half the monthly amount from every paycheck adds up to 13 months of contributions.
+        let bi_weekly = Investment {
+            contribution: 500.0,
+            contribution_frequency: ContributionFrequency::BiWeekly,
+            ..monthly.clone()
+        };
+        assert_eq!(bi_weekly.annual_contribution(1), 13000.0);
+        let months: u32 = (1..=12)
+            .map(|month| ContributionFrequency::BiWeekly.contributions_in_month(month))
+            .sum();
+        assert_eq!(months, 26);
+
+        let monthly = monthly.yearly_summary().unwrap();
+        let bi_weekly = bi_weekly.yearly_summary().unwrap();
+        assert_eq!(bi_weekly[0].total_contribution, 13000.0);
+        assert!(bi_weekly[0].total_amount > monthly[0].total_amount);
+        assert!(bi_weekly[0].annual_interest > monthly[0].annual_interest);
+
+        // A salary-based contribution saves the same amount per year, only split differently.
+        let salary = Investment {
+            salary: 52000.0,
+            contribution_frequency: ContributionFrequency::BiWeekly,
+            ..Default::default()
+        };
+        assert!((salary.contribution_per_period(1) - 200.0).abs() < 1e-9);
+        assert!((salary.annual_contribution(1) - 5200.0).abs() < 1e-9);
+
+        let matches = crate::args::build_cli().get_matches_from([
+            "cic",
+            "--contribution-frequency",
+            "bi-weekly",
+        ]);
+        assert_eq!(
+            Investment::from_matches(&matches).contribution_frequency,
+            ContributionFrequency::BiWeekly
+        );
+        assert!("weekly".parse::<ContributionFrequency>().is_err());
+    }
+
+    #[test]
+    fn test_summary_columns() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            inflation: 2.0,
+            match_rate: 50.0,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let columns = summary_columns(&summary);
+
+        let lengths = [
+            columns.years.len(),
+            columns.principal.len(),
+            columns.annual_contribution.len(),
+            columns.total_contribution.len(),
+            columns.annual_match.len(),
+            columns.total_match.len(),
+            columns.annual_interest.len(),
+            columns.total_interest.len(),
+            columns.total_amount.len(),
+            columns.real_total_amount.len(),
+            columns.real_growth_flag.len(),
+        ];
+        assert!(lengths.iter().all(|&length| length == summary.len()));
+        for (i, s) in summary.iter().enumerate() {
+            assert_eq!(columns.years[i], s.year);
+            assert_eq!(columns.principal[i], s.principal);
+            assert_eq!(columns.annual_contribution[i], s.annual_contribution);
+            assert_eq!(columns.total_contribution[i], s.total_contribution);
+            assert_eq!(columns.annual_match[i], s.annual_match);
+            assert_eq!(columns.total_match[i], s.total_match);
+            assert_eq!(columns.annual_interest[i], s.annual_interest);
+            assert_eq!(columns.total_interest[i], s.total_interest);
+            assert_eq!(columns.total_amount[i], s.total_amount);
+            assert_eq!(columns.real_total_amount[i], s.real_total_amount);
+            assert_eq!(columns.real_growth_flag[i], s.real_growth_flag);
+        }
+        assert_eq!(summary_columns(&[]), SummaryColumns::default());
+    }
+
+    #[test]
+    fn test_allocation() {
+        let allocation: Allocation = "stocks:60:8,bonds:40:3".parse().unwrap();
+        assert_eq!(allocation.assets.len(), 2);
+        assert_eq!(
+            allocation.assets[1],
+            AssetClass {
+                name: "bonds".into(),
+                weight: 40.0,
+                rate: 3.0
+            }
+        );
+        assert_eq!(allocation.blended_rate(), 6.0);
+        assert_eq!(allocation.to_string(), "stocks 60% at 8%, bonds 40% at 3%");
+
+        let thirds: Allocation = "a:33.33:6,b:33.33:3,c:33.34:0".parse().unwrap();
+        assert!((thirds.blended_rate() - 3.0).abs() < 0.01);
+        for invalid in [
+            "stocks:60:8,bonds:30:3",
+            "stocks:60:8,bonds:40.02:3",
+            "stocks:60",
+            ":100:5",
+            "stocks:-10:8,bonds:110:3",
+            "stocks:100:fast",
+        ] {
+            assert!(invalid.parse::<Allocation>().is_err(), "{}", invalid);
+        }
+
+        let matches = crate::args::build_cli().get_matches_from([
+            "cic",
+            "--allocation",
+            "stocks:60:8,bonds:40:3",
+        ]);
+        let (params, _) = crate::args::resolve_params(&matches, |_| None).unwrap();
+        assert_eq!(params.rate, 6.0);
+        assert!(crate::args::build_cli()
+            .try_get_matches_from(["cic", "--allocation", "stocks:100:8", "-r", "5"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_pause_years() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            ..Default::default()
+        };
+        let paused = Investment {
+            pause_years: Some((3, 4)),
+            ..investment.clone()
+        };
+        let summary = paused.yearly_summary().unwrap();
+        assert_eq!(summary[2].annual_contribution, 0.0);
+        assert_eq!(summary[3].annual_contribution, 0.0);
+        assert_eq!(summary[4].annual_contribution, 1200.0);
+        assert!(summary[3].annual_interest > summary[1].annual_interest);
+
+        // The contributions of years 3 and 4 would have earned interest for 7 and 6 years.
+        let missed = 1200.0 * 1.05_f64.powi(7) + 1200.0 * 1.05_f64.powi(6);
+        let full = investment.yearly_summary().unwrap();
+        let lost = full[9].total_amount - summary[9].total_amount;
+        assert!((lost - missed).abs() < 1e-6, "{} != {}", lost, missed);
+        assert!(lost > 2400.0);
+
+        let matches = crate::args::build_cli().get_matches_from(["cic", "--pause-years", "3:4"]);
+        assert_eq!(Investment::from_matches(&matches).pause_years, Some((3, 4)));
+        assert!(crate::args::build_cli()
+            .try_get_matches_from(["cic", "--pause-years", "4:3"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_skip_months() {
+        let skipping = Investment {
+            principal: 0.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 2,
+            skip_months: vec![6, 7, 8],
+            ..Default::default()
+        };
+        for frequency in [CompoundFrequency::Annual, CompoundFrequency::Monthly] {
+            let investment = Investment {
+                compound_frequency: frequency,
+                ..skipping.clone()
+            };
+            let summary = investment.yearly_summary().unwrap();
+            assert_eq!(summary[0].annual_contribution, 900.0);
+            assert_eq!(summary[1].total_contribution, 1800.0);
+        }
+
+        let matches = crate::args::build_cli().get_matches_from(["cic", "--skip-months", "6,7,8"]);
+        assert_eq!(
+            Investment::from_matches(&matches).skip_months,
+            vec![6, 7, 8]
+        );
+        assert!(crate::args::build_cli()
+            .try_get_matches_from(["cic", "--skip-months", "13"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_high_rate_warning() {
+        let high = Investment {
+            rate: 50.0,
+            ..Default::default()
+        };
+        assert!(high.high_rate_warning(DEFAULT_MAX_RATE).is_some());
+        assert!(high.high_rate_warning(60.0).is_none());
+
+        let realistic = Investment {
+            rate: 7.0,
+            ..Default::default()
+        };
+        assert!(realistic.high_rate_warning(DEFAULT_MAX_RATE).is_none());
+    }
+
+    #[test]
+    fn test_account_from_str() {
+        let account: Account = "savings:1000:100:2".parse().unwrap();
+        assert_eq!(
+            account,
+            Account {
+                name: "savings".to_string(),
+                principal: 1000.0,
+                contribution: 100.0,
+                rate: 2.0,
+            }
+        );
+
+        assert!("savings:1000:100".parse::<Account>().is_err());
+        assert!("savings:1000:abc:2".parse::<Account>().is_err());
+        assert!(":1000:100:2".parse::<Account>().is_err());
+    }
+
+    #[test]
+    fn test_portfolio_combined_total_is_sum_of_accounts() {
+        let savings: Account = "savings:1000:100:2".parse().unwrap();
+        let index: Account = "index:5000:200:7".parse().unwrap();
+        let portfolio = Portfolio {
+            accounts: vec![savings.clone(), index.clone()],
+        };
+
+        let summary = portfolio.yearly_summary(10).unwrap();
+        assert_eq!(summary.len(), 10);
+
+        let savings_summary = savings.to_investment(10).yearly_summary().unwrap();
+        let index_summary = index.to_investment(10).yearly_summary().unwrap();
+        for (i, year) in summary.iter().enumerate() {
+            let expected = savings_summary[i].total_amount + index_summary[i].total_amount;
+            assert!((year.combined.total_amount - expected).abs() < 1e-6);
+            assert_eq!(year.accounts.len(), 2);
+            assert_eq!(year.accounts[0].name, "savings");
+            assert_eq!(
+                year.accounts[1].summary.total_amount,
+                index_summary[i].total_amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_portfolio_rejects_invalid_years() {
+        let empty = Portfolio::default();
+        let index: Account = "index:5000:200:7".parse().unwrap();
+        let portfolio = Portfolio {
+            accounts: vec![index],
+        };
+        for portfolio in [&empty, &portfolio] {
+            for years in [-1, MAX_YEARS + 1, i32::MAX] {
+                assert!(matches!(
+                    portfolio.yearly_summary(years),
+                    Err(CicError::Validation(_))
+                ));
+                assert!(matches!(
+                    portfolio.par_yearly_summary(years),
+                    Err(CicError::Validation(_))
+                ));
+            }
+        }
+        assert_eq!(empty.yearly_summary(3).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_contributions_grow_with_salary() {
+        let investment = Investment {
+            principal: 0.0,
+            rate: 5.0,
+            years: 3,
+            salary: 60000.0,
+            savings_rate: 10.0,
+            salary_growth: 3.0,
+            ..Default::default()
+        };
+
+        let summary = investment.yearly_summary().unwrap();
+        assert!((summary[0].annual_contribution - 6000.0).abs() < 1e-6);
+        assert!((summary[1].annual_contribution - 6180.0).abs() < 1e-6);
+        assert!((summary[2].annual_contribution - 6365.4).abs() < 1e-6);
+        assert!(summary[1].annual_contribution > summary[0].annual_contribution);
+        assert!((summary[2].total_contribution - 18545.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_chart_caption_contains_final_value() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        assert_eq!(
+            chart_caption(&summary, &PlotOptions::default()),
+            "Investment Summary"
+        );
+        let options = PlotOptions {
+            path: std::env::temp_dir().join("cic_test_annotated_caption.png"),
+            annotate_caption: true,
+            cagr: cagr(&investment),
+            ..Default::default()
+        };
+        let caption = chart_caption(&summary, &options);
+        assert!(caption.contains("Final: $4,941"));
+        // Every contribution grows at 5% from the year it is added.
+        assert!(caption.contains("CAGR 5.0%"), "{}", caption);
+
+        plot_summary(&summary, &options).unwrap();
+        assert!(options.path.exists());
+        std::fs::remove_file(&options.path).unwrap();
+    }
+
+    #[test]
+    fn test_yearly_summary_rejects_absurd_years() {
+        let investment = Investment {
+            years: 2_000_000_000,
+            ..Default::default()
+        };
+        let e = investment.yearly_summary().unwrap_err();
+        assert!(matches!(e, CicError::Validation(_)));
+        assert_eq!(
+            e.to_string(),
+            format!("Invalid input: {}", YEARS_LIMIT_ERROR)
+        );
+
+        let params = InvestmentParams {
+            years: 2_000_000_000,
+            ..Default::default()
+        };
+        assert!(Investment::from_params(params).is_err());
+
+        let portfolio = Portfolio {
+            accounts: vec!["savings:1000:100:2".parse().unwrap()],
+        };
+        assert!(portfolio.yearly_summary(2_000_000_000).is_err());
+    }
+
+    #[test]
+    fn test_diff_summaries() {
+        let base = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 3,
+            ..Default::default()
+        };
+        let other = Investment {
+            principal: 2000.0,
+            ..base.clone()
+        };
+
+        let diff = diff_summaries(
+            &base.yearly_summary().unwrap(),
+            &other.yearly_summary().unwrap(),
+        );
+        assert_eq!(diff.len(), 3);
+        assert_eq!(diff[0].year, 1);
+        assert_eq!(diff[0].total_contribution, 0.0);
+        assert!((diff[0].total_interest - 50.0).abs() < 1e-9);
+        assert!((diff[2].total_amount - 1157.625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_what_if_extra_contribution_yields_uplift() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            ..Default::default()
+        };
+
+        let what_if = what_if_extra_contribution(&investment, 50.0).unwrap();
+        assert!(what_if.difference > 0.0);
+        assert!(what_if.uplift_pct.unwrap() > 0.0);
+        assert!((what_if.boosted - what_if.baseline - what_if.difference).abs() < 1e-9);
+        // The extra contribution alone is 50 * 12 * 10 = 6000, plus the interest it earns.
+        assert!(what_if.difference > 6000.0);
+    }
+
+    #[test]
+    fn test_goal_gap() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            ..Default::default()
+        };
+        let final_amount = investment.yearly_summary().unwrap()[9].total_amount;
+
+        let surplus = goal_gap(&investment, 10000.0).unwrap();
+        assert!(surplus.met);
+        assert_eq!(surplus.final_amount, final_amount);
+        assert_eq!(surplus.gap, final_amount - 10000.0);
+        assert!(surplus.gap > 0.0);
+        assert_eq!(surplus.gap_pct, Some(surplus.gap / 100.0));
+
+        let shortfall = goal_gap(&investment, 50000.0).unwrap();
+        assert!(!shortfall.met);
+        assert!(shortfall.gap < 0.0);
+        assert!((shortfall.final_amount - shortfall.gap - 50000.0).abs() < 1e-9);
+        assert!(shortfall.gap_pct.unwrap() > -100.0 && shortfall.gap_pct.unwrap() < 0.0);
+
+        let exact = goal_gap(&investment, final_amount).unwrap();
+        assert!(exact.met);
+        assert_eq!(goal_gap(&investment, 0.0).unwrap().gap_pct, None);
+    }
+
+    #[test]
+    fn test_cost_of_waiting() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 30,
+            ..Default::default()
+        };
+        let now = cost_of_waiting(&investment, 0).unwrap();
+        assert_eq!(now.cost, 0.0);
+
+        let cost = cost_of_waiting(&investment, 5).unwrap();
+        assert_eq!(cost.start_now, now.start_now);
+        let later = Investment {
+            years: 25,
+            ..investment.clone()
+        }
+        .yearly_summary()
+        .unwrap();
+        assert_eq!(cost.start_later, later[24].total_amount);
+        // Waiting 5 years skips 6000 of contributions, but costs far more in lost growth.
+        assert!(cost.cost > 6000.0 * 2.0, "{}", cost.cost);
+        assert!(cost.cost_pct.unwrap() > 20.0);
+        assert!(cost_of_waiting(&investment, 10).unwrap().cost > cost.cost);
+
+        // The delayed start keeps the calendar of the rates.
+        let glide = Investment {
+            variable_rates: vec![10.0, 10.0, 0.0],
+            years: 3,
+            contribution: 0.0,
+            ..investment.clone()
+        };
+        assert_eq!(cost_of_waiting(&glide, 2).unwrap().start_later, 1000.0);
+
+        // So do the pause, the lump sums and the inflation series.
+        let modified = Investment {
+            inflation_rates: vec![10.0, 10.0, 2.0, 3.0],
+            index_contributions_to_inflation: true,
+            pause_years: Some((2, 4)),
+            lump_sums: vec![(1, 500.0), (6, 2000.0)],
+            years: 8,
+            ..investment.clone()
+        };
+        let shifted = Investment {
+            contribution: 100.0 * modified.inflation_factor(2),
+            inflation_rates: vec![2.0, 3.0],
+            pause_years: Some((1, 2)),
+            lump_sums: vec![(4, 2000.0)],
+            years: 6,
+            ..modified.clone()
+        };
+        let later = shifted.yearly_summary().unwrap();
+        assert_eq!(
+            cost_of_waiting(&modified, 2).unwrap().start_later,
+            later[5].total_amount
+        );
+
+        let expired = cost_of_waiting(&investment, 30).unwrap();
+        assert_eq!(expired.start_later, investment.principal);
+        assert!(cost_of_waiting(&investment, 31).is_err());
+        assert!(cost_of_waiting(&investment, -1).is_err());
+    }
+
+    #[test]
+    fn test_time_and_money_weighted_returns() {
+        // +50% in year 1, then a contribution of 1200, then -50% in year 2.
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            variable_rates: vec![50.0, -50.0],
+            years: 2,
+            ..Default::default()
+        };
+        // TWR: sqrt(1.5 * 0.5) - 1, whatever the contributions.
+        let twr = investment.time_weighted_return().unwrap();
+        assert!((twr - (0.75f64.sqrt() - 1.0)).abs() < 1e-9);
+        // MWR: -1000 * g^2 - 1200 * g - 1200 + 2550 = 0; the contribution suffered the loss.
+        let growth = (-1200.0 + (1200.0f64.powi(2) + 4.0 * 1000.0 * 1350.0).sqrt()) / 2000.0;
+        let mwr = investment.money_weighted_return().unwrap();
+        assert!((mwr - (growth - 1.0)).abs() < 1e-9);
+        assert!(mwr < twr);
+
+        // Monthly contributions earn interest within the year, which is not a return of the
+        // portfolio: both returns are the APY.
+        let monthly = Investment {
+            principal: 1000.0,
+            contribution: 1000.0,
+            rate: 12.0,
+            years: 3,
+            compound_frequency: CompoundFrequency::Monthly,
+            ..Default::default()
+        };
+        let apy = apr_to_apy(12.0, 12) / 100.0;
+        let twr = monthly.time_weighted_return().unwrap();
+        assert!((twr - apy).abs() < 1e-9, "{}", twr);
+        let mwr = monthly.money_weighted_return().unwrap();
+        assert!((mwr - apy).abs() < 1e-9, "{}", mwr);
+
+        // Contributions credited with half a year of interest were invested for half of it.
+        let mid_year = Investment {
+            principal: 1000.0,
+            contribution: 1000.0,
+            rate: 12.0,
+            years: 3,
+            mid_year_contribution: true,
+            ..Default::default()
+        };
+        let twr = mid_year.time_weighted_return().unwrap();
+        assert!((twr - 0.12).abs() < 1e-9, "{}", twr);
+        // Half of a year's interest is a little more than half a year of compounding.
+        let mwr = mid_year.money_weighted_return().unwrap();
+        assert!(mwr > 0.12 && mwr < 0.122, "{}", mwr);
+
+        // A huge rate must not stall the search, although floats are sparse at its growth factor.
+        let huge_rate = Investment {
+            principal: 1000.0,
+            rate: 1e6,
+            years: 2,
+            ..Default::default()
+        };
+        let mwr = huge_rate.money_weighted_return().unwrap();
+        assert!((mwr / 1e4 - 1.0).abs() < 1e-3, "{}", mwr);
+
+        let nothing_invested = Investment {
+            principal: 0.0,
+            contribution: 0.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            nothing_invested.time_weighted_return(),
+            Err(CicError::Calculation(_))
+        ));
+        assert!(matches!(
+            nothing_invested.money_weighted_return(),
+            Err(CicError::Calculation(_))
+        ));
+    }
+
+    #[test]
+    fn test_inflation_series() {
+        let rates = parse_inflation_series("# CPI\n10\n\n-5\n20\n", 3, false).unwrap();
+        assert_eq!(rates, vec![10.0, -5.0, 20.0]);
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            rate: 0.0,
+            years: 3,
+            inflation_rates: rates,
+            ..Default::default()
+        };
+        let real: Vec<f64> = investment
+            .yearly_summary()
+            .unwrap()
+            .iter()
+            .map(|s| s.real_total_amount)
+            .collect();
+        let expected = [
+            1000.0 / 1.1,
+            1000.0 / (1.1 * 0.95),
+            1000.0 / (1.1 * 0.95 * 1.2),
+        ];
+        for (real, expected) in real.iter().zip(expected) {
+            assert!((real - expected).abs() < 1e-9);
+        }
+
+        assert!(matches!(
+            parse_inflation_series("2\n3\n", 4, false),
+            Err(CicError::Validation(_))
+        ));
+        assert_eq!(
+            parse_inflation_series("2\n3\n", 4, true).unwrap(),
+            vec![2.0, 3.0, 3.0, 3.0]
+        );
+        assert!(parse_inflation_series("2\nhigh\n", 2, false).is_err());
+        assert!(parse_inflation_series("# nothing\n", 0, true).is_err());
+    }
+
+    #[test]
+    fn test_total_return_pct() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 3,
+            ..Default::default()
+        };
+        // Invested: 1000 + 3600 = 4600, final: 4940.625
+        let expected = (4940.625 - 4600.0) / 4600.0 * 100.0;
+        assert!((investment.total_return_pct() - expected).abs() < 1e-9);
+
+        let nothing_invested = Investment {
+            principal: 0.0,
+            contribution: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(nothing_invested.total_return_pct(), 0.0);
+    }
+
+    #[test]
+    fn test_rate_is_net_ignores_fee() {
+        let gross = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 7.0,
+            years: 10,
+            ..Default::default()
+        };
+        let final_amount = |investment: &Investment| {
+            investment
+                .yearly_summary()
+                .unwrap()
+                .last()
+                .unwrap()
+                .total_amount
+        };
+        let with_fee = Investment {
+            fee: 1.0,
+            ..gross.clone()
+        };
+        let six_percent = Investment {
+            rate: 6.0,
+            ..gross.clone()
+        };
+        assert!((final_amount(&with_fee) - final_amount(&six_percent)).abs() < 1e-6);
+        assert!(gross.fee_warning().is_none());
+
+        let net = Investment {
+            rate_is_net: true,
+            ..with_fee
+        };
+        assert_eq!(final_amount(&net), final_amount(&gross));
+        assert!(net.fee_warning().is_some());
+    }
+
+    #[test]
+    fn test_glide_path_interpolates_middle_year() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            years: 5,
+            variable_rates: glide_path(8.0, 4.0, 5),
+            ..Default::default()
+        };
+        assert!((investment.rate_for_year(1) - 0.08).abs() < 1e-12);
+        assert!((investment.rate_for_year(3) - 0.06).abs() < 1e-12);
+        assert!((investment.rate_for_year(5) - 0.04).abs() < 1e-12);
+        assert!((investment.rate_for_year(6) - 0.05).abs() < 1e-12);
+
+        let summary = investment.yearly_summary().unwrap();
+        let start_of_year_3 = summary[1].total_amount;
+        assert!((summary[2].annual_interest - start_of_year_3 * 0.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_plot_summary_with_hex_colors() {
+        assert_eq!(parse_hex_color("#1f77b4").unwrap(), RGBColor(31, 119, 180));
+        assert_eq!(parse_hex_color("FF7F0E").unwrap(), RGBColor(255, 127, 14));
+        assert!(parse_hex_color("#1f77b").is_err());
+        assert!(parse_hex_color("#1f77bz").is_err());
+        assert!(parse_hex_color("").is_err());
+
+        let summary = Investment::default().yearly_summary().unwrap();
+        let options = PlotOptions {
+            path: std::env::temp_dir().join("cic_test_hex_colors.png"),
+            color_total: parse_hex_color("#1f77b4").unwrap(),
+            color_contribution: parse_hex_color("#ff7f0e").unwrap(),
+            ..Default::default()
+        };
+        plot_summary(&summary, &options).unwrap();
+        assert!(options.path.exists());
+        std::fs::remove_file(&options.path).unwrap();
+    }
+
+    #[test]
+    fn test_contributions_indexed_to_inflation() {
+        let investment = Investment {
+            principal: 0.0,
+            contribution: 100.0,
+            years: 4,
+            inflation: 3.0,
+            index_contributions_to_inflation: true,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        for i in 1..summary.len() {
+            assert!(summary[i].annual_contribution > summary[i - 1].annual_contribution);
+
+            let year = summary[i].year;
+            let real_contribution =
+                summary[i].annual_contribution / investment.inflation_factor(year - 1);
+            assert!((real_contribution - 1200.0).abs() < 1e-9);
+        }
+        assert!((summary[1].annual_contribution - 1236.0).abs() < 1e-9);
+
+        let not_indexed = Investment {
+            index_contributions_to_inflation: false,
+            ..investment.clone()
+        };
+        let summary = not_indexed.yearly_summary().unwrap();
+        assert!(summary.iter().all(|s| s.annual_contribution == 1200.0));
+        let last = summary.last().unwrap();
+        assert!((last.real_total_amount - last.total_amount / 1.03_f64.powi(4)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rate_below_inflation_yields_negative_real_growth() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            rate: 2.0,
+            years: 3,
+            inflation: 5.0,
+            ..Default::default()
+        };
+        assert_eq!(investment.breakeven_rate(), 5.0);
+
+        let summary = investment.yearly_summary().unwrap();
+        assert!(summary.iter().all(|s| !s.real_growth_flag));
+        assert!(summary[2].real_total_amount < summary[1].real_total_amount);
+
+        let above_inflation = Investment {
+            rate: 7.0,
+            ..investment
+        };
+        let summary = above_inflation.yearly_summary().unwrap();
+        assert!(summary.iter().all(|s| s.real_growth_flag));
+    }
+
+    #[test]
+    fn test_simple_interest_grows_linearly() {
+        let simple = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            rate: 10.0,
+            years: 5,
+            interest_mode: InterestMode::Simple,
+            ..Default::default()
+        };
+        let compound = Investment {
+            interest_mode: InterestMode::Compound,
+            ..simple.clone()
+        };
+
+        let simple_summary = simple.yearly_summary().unwrap();
+        let compound_summary = compound.yearly_summary().unwrap();
+        for (i, s) in simple_summary.iter().enumerate() {
+            assert!((s.annual_interest - 100.0).abs() < 1e-9);
+            assert!((s.total_amount - (1000.0 + 100.0 * (i + 1) as f64)).abs() < 1e-9);
+        }
+        assert!((compound_summary[4].total_amount - 1610.51).abs() < 1e-9);
+        assert!(compound_summary[4].total_amount > simple_summary[4].total_amount);
+
+        let with_contributions = Investment {
+            contribution: 100.0,
+            ..simple
+        };
+        let summary = with_contributions.yearly_summary().unwrap();
+        // Year 2 earns interest on the principal and the contributions of year 1 only.
+        assert!((summary[1].annual_interest - 220.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_iter_years_matches_yearly_summary() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            inflation: 2.0,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        let first_two: Vec<YearlySummary> = investment.iter_years().take(2).collect();
+        assert_eq!(first_two.len(), 2);
+        for (lazy, eager) in first_two.iter().zip(summary.iter()) {
+            assert_eq!(lazy.year, eager.year);
+            assert_eq!(lazy.total_amount, eager.total_amount);
+            assert_eq!(lazy.total_interest, eager.total_interest);
+            assert_eq!(lazy.real_total_amount, eager.real_total_amount);
+        }
+        assert_eq!(investment.iter_years().len(), 10);
+
+        let absurd = Investment {
+            years: 2_000_000_000,
+            ..investment
+        };
+        assert_eq!(absurd.iter_years().take(3).count(), 3);
+    }
+
+    #[test]
+    fn test_plot_with_x_tick_interval() {
+        let investment = Investment {
+            principal: 1000.0,
+            years: 50,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let options = PlotOptions {
+            x_tick_interval: Some(10),
+            ..Default::default()
+        };
+
+        let png = render_png(&summary, &options).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(x_tick_interval(50), 5);
+        assert_eq!(x_tick_interval(1000), 100);
+    }
+
+    #[test]
+    fn test_plot_with_dpi() {
+        let summary = Investment::default().yearly_summary().unwrap();
+        let size = |dpi| {
+            let options = PlotOptions {
+                dpi,
+                ..Default::default()
+            };
+            let png = render_png(&summary, &options).unwrap();
+            image::load_from_memory(&png)
+                .unwrap()
+                .to_rgb8()
+                .dimensions()
+        };
+
+        assert_eq!(size(SCREEN_DPI), (600, 400));
+        assert_eq!(size(300), (1875, 1250));
+    }
+
+    #[test]
+    fn test_draw_summary_side_by_side() {
+        let options = PlotOptions::default();
+        let (width, height) = options.chart_size();
+        let summary = Investment::default().yearly_summary().unwrap();
+        let other = Investment {
+            principal: 1000.0,
+            years: 20,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+
+        let mut buffer = vec![0; 2 * width as usize * height as usize * 3];
+        {
+            let root =
+                BitMapBackend::with_buffer(&mut buffer, (2 * width, height)).into_drawing_area();
+            let (left, right) = root.split_horizontally(width);
+            draw_summary(&left, &summary, &options).unwrap();
+            draw_summary(&right, &other, &options).unwrap();
+            assert!(matches!(
+                draw_summary(&right, &[], &options),
+                Err(PlotError::EmptyData)
+            ));
+            root.present().unwrap();
+        }
+
+        // Each half is the same as the chart drawn on its own.
+        let dashboard = image::RgbImage::from_raw(2 * width, height, buffer).unwrap();
+        for (x, summary) in [(0, &summary), (width, &other)] {
+            let png = render_png(summary, &options).unwrap();
+            let chart = image::load_from_memory(&png).unwrap().to_rgb8();
+            let half = image::imageops::crop_imm(&dashboard, x, 0, width, height).to_image();
+            assert!(half == chart);
+        }
+    }
+
+    #[test]
+    fn test_plot_without_legend() {
+        let summary = Investment::default().yearly_summary().unwrap();
+        let thumbnail = PlotOptions {
+            dpi: 48,
+            ..Default::default()
+        };
+        let without_legend = PlotOptions {
+            legend: false,
+            ..thumbnail.clone()
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let png = render_png(&summary, &without_legend).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (300, 200));
+        assert_ne!(png, render_png(&summary, &thumbnail).unwrap());
+    }
 
     #[test]
-    fn test_investment_initialization() {
+    fn test_plot_with_goal() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 10,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let reached = PlotOptions {
+            goal: Some(8000.0),
+            ..Default::default()
+        };
+
+        assert_eq!(goal_year(&summary, 8000.0), Some(6));
+        let png = render_png(&summary, &reached).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(caption_with_goal(&summary, &reached), "Investment Summary");
+
+        let unreached = PlotOptions {
+            goal: Some(1_000_000.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            caption_with_goal(&summary, &unreached),
+            "Investment Summary — Goal $1,000,000 not reached"
+        );
+        assert!(render_png(&summary, &unreached).is_ok());
+    }
+
+    const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden");
+
+    // The text is rasterized with the system's sans-serif font (DejaVu Sans for the committed
+    // goldens), and different versions of the rasterizer anti-alias glyph edges slightly
+    // differently. A channel may therefore be off by `CHANNEL_TOLERANCE`, and up to
+    // `MAX_MISMATCH_RATIO` of the pixels may differ by more. The ratio is kept small on purpose:
+    // swapping the colors of the two lines changes only about 0.7% of the pixels, so a looser
+    // bound would miss it. With an entirely different font the comparison fails; regenerate the
+    // goldens locally in that case, but do not commit them.
+    const CHANNEL_TOLERANCE: u8 = 16;
+    const MAX_MISMATCH_RATIO: f64 = 0.002;
+
+    /// Returns the share of the pixels of a rendered PNG that differ from `testdata/golden/<name>`
+    /// by more than `CHANNEL_TOLERANCE` in any channel.
+    fn golden_mismatch_ratio(name: &str, png: &[u8]) -> f64 {
+        let path = std::path::Path::new(GOLDEN_DIR).join(name);
+        let golden = image::open(&path)
+            .unwrap_or_else(|e| panic!("Failed to open {}: {}", path.display(), e))
+            .to_rgb8();
+        let actual = image::load_from_memory(png).unwrap().to_rgb8();
+        assert_eq!(actual.dimensions(), golden.dimensions(), "{}", name);
+
+        let mismatches = actual
+            .pixels()
+            .zip(golden.pixels())
+            .filter(|(a, g)| {
+                a.0.iter()
+                    .zip(g.0.iter())
+                    .any(|(a, g)| a.abs_diff(*g) > CHANNEL_TOLERANCE)
+            })
+            .count();
+        mismatches as f64 / (actual.width() * actual.height()) as f64
+    }
+
+    /// Compares a rendered PNG against `testdata/golden/<name>`, or overwrites the golden when the
+    /// `CIC_UPDATE_GOLDEN` environment variable is set. Regenerate the goldens with
+    /// `CIC_UPDATE_GOLDEN=1 cargo test golden` after an intended change to the chart, and check the
+    /// new images before committing them.
+    fn assert_matches_golden(name: &str, png: &[u8]) {
+        if std::env::var_os("CIC_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(GOLDEN_DIR).unwrap();
+            std::fs::write(std::path::Path::new(GOLDEN_DIR).join(name), png).unwrap();
+            return;
+        }
+        let ratio = golden_mismatch_ratio(name, png);
+        assert!(
+            ratio <= MAX_MISMATCH_RATIO,
+            "{} differs from the golden image in {:.2}% of the pixels",
+            name,
+            ratio * 100.0
+        );
+    }
+
+    #[test]
+    fn test_golden_chart() {
         let investment = Investment {
             principal: 1000.0,
             contribution: 100.0,
             rate: 5.0,
             years: 10,
+            ..Default::default()
         };
+        let summary = investment.yearly_summary().unwrap();
 
-        assert_eq!(investment.principal, 1000.0);
-        assert_eq!(investment.contribution, 100.0);
-        assert_eq!(investment.rate, 5.0);
-        assert_eq!(investment.years, 10);
+        let png = render_png(&summary, &PlotOptions::default()).unwrap();
+        assert_matches_golden("default_chart.png", &png);
+
+        let with_goal = PlotOptions {
+            goal: Some(8000.0),
+            ..Default::default()
+        };
+        let png = render_png(&summary, &with_goal).unwrap();
+        assert_matches_golden("goal_chart.png", &png);
+
+        // The tolerance must still catch a color regression, not only a layout one.
+        if std::env::var_os("CIC_UPDATE_GOLDEN").is_none() {
+            let swapped = PlotOptions {
+                color_total: RED,
+                color_contribution: BLUE,
+                ..Default::default()
+            };
+            let png = render_png(&summary, &swapped).unwrap();
+            assert!(golden_mismatch_ratio("default_chart.png", &png) > MAX_MISMATCH_RATIO);
+        }
     }
 
     #[test]
-    fn test_yearly_summary() {
+    fn test_lump_sum_wins_in_rising_market() {
+        let comparison = lump_vs_dca(12000.0, 12, 10, 7.0, CompoundFrequency::Annual).unwrap();
+        // DCA invests the same amount, but only from the end of the first year on.
+        assert_eq!(comparison.dca[0].total_contribution, 12000.0);
+        assert_eq!(comparison.dca[9].total_contribution, 12000.0);
+        let expected_lump = 12000.0 * 1.07f64.powi(10);
+        assert!((comparison.lump_sum_final() - expected_lump).abs() < 1e-6);
+        assert!((comparison.dca_final() - 12000.0 * 1.07f64.powi(9)).abs() < 1e-6);
+        assert!(comparison.difference() > 0.0);
+
+        let falling = lump_vs_dca(12000.0, 12, 10, -5.0, CompoundFrequency::Annual).unwrap();
+        assert!(falling.difference() < 0.0);
+        assert!(lump_vs_dca(12000.0, 0, 10, 7.0, CompoundFrequency::Annual).is_err());
+    }
+
+    #[test]
+    fn test_monthly_compounding() {
+        let annual = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            rate: 12.0,
+            years: 2,
+            ..Default::default()
+        };
+        let monthly = Investment {
+            compound_frequency: CompoundFrequency::Monthly,
+            ..annual.clone()
+        };
+        let summary = monthly.yearly_summary().unwrap();
+        assert!((summary[1].total_amount - 1000.0 * 1.01f64.powi(24)).abs() < 1e-9);
+        assert!(summary[1].total_amount > annual.yearly_summary().unwrap()[1].total_amount);
+
+        // Monthly contributions earn interest within the year; 100 at 0% stays 1200.
+        let contributions = Investment {
+            principal: 0.0,
+            contribution: 100.0,
+            rate: 0.0,
+            years: 1,
+            compound_frequency: CompoundFrequency::Monthly,
+            contribution_months: Some(6),
+            ..Default::default()
+        };
+        let summary = contributions.yearly_summary().unwrap();
+        assert_eq!(summary[0].annual_contribution, 600.0);
+        assert_eq!(summary[0].total_amount, 600.0);
+        assert_eq!("monthly".parse(), Ok(CompoundFrequency::Monthly));
+    }
+
+    #[test]
+    fn test_semi_annual_compounding() {
+        let at = |compound_frequency| {
+            Investment {
+                principal: 1000.0,
+                contribution: 0.0,
+                rate: 6.0,
+                years: 1,
+                compound_frequency,
+                ..Default::default()
+            }
+            .yearly_summary()
+            .unwrap()[0]
+                .total_amount
+        };
+        // Twice a year at half the rate: 1000 * 1.03^2.
+        let semi_annual = at(CompoundFrequency::SemiAnnual);
+        assert!((semi_annual - 1060.9).abs() < 1e-9, "{}", semi_annual);
+        assert!(at(CompoundFrequency::Annual) < semi_annual);
+        assert!(semi_annual < at(CompoundFrequency::Monthly));
+
+        let yields: Vec<f64> = CompoundFrequency::ALL
+            .iter()
+            .map(|frequency| apr_to_apy(6.0, frequency.periods_per_year()))
+            .collect();
+        assert!(yields.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((yields[1] - 6.09).abs() < 1e-9);
+
+        // The contributions of each half are added at its end, so the first half earns 3%.
+        let contributions = Investment {
+            contribution: 100.0,
+            rate: 6.0,
+            years: 1,
+            compound_frequency: CompoundFrequency::SemiAnnual,
+            ..Default::default()
+        };
+        let summary = contributions.yearly_summary().unwrap();
+        assert_eq!(summary[0].annual_contribution, 1200.0);
+        assert!((summary[0].annual_interest - 18.0).abs() < 1e-9);
+        assert_eq!("semi-annual".parse(), Ok(CompoundFrequency::SemiAnnual));
+        assert_eq!(
+            serde_json::to_value(CompoundFrequency::SemiAnnual).unwrap(),
+            "semi-annual"
+        );
+    }
+
+    #[test]
+    fn test_tax_free_beats_taxable() {
+        let investment = Investment {
+            principal: 10000.0,
+            contribution: 500.0,
+            rate: 7.0,
+            years: 30,
+            tax_rate: 20.0,
+            ..Default::default()
+        };
+        let taxable = investment.yearly_summary().unwrap();
+        // Year 1: 10000 * 7% = 700 of interest, of which 20% is taxed.
+        assert!((taxable[0].annual_interest - 560.0).abs() < 1e-9);
+
+        let results = compare_account_types(&investment).unwrap();
+        let amount = |account_type| {
+            results
+                .iter()
+                .find(|(t, _)| *t == account_type)
+                .map(|(_, amount)| *amount)
+                .unwrap()
+        };
+        assert!((amount(AccountType::Taxable) - taxable[29].total_amount).abs() < 1e-6);
+        assert!(amount(AccountType::TaxFree) > amount(AccountType::Taxable));
+        assert!(
+            (amount(AccountType::TaxDeferred) - amount(AccountType::TaxFree) * 0.8).abs() < 1e-6
+        );
+        assert_eq!("tax-free".parse(), Ok(AccountType::TaxFree));
+        assert!("roth".parse::<AccountType>().is_err());
+    }
+
+    #[test]
+    fn test_crossover_year() {
+        let investment = Investment {
+            contribution: 100.0,
+            rate: 10.0,
+            years: 15,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        // The balance after 8 years is just below 12,000, so year 9 is the first to earn more
+        // than the 1,200 contributed in it.
+        assert!(summary[7].annual_interest <= 1200.0);
+        assert!(summary[8].annual_interest > 1200.0);
+        assert_eq!(crossover_year(&summary), Some(9));
+        assert_eq!(crossover_year(&summary[..8]), None);
+    }
+
+    #[test]
+    fn test_debt_is_paid_off() {
+        let debt = Investment {
+            principal: -10000.0,
+            contribution: 300.0,
+            rate: 10.0,
+            years: 5,
+            debt: true,
+            ..Default::default()
+        };
+        let summary = debt.yearly_summary().unwrap();
+        // Interest accrues on the balance owed, so the first year pays off less than 3,600.
+        assert!(summary[0].annual_interest < 0.0);
+        assert!(summary[0].total_amount > -10000.0);
+        assert!(summary[0].total_amount < -10000.0 + 3600.0);
+        assert!(summary[2].total_amount < 0.0);
+        assert!(summary[3].total_amount >= 0.0);
+        assert_eq!(payoff_year(&summary), Some(4));
+        assert!(render_png(&summary, &PlotOptions::default()).is_ok());
+
+        let params = InvestmentParams {
+            principal: -10000.0,
+            ..Default::default()
+        };
+        assert!(Investment::from_params(params).is_err());
+        let params = InvestmentParams {
+            principal: -10000.0,
+            debt: true,
+            ..Default::default()
+        };
+        assert!(Investment::from_params(params).unwrap().debt);
+    }
+
+    #[test]
+    fn test_from_params_rejects_invalid_rates() {
+        // Deflation is allowed.
+        let params = InvestmentParams {
+            inflation: -2.0,
+            ..Default::default()
+        };
+        assert_eq!(Investment::from_params(params).unwrap().inflation, -2.0);
+
+        for params in [
+            InvestmentParams {
+                fee: -5.0,
+                ..Default::default()
+            },
+            InvestmentParams {
+                fee: f64::INFINITY,
+                ..Default::default()
+            },
+            InvestmentParams {
+                inflation: -100.0,
+                ..Default::default()
+            },
+            InvestmentParams {
+                rate: f64::NAN,
+                ..Default::default()
+            },
+            InvestmentParams {
+                principal: f64::NEG_INFINITY,
+                debt: true,
+                ..Default::default()
+            },
+            InvestmentParams {
+                contribution: f64::INFINITY,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                Investment::from_params(params),
+                Err(CicError::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_milestone_years() {
+        let summary: Vec<YearlySummary> = [50000.0, 120000.0, 240000.0, 260000.0, 600000.0]
+            .into_iter()
+            .zip(1..)
+            .map(|(total_amount, year)| YearlySummary {
+                year,
+                total_amount,
+                ..Default::default()
+            })
+            .collect();
+
+        assert_eq!(
+            milestone_years(&summary, &[100000.0, 250000.0, 500000.0, 1000000.0]),
+            vec![
+                (100000.0, Some(2)),
+                (250000.0, Some(4)),
+                (500000.0, Some(5)),
+                (1000000.0, None)
+            ]
+        );
+        assert_eq!(
+            milestone_years(&summary, &[50000.0]),
+            vec![(50000.0, Some(1))]
+        );
+        assert!(milestone_years(&summary, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_required_contribution_and_savings_rate() {
+        let investment = Investment {
+            principal: 1000.0,
+            rate: 5.0,
+            years: 3,
+            salary: 50000.0,
+            ..Default::default()
+        };
+
+        let contribution = required_contribution(&investment, 4940.625).unwrap();
+        assert!((contribution - 100.0).abs() < 0.01);
+        assert_eq!(
+            required_savings_rate(contribution, investment.salary),
+            Some(contribution * 12.0 / investment.salary * 100.0)
+        );
+
+        assert_eq!(required_contribution(&investment, 500.0).unwrap(), 0.0);
+        let no_time = Investment {
+            years: 0,
+            ..investment
+        };
+        assert!(required_contribution(&no_time, 5000.0).is_err());
+    }
+
+    #[test]
+    fn test_required_contribution_with_huge_target() {
+        let investment = Investment {
+            years: 1,
+            ..Default::default()
+        };
+        for target in [1e15, 1e100, 1e300] {
+            let contribution = required_contribution(&investment, target).unwrap();
+            assert!(contribution * 12.0 >= target * 0.999, "{}", contribution);
+        }
+        assert!(offset_expense(&investment, 1, 1e300).is_ok());
+    }
+
+    #[test]
+    fn test_required_contribution_accounts_for_fees_and_tax() {
+        let investment = Investment {
+            principal: 1000.0,
+            rate: 7.0,
+            years: 20,
+            ..Default::default()
+        };
+        let target = 100000.0;
+        let no_fee = required_contribution(&investment, target).unwrap();
+
+        let with_fee = Investment {
+            fee: 1.0,
+            ..investment.clone()
+        };
+        let contribution = required_contribution(&with_fee, target).unwrap();
+        assert!(contribution > no_fee, "{} <= {}", contribution, no_fee);
+        let solved = Investment {
+            contribution,
+            ..with_fee
+        };
+        let summary = solved.yearly_summary().unwrap();
+        assert!(summary.last().unwrap().total_amount >= target);
+
+        let tax_deferred = Investment {
+            account_type: AccountType::TaxDeferred,
+            tax_rate: 25.0,
+            ..investment
+        };
+        let contribution = required_contribution(&tax_deferred, target).unwrap();
+        assert!(contribution > no_fee);
+        let solved = Investment {
+            contribution,
+            ..tax_deferred
+        };
+        let summary = solved.yearly_summary().unwrap();
+        assert!(solved.after_tax_amount(summary.last().unwrap().total_amount) >= target);
+    }
+
+    #[test]
+    fn test_years_and_months_to_target() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            ..Default::default()
+        };
+        // 2250 after the first year and 3562.5 after the second, so 2500 takes 1.19 years.
+        let years = fractional_years_to_target(&investment, 2500.0).unwrap();
+        assert!((years - 1.190476).abs() < 1e-6, "{}", years);
+        assert_eq!(
+            years_and_months_to_target(&investment, 2500.0).unwrap(),
+            "1 year 3 months"
+        );
+        // Reached exactly at the end of a year.
+        assert_eq!(
+            years_and_months_to_target(&investment, 2250.0).unwrap(),
+            "1 year"
+        );
+        assert_eq!(
+            years_and_months_to_target(&investment, 1100.0).unwrap(),
+            "1 month"
+        );
+        assert_eq!(
+            years_and_months_to_target(&investment, 5000.0).unwrap(),
+            "3 years 1 month"
+        );
+        assert_eq!(
+            years_and_months_to_target(&investment, 500.0).unwrap(),
+            "0 months"
+        );
+
+        let nothing = Investment {
+            contribution: 0.0,
+            rate: 0.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            years_and_months_to_target(&nothing, 2500.0),
+            Err(CicError::Calculation(_))
+        ));
+    }
+
+    #[test]
+    fn test_rate_conversion() {
+        // 12% APR compounded monthly is 1% a month and 12.6825% a year.
+        assert!((apr_to_apy(12.0, 12) - 12.682503).abs() < 1e-6);
+        assert!((periodic_rate(12.0, 12) - 1.0).abs() < 1e-12);
+        assert!((apy_to_apr(12.682503013196977, 12) - 12.0).abs() < 1e-9);
+        // Daily compounding approaches the continuous limit e^r - 1.
+        assert!((apr_to_apy(5.0, 365) - 5.12675).abs() < 1e-4);
+        // Compounding once a year changes nothing.
+        assert!((apr_to_apy(5.0, 1) - 5.0).abs() < 1e-12);
+        assert!((apy_to_apr(5.0, 1) - 5.0).abs() < 1e-12);
+        assert!((apy_to_apr(apr_to_apy(7.5, 4), 4) - 7.5).abs() < 1e-9);
+
+        // The monthly compounding of the calculation earns the APY.
         let investment = Investment {
             principal: 1000.0,
+            contribution: 0.0,
+            rate: 12.0,
+            years: 1,
+            compound_frequency: CompoundFrequency::Monthly,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        assert!((summary[0].annual_interest - 10.0 * apr_to_apy(12.0, 12)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fire() {
+        let number = fire_number(40000.0, 4.0).unwrap();
+        assert_eq!(number, 1000000.0);
+        assert!(fire_number(40000.0, 0.0).is_err());
+        assert!(fire_number(-1.0, 4.0).is_err());
+
+        let investment = Investment {
+            principal: 100000.0,
+            contribution: 2000.0,
+            rate: 7.0,
+            ..Default::default()
+        };
+        let year = years_to_target(&investment, number).unwrap();
+        let summary = Investment {
+            years: year,
+            ..investment.clone()
+        }
+        .yearly_summary()
+        .unwrap();
+        assert!(summary[year as usize - 1].total_amount >= number);
+        assert!(summary[year as usize - 2].total_amount < number);
+        assert!((15..=25).contains(&year), "{}", year);
+
+        let nothing = Investment {
+            contribution: 0.0,
+            rate: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(years_to_target(&nothing, number), None);
+    }
+
+    #[test]
+    fn test_save_for_down_payment() {
+        // 20% of $400k is $80k; 5 annual deposits of 12 * monthly at 4% grow by a factor of ~5.416.
+        let monthly =
+            save_for_down_payment(400000.0, 20.0, 5, 0.0, 4.0, CompoundFrequency::Annual).unwrap();
+        assert!((monthly - 1230.85).abs() < 0.01);
+
+        let with_savings =
+            save_for_down_payment(400000.0, 20.0, 5, 20000.0, 4.0, CompoundFrequency::Annual)
+                .unwrap();
+        assert!(with_savings < monthly);
+        assert_eq!(
+            save_for_down_payment(400000.0, 20.0, 5, 80000.0, 4.0, CompoundFrequency::Annual)
+                .unwrap(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_plot_errors() {
+        let empty = Investment {
+            years: 0,
+            ..Default::default()
+        };
+        let summary = empty.yearly_summary().unwrap();
+        let options = PlotOptions {
+            path: std::env::temp_dir().join("cic_test_empty_plot.png"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            plot_summary(&summary, &options),
+            Err(CicError::Plot(PlotError::EmptyData))
+        ));
+        assert!(!options.path.exists());
+        assert!(matches!(
+            render_png(&summary, &PlotOptions::default()),
+            Err(CicError::Plot(PlotError::EmptyData))
+        ));
+
+        let summary = Investment::default().yearly_summary().unwrap();
+        let options = PlotOptions {
+            path: std::env::temp_dir().join("cic_missing_dir/plot.png"),
+            ..Default::default()
+        };
+        let e = plot_summary(&summary, &options).unwrap_err();
+        assert!(
+            matches!(e, CicError::Plot(PlotError::IoError(_))),
+            "{:?}",
+            e
+        );
+    }
+
+    #[test]
+    fn test_monte_carlo_and_percentile() {
+        let investment = Investment {
+            years: 10,
+            ..Default::default()
+        };
+        let finals = monte_carlo(&investment, 15.0, 500, 7).unwrap();
+        assert_eq!(finals, monte_carlo(&investment, 15.0, 500, 7).unwrap());
+        assert!(percentile(&finals, 10.0) < percentile(&finals, 90.0));
+
+        let steady = monte_carlo(&investment, 0.0, 3, 7).unwrap();
+        let expected = investment
+            .yearly_summary()
+            .unwrap()
+            .last()
+            .unwrap()
+            .total_amount;
+        assert!(steady.iter().all(|f| (f - expected).abs() < 1e-9));
+
+        assert!(monte_carlo(&investment, -1.0, 10, 7).is_err());
+        assert!(monte_carlo(&investment, 1.0, 0, 7).is_err());
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_annualize_return() {
+        let rate = annualize_return(100.0, 10.0);
+        assert!((rate - 7.18).abs() < 0.005, "{}", rate);
+        // Compounding the annualized rate gives the total return back.
+        let total = ((1.0 + rate / 100.0).powf(10.0) - 1.0) * 100.0;
+        assert!((total - 100.0).abs() < 1e-9);
+
+        assert_eq!(annualize_return(0.0, 5.0), 0.0);
+        assert!(annualize_return(-50.0, 2.5) < 0.0);
+        assert!(annualize_return(100.0, 0.0).is_nan());
+        assert!(annualize_return(-150.0, 3.0).is_nan());
+    }
+
+    #[test]
+    fn test_breakeven_fee() {
+        // The fee is subtracted from the rate, so a fund at 8% with a fee of 3% earns exactly
+        // the 5% of the benchmark, whatever the contributions, compounding and taxes.
+        let investment = Investment {
+            principal: 10000.0,
+            contribution: 500.0,
+            years: 20,
+            compound_frequency: CompoundFrequency::Monthly,
+            tax_rate: 25.0,
+            ..Default::default()
+        };
+        let fee = breakeven_fee(&investment, 8.0, 5.0).unwrap();
+        assert!((fee - 3.0).abs() < 1e-6, "{}", fee);
+
+        let at_fee = |fee| {
+            Investment {
+                rate: 8.0,
+                fee,
+                ..investment.clone()
+            }
+            .yearly_summary()
+            .unwrap()[19]
+                .total_amount
+        };
+        let benchmark = Investment {
+            rate: 5.0,
+            ..investment.clone()
+        }
+        .yearly_summary()
+        .unwrap()[19]
+            .total_amount;
+        assert!(at_fee(fee - 0.01) > benchmark);
+        assert!(at_fee(fee + 0.01) < benchmark);
+
+        assert!(matches!(
+            breakeven_fee(&investment, 5.0, 8.0),
+            Err(CicError::Validation(_))
+        ));
+        let no_years = Investment {
+            years: 0,
+            ..investment.clone()
+        };
+        assert!(matches!(
+            breakeven_fee(&no_years, 8.0, 5.0),
+            Err(CicError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_required_contribution_for_percentile() {
+        let investment = Investment {
+            principal: 1000.0,
+            rate: 6.0,
+            years: 20,
+            ..Default::default()
+        };
+        let p10 = required_contribution_for_percentile(&investment, 100000.0, 10.0, 15.0, 500, 42)
+            .unwrap();
+        let p50 = required_contribution_for_percentile(&investment, 100000.0, 50.0, 15.0, 500, 42)
+            .unwrap();
+        assert!(p10 > p50, "p10 {} should exceed p50 {}", p10, p50);
+    }
+
+    #[test]
+    fn test_round_up_solved_contribution() {
+        let investment = Investment {
+            rate: 0.0,
+            years: 1,
+            ..Default::default()
+        };
+        let target = 473.0 * 12.0;
+        let solved = required_contribution(&investment, target).unwrap();
+        assert!((solved - 473.0).abs() < 0.01);
+
+        let rounded = round_up_to_step(solved, 10.0);
+        assert_eq!(rounded, 480.0);
+        let reached = Investment {
+            contribution: rounded,
+            ..investment
+        };
+        let final_amount = reached
+            .yearly_summary()
+            .unwrap()
+            .last()
+            .unwrap()
+            .total_amount;
+        assert!(final_amount >= target);
+    }
+
+    #[test]
+    fn test_employer_match() {
+        let investment = Investment {
             contribution: 100.0,
             rate: 5.0,
             years: 3,
+            ..Default::default()
+        };
+        let matched = Investment {
+            match_rate: 50.0,
+            match_cap: 1000.0,
+            ..investment.clone()
+        };
+        let capped = Investment {
+            match_cap: 300.0,
+            ..matched.clone()
         };
 
-        let summary = investment.yearly_summary();
-        assert_eq!(summary.len(), 3);
+        let base = investment.yearly_summary().unwrap();
+        let with_match = matched.yearly_summary().unwrap();
+        let with_cap = capped.yearly_summary().unwrap();
+        assert_eq!(with_match[0].annual_match, 600.0);
+        assert_eq!(with_match[2].total_match, 1800.0);
+        assert_eq!(with_match[2].total_contribution, base[2].total_contribution);
+        assert_eq!(with_cap[0].annual_match, 300.0);
 
-        // Year 1
-        assert_eq!(summary[0].year, 1);
-        assert_eq!(summary[0].principal, 1000.0);
-        assert_eq!(summary[0].annual_contribution, 1200.0);
-        assert!((summary[0].annual_interest - 50.0).abs() < 1e-2);
-        assert_eq!(summary[0].total_contribution, 1200.0);
-        assert!((summary[0].total_interest - 50.0).abs() < 1e-2);
-        assert!((summary[0].total_amount - 2250.0).abs() < 1e-2);
+        // Each year's match compounds for the remaining years: 1.05^2 + 1.05 + 1 = 3.1525.
+        let growth = 3.1525;
+        let difference = with_match[2].total_amount - base[2].total_amount;
+        assert!((difference - 600.0 * growth).abs() < 1e-6);
+        let difference = with_cap[2].total_amount - base[2].total_amount;
+        assert!((difference - 300.0 * growth).abs() < 1e-6);
+    }
 
-        // Year 2
-        assert_eq!(summary[1].year, 2);
-        assert_eq!(summary[1].principal, 1000.0);
-        assert_eq!(summary[1].annual_contribution, 1200.0);
-        assert!((summary[1].annual_interest - 112.5).abs() < 1e-2);
-        assert_eq!(summary[1].total_contribution, 2400.0);
-        assert!((summary[1].total_interest - 162.5).abs() < 1e-2);
-        assert!((summary[1].total_amount - 3562.5).abs() < 1e-2);
+    #[test]
+    fn test_plot_composition_pct() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 10.0,
+            years: 40,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let (contribution_pct, interest_pct) = summary.last().unwrap().composition_pct();
+        assert!(interest_pct > contribution_pct);
+        assert!((contribution_pct + interest_pct - 100.0).abs() < 1e-9);
 
-        // Year 3
-        assert_eq!(summary[2].year, 3);
-        assert_eq!(summary[2].principal, 1000.0);
-        assert_eq!(summary[2].annual_contribution, 1200.0);
-        assert!((summary[2].annual_interest - 178.125).abs() < 1e-2);
-        assert_eq!(summary[2].total_contribution, 3600.0);
-        assert!((summary[2].total_interest - 340.625).abs() < 1e-2);
-        assert!((summary[2].total_amount - 4940.625).abs() < 1e-2);
+        let path = std::env::temp_dir().join("cic_test_composition.png");
+        plot_composition_pct(&summary, &path).unwrap();
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_plot_fan() {
+        let summary = |rate| {
+            Investment {
+                principal: 1000.0,
+                contribution: 100.0,
+                rate,
+                years: 20,
+                ..Default::default()
+            }
+            .yearly_summary()
+            .unwrap()
+        };
+        let (low, expected, high) = (summary(4.0), summary(6.0), summary(8.0));
+        assert!(high.last().unwrap().total_amount > expected.last().unwrap().total_amount);
+        assert!(expected.last().unwrap().total_amount > low.last().unwrap().total_amount);
+
+        let path = std::env::temp_dir().join("cic_test_fan.png");
+        plot_fan([&low, &expected, &high], &path).unwrap();
+        let image = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).unwrap();
+        for color in FAN_COLORS {
+            let RGBColor(r, g, b) = color;
+            assert!(
+                image.pixels().any(|pixel| pixel.0 == [r, g, b]),
+                "{:?} is not drawn",
+                color
+            );
+        }
+
+        let path = std::env::temp_dir().join("cic_test_fan_empty.png");
+        assert!(plot_fan([&low, &[], &high], &path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_target_in_todays_dollars() {
+        let investment = Investment {
+            principal: 10000.0,
+            rate: 6.0,
+            years: 30,
+            ..Default::default()
+        };
+        let inflated = Investment {
+            inflation: 3.0,
+            ..investment.clone()
+        };
+        assert_eq!(investment.nominal_target(1_000_000.0), 1_000_000.0);
+
+        let nominal = inflated.nominal_target(1_000_000.0);
+        assert!(nominal > 2_400_000.0);
+        let nominal_contribution = required_contribution(&inflated, 1_000_000.0).unwrap();
+        let adjusted_contribution = required_contribution(&inflated, nominal).unwrap();
+        assert!(adjusted_contribution > nominal_contribution);
+    }
+
+    #[test]
+    fn test_verify_summary() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 5,
+            ..Default::default()
+        };
+        let mut summary = investment.yearly_summary().unwrap();
+        assert!(verify_summary(&investment, &summary, 0.01)
+            .unwrap()
+            .is_empty());
+
+        summary[2].total_amount += 1.0;
+        assert_eq!(
+            verify_summary(&investment, &summary, 0.01).unwrap(),
+            vec![3]
+        );
+        summary.pop();
+        assert_eq!(
+            verify_summary(&investment, &summary, 0.01).unwrap(),
+            vec![3, 5]
+        );
+    }
+
+    #[test]
+    fn test_parallel_summaries_match_sequential() {
+        let investments: Vec<Investment> = (0..50)
+            .map(|i| Investment {
+                principal: 1000.0 * i as f64,
+                contribution: 10.0 * i as f64,
+                rate: 1.0 + (i % 7) as f64,
+                years: 10 + i % 20,
+                ..Default::default()
+            })
+            .collect();
+
+        let sequential = yearly_summaries(&investments, false).unwrap();
+        let parallel = yearly_summaries(&investments, true).unwrap();
+        assert_eq!(sequential.len(), parallel.len());
+        for (a, b) in sequential.iter().zip(parallel.iter()) {
+            assert_eq!(
+                serde_json::to_string(a).unwrap(),
+                serde_json::to_string(b).unwrap()
+            );
+        }
+
+        let portfolio = Portfolio {
+            accounts: vec![
+                "savings:1000:100:2".parse().unwrap(),
+                "index:1000:100:7".parse().unwrap(),
+            ],
+        };
+        assert_eq!(
+            serde_json::to_string(&portfolio.yearly_summary(10).unwrap()).unwrap(),
+            serde_json::to_string(&portfolio.par_yearly_summary(10).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_no_server_gated_dependencies() {
+        // Library users embedding the calculations in a sync program must not need actix or
+        // tokio, so no import, attribute or function of this module may depend on the web layer.
+        let web_layer = ["crate::server", "actix", "tokio"];
+        for line in include_str!("calculations.rs").lines().map(str::trim) {
+            if line.starts_with("use ") {
+                assert!(
+                    !web_layer.iter().any(|path| line.contains(path)),
+                    "{}",
+                    line
+                );
+            }
+            if line.starts_with("#[cfg") {
+                assert!(!line.contains("feature"), "{}", line);
+            }
+            assert!(!line.starts_with("async fn") && !line.starts_with("pub async fn"));
+        }
     }
 }
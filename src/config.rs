@@ -0,0 +1,127 @@
+use crate::params::InvestmentParams;
+
+/// The fields written to the example config, in order, with the comment describing each one.
+const FIELD_DESCRIPTIONS: [(&str, &str); 10] = [
+    (
+        "principal",
+        "The principal at the time you started investing.",
+    ),
+    ("contribution", "The monthly contribution amount."),
+    ("rate", "The annual interest rate (in %)."),
+    ("years", "The number of years for contributions."),
+    (
+        "compound_frequency",
+        "How often interest is compounded: annual, semi-annual or monthly.",
+    ),
+    (
+        "debt",
+        "Whether a negative principal is a debt paid down by the contributions.",
+    ),
+    (
+        "inflation",
+        "The annual inflation rate (in %) for the inflation-adjusted amounts.",
+    ),
+    ("fee", "The annual fee (in %), e.g. an expense ratio."),
+    (
+        "tax_rate",
+        "The tax rate (in %) applied as described by account_type.",
+    ),
+    (
+        "account_type",
+        "How the investment is taxed: taxable (interest taxed yearly), tax-deferred (withdrawal taxed) or tax-free.",
+    ),
+];
+
+/// Generates a commented example config file in TOML format.
+///
+/// The values are taken from the default `InvestmentParams`, so the template always reflects the
+/// defaults used by the CLI and the server.
+///
+/// # Returns
+///
+/// Returns the config file contents as a `String`, or a `toml::ser::Error` if the default
+/// parameters cannot be serialized.
+///
+/// # Example
+///
+/// ```
+/// use cic::config::example_config;
+///
+/// let config = example_config().expect("Failed to generate config");
+/// assert!(config.contains("principal = 0.0"));
+/// ```
+pub fn example_config() -> Result<String, toml::ser::Error> {
+    let defaults = toml::Table::try_from(InvestmentParams::default())?;
+
+    let mut config = String::from("# cic - compound interest calculator config\n");
+    for (key, description) in FIELD_DESCRIPTIONS {
+        if let Some(value) = defaults.get(key) {
+            config.push_str(&format!("\n# {}\n{} = {}\n", description, key, value));
+        }
+    }
+    Ok(config)
+}
+
+/// Loads `InvestmentParams` from a TOML config file.
+///
+/// Fields that are missing from the file keep their default values, so a config generated by
+/// `example_config` can be trimmed down to the fields that matter.
+///
+/// # Arguments
+///
+/// * `path` - The path of the config file.
+///
+/// # Returns
+///
+/// Returns the loaded `InvestmentParams`, or an error message if the file cannot be read or parsed.
+pub fn load_params<P: AsRef<std::path::Path>>(path: P) -> Result<InvestmentParams, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&contents).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_config_parses_back() {
+        let config = example_config().unwrap();
+        let params: InvestmentParams = toml::from_str(&config).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(params).unwrap(),
+            serde_json::to_value(InvestmentParams::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_example_config_documents_every_field() {
+        let config = example_config().unwrap();
+        // JSON keeps every field, unlike TOML, which would drop one without a value.
+        let defaults = match serde_json::to_value(InvestmentParams::default()).unwrap() {
+            serde_json::Value::Object(map) => map,
+            value => panic!("not an object: {}", value),
+        };
+
+        for key in defaults.keys() {
+            assert!(config.contains(&format!("\n{} = ", key)), "missing {}", key);
+        }
+        assert_eq!(FIELD_DESCRIPTIONS.len(), defaults.len());
+    }
+
+    #[test]
+    fn test_load_params() {
+        let path = std::env::temp_dir().join("cic_test_load_params.toml");
+        std::fs::write(&path, "rate = 7.0\nyears = 10\n").unwrap();
+
+        let params = load_params(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(params.rate, 7.0);
+        assert_eq!(params.years, 10);
+        assert_eq!(params.principal, InvestmentParams::default().principal);
+
+        assert!(load_params(std::env::temp_dir().join("cic_test_missing.toml")).is_err());
+    }
+}
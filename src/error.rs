@@ -0,0 +1,87 @@
+use crate::calculations::PlotError;
+
+/// The error type of the crate.
+///
+/// Every fallible calculation, plot, and server handler reports one of these variants, so library
+/// users can match on the kind of failure. In the server, the variant decides the HTTP status.
+#[derive(Debug, thiserror::Error)]
+pub enum CicError {
+    /// The input is invalid, e.g. a negative amount or too many years.
+    #[error("Invalid input: {0}")]
+    Validation(String),
+    /// The input is valid, but the calculation has no result, e.g. an unreachable target.
+    #[error("Calculation failed: {0}")]
+    Calculation(String),
+    /// The chart could not be drawn or written.
+    #[error("Failed to plot: {0}")]
+    Plot(#[from] PlotError),
+    /// A file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The summary could not be rendered in the requested output format.
+    #[error("Failed to write output: {0}")]
+    Output(String),
+    /// The server failed to produce a response.
+    #[error("Server error: {0}")]
+    Server(String),
+    /// The client sent more requests than the rate limit allows. Holds the seconds to wait.
+    #[error("Too many requests: retry in {0} seconds")]
+    RateLimited(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let cases = [
+            (
+                CicError::Validation("Negative values are not allowed".into()),
+                "Invalid input: Negative values are not allowed",
+            ),
+            (
+                CicError::Calculation("The target cannot be reached".into()),
+                "Calculation failed: The target cannot be reached",
+            ),
+            (
+                CicError::Plot(PlotError::EmptyData),
+                "Failed to plot: There is no data to plot",
+            ),
+            (
+                CicError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "missing")),
+                "I/O error: missing",
+            ),
+            (
+                CicError::Output("invalid worksheet name".into()),
+                "Failed to write output: invalid worksheet name",
+            ),
+            (
+                CicError::Server("serialization failed".into()),
+                "Server error: serialization failed",
+            ),
+            (
+                CicError::RateLimited(2),
+                "Too many requests: retry in 2 seconds",
+            ),
+        ];
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_plot_error_keeps_its_source() {
+        use std::error::Error;
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let error = CicError::from(PlotError::from(io));
+        let plot = error.source().unwrap();
+        assert_eq!(plot.to_string(), "I/O error: missing");
+        assert_eq!(plot.source().unwrap().to_string(), "missing");
+
+        let backend = PlotError::BackendError("out of memory".into());
+        assert_eq!(backend.source().unwrap().to_string(), "out of memory");
+        assert!(PlotError::EmptyData.source().is_none());
+    }
+}
@@ -1,3 +1,8 @@
 pub mod args;
 pub mod calculations;
+pub mod config;
+pub mod error;
+pub mod output;
+pub mod params;
+#[cfg(feature = "server")]
 pub mod server;
@@ -1,45 +1,2353 @@
-mod args;
-mod calculations;
-mod server;
-
-use calculations::{plot_summary, Investment};
+use cic::calculations::{
+    annualize_return, apr_to_apy, apy_to_apr, breakeven_fee, cagr, compare_account_types,
+    cost_of_waiting, fire_number, format_currency, goal_gap, humanize, load_inflation_series,
+    lump_vs_dca, milestone_years, monte_carlo, offset_expense, payoff_year, percentile,
+    periodic_rate, plot_composition_pct, plot_fan, plot_summary, render_png, render_svg,
+    required_contribution, required_principal, required_savings_rate, round_up_to_step,
+    save_for_down_payment, verify_summary, what_if_extra_contribution, years_and_months_to_target,
+    years_to_target, Account, Allocation, CompoundFrequency, GoalGap, Investment, PlotOptions,
+    Portfolio, YearlySummary, DEFAULT_MAX_RATE, MAX_YEARS,
+};
+use cic::output::{self, CalculationOptions, Emit, JsonOptions, Metrics, OutputFormat};
+#[cfg(feature = "server")]
+use cic::server;
+use cic::{args, config};
+use clap::ArgMatches;
 use serde_json::to_string_pretty;
-use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::process::ExitCode;
 
-async fn run() -> std::io::Result<()> {
-    let matches = args::build_cli().get_matches();
+/// The exit code of the `server` subcommand when its port is already taken, so a script can tell
+/// it apart from other failures and retry on another port.
+#[cfg(feature = "server")]
+const EXIT_PORT_IN_USE: u8 = 3;
 
-    let args: Vec<String> = env::args().collect();
+/// Runs the CLI with the given arguments.
+///
+/// Only the actual result (JSON, data URIs, config files, ...) is written to `out`, so it can be
+/// piped safely. Errors, warnings and informational messages are written to `err`.
+///
+/// Without any arguments, the usage is written to `err` and exit code 2 (misuse) is returned. If
+/// an error is written to `err`, exit code 1 is returned, or `EXIT_PORT_IN_USE` if the port of
+/// the server is taken.
+fn run<I, T, W, E>(args: I, out: &mut W, err: &mut E) -> std::io::Result<ExitCode>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+    W: Write,
+    E: Write,
+{
+    let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
     if args.len() == 1 {
-        println!("`cls --help` for usage");
-        return Ok(());
+        writeln!(err, "{}", args::build_cli().render_usage())?;
+        writeln!(err, "\nFor more information, try '--help'.")?;
+        return Ok(ExitCode::from(2));
+    }
+    let matches = args::build_cli().get_matches_from(args);
+
+    match matches.subcommand() {
+        Some(("verify", matches)) => run_verify(matches, out, err),
+        Some(("server", matches)) => run_server(matches, err),
+        Some(("portfolio", matches)) => run_portfolio(matches, out, err),
+        Some(("what-if", matches)) => run_what_if(matches, out, err),
+        Some(("cost-of-waiting", matches)) => run_cost_of_waiting(matches, out, err),
+        Some(("offset-expense", matches)) => run_offset_expense(matches, out, err),
+        Some(("goal", matches)) => run_goal(matches, out, err),
+        Some(("fire", matches)) => run_fire(matches, out, err),
+        Some(("time-to-target", matches)) => run_time_to_target(matches, out, err),
+        Some(("breakeven-fee", matches)) => run_breakeven_fee(matches, out, err),
+        Some(("annualize", matches)) => run_annualize(matches, out, err),
+        Some(("convert-rate", matches)) => run_convert_rate(matches, out),
+        Some(("save-for", matches)) => run_save_for(matches, out, err),
+        Some(("solve-principal", matches)) => run_solve_principal(matches, out, err),
+        Some(("lump-vs-dca", matches)) => run_lump_vs_dca(matches, out, err),
+        Some(("value", matches)) => run_value(matches, out, err),
+        Some(("metrics", matches)) => run_metrics(matches, out, err),
+        Some(("simulate", matches)) => run_simulate(matches, out, err),
+        Some(("compare-accounts", matches)) => run_compare_accounts(matches, out, err),
+        Some(("datauri", matches)) => run_datauri(matches, out, err),
+        Some(("init-config", _)) => run_init_config(out, err),
+        _ => run_calculation(&matches, out, err),
+    }
+}
+
+/// Builds the investment from the command line, reading the scenario from `--query` if given.
+///
+/// Unknown query keys are reported as warnings. If the query is invalid, the error is written to
+/// `err` and `None` is returned.
+fn investment_from_matches<E: Write>(
+    matches: &ArgMatches,
+    err: &mut E,
+) -> std::io::Result<Option<Investment>> {
+    let mut investment = Investment::from_matches(matches);
+    let Some(base) = base_investment_from_matches(matches, err)? else {
+        return Ok(None);
+    };
+    investment.principal = base.principal;
+    investment.contribution = base.contribution;
+    investment.rate = base.rate;
+    investment.years = base.years;
+    investment.compound_frequency = base.compound_frequency;
+    investment.debt = base.debt;
+    investment.inflation = base.inflation;
+    investment.fee = base.fee;
+    investment.tax_rate = base.tax_rate;
+    investment.account_type = base.account_type;
+
+    if let Some(allocation) = matches
+        .try_get_one::<Allocation>("allocation")
+        .ok()
+        .flatten()
+    {
+        writeln!(
+            err,
+            "Note: using the blended rate of {}% from {}",
+            allocation.blended_rate(),
+            allocation
+        )?;
+    }
+    if let Some(warning) = investment.fee_warning() {
+        writeln!(err, "{}", warning)?;
+    }
+    if !args::get_flag(matches, "no-warn") {
+        let max_rate = args::get_number(matches, "max-rate").unwrap_or(DEFAULT_MAX_RATE);
+        if let Some(warning) = investment.high_rate_warning(max_rate) {
+            writeln!(err, "{}", warning)?;
+        }
+    }
+
+    // The series is loaded last, so that it is checked against the final number of years.
+    if let Some(path) = matches.get_one::<String>("inflation-file") {
+        let carry_forward = args::get_flag(matches, "carry-forward-inflation");
+        match load_inflation_series(path.as_ref(), investment.years, carry_forward) {
+            Ok(rates) => investment.inflation_rates = rates,
+            Err(e) => {
+                writeln!(err, "Failed to load inflation file: {}", e)?;
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(investment))
+}
+
+/// Builds the investment from the parameters every source can set (see `args::resolve_params`),
+/// without the options of the main command.
+///
+/// Unknown query keys and a rate that looks like a decimal fraction are reported as warnings. If
+/// a source or the parameters are invalid, the error is written to `err` and `None` is returned.
+fn base_investment_from_matches<E: Write>(
+    matches: &ArgMatches,
+    err: &mut E,
+) -> std::io::Result<Option<Investment>> {
+    let params = match args::resolve_params(matches, |key| std::env::var(key).ok()) {
+        Ok((params, unknown)) => {
+            for key in unknown {
+                writeln!(err, "Warning: ignoring unknown query key \"{}\"", key)?;
+            }
+            params
+        }
+        Err(e) => {
+            writeln!(err, "{}", e)?;
+            return Ok(None);
+        }
+    };
+    if params.principal < 0.0 && !params.debt {
+        writeln!(
+            err,
+            "A negative principal is only allowed with --debt, to model paying down a debt"
+        )?;
+        return Ok(None);
+    }
+    let investment = match Investment::from_params(params) {
+        Ok(investment) => investment,
+        Err(e) => {
+            writeln!(err, "Invalid parameters: {}", e)?;
+            return Ok(None);
+        }
+    };
+    let rate_format = args::get_value(matches, "rate-format").unwrap_or_default();
+    if let Some(warning) = investment.rate_warning(rate_format) {
+        writeln!(err, "{}", warning)?;
+    }
+    Ok(Some(investment))
+}
+
+#[cfg(feature = "server")]
+fn run_server<E: Write>(matches: &ArgMatches, err: &mut E) -> std::io::Result<ExitCode> {
+    let port = args::get_port(matches);
+    let quiet = matches.get_flag("quiet");
+    let defaults = match args::resolve_params(matches, |key| std::env::var(key).ok()) {
+        Ok((params, _)) => params,
+        Err(e) => {
+            writeln!(err, "{}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    // Only the server needs an async runtime, so it is started here rather than in `main`.
+    let log_format =
+        args::get_value::<server::LogFormat>(matches, "log-format").unwrap_or_default();
+    let rate_limit = matches
+        .get_one::<u32>("rate-limit")
+        .copied()
+        .and_then(std::num::NonZeroU32::new);
+    let server = server::start_server(port, quiet, defaults, log_format, rate_limit);
+    match actix_web::rt::System::new().block_on(server) {
+        Err(cic::error::CicError::Io(e)) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            writeln!(err, "Port {} is already in use; try --port <other>", port)?;
+            Ok(ExitCode::from(EXIT_PORT_IN_USE))
+        }
+        Err(e) => {
+            writeln!(err, "Failed to start server: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+        Ok(()) => Ok(ExitCode::SUCCESS),
+    }
+}
+
+#[cfg(not(feature = "server"))]
+fn run_server<E: Write>(_matches: &ArgMatches, err: &mut E) -> std::io::Result<ExitCode> {
+    writeln!(
+        err,
+        "Failed to start server: cic was built without the \"server\" feature"
+    )?;
+    Ok(ExitCode::FAILURE)
+}
+
+#[cfg(feature = "sqlite")]
+fn write_sqlite(summary: &[YearlySummary], path: &str) -> Result<i64, cic::error::CicError> {
+    output::write_sqlite(summary, path.as_ref())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite(_summary: &[YearlySummary], _path: &str) -> Result<i64, cic::error::CicError> {
+    Err(cic::error::CicError::Output(
+        "cic was built without the \"sqlite\" feature".into(),
+    ))
+}
+
+fn run_portfolio<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let portfolio = Portfolio {
+        accounts: matches
+            .get_many::<Account>("account")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+    };
+    let Some(base) = base_investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let summary = if matches.get_flag("parallel") {
+        portfolio.par_yearly_summary(base.years)
+    } else {
+        portfolio.yearly_summary(base.years)
+    };
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            writeln!(err, "Failed to calculate summary: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    match to_string_pretty(&summary) {
+        Ok(json) => {
+            writeln!(out, "{}", json)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            writeln!(err, "Failed to serialize to JSON: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn run_what_if<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let extra = matches
+        .get_one::<f64>("extra-contribution")
+        .copied()
+        .unwrap_or_default();
+    let what_if = match what_if_extra_contribution(&investment, extra) {
+        Ok(what_if) => what_if,
+        Err(e) => {
+            writeln!(err, "Failed to calculate what-if scenario: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let boosted_label = format!("With +{}/month:", format_currency(extra));
+    writeln!(
+        out,
+        "{:<24}{}",
+        "Baseline final balance:",
+        format_currency(what_if.baseline)
+    )?;
+    writeln!(
+        out,
+        "{:<24}{}",
+        boosted_label,
+        format_currency(what_if.boosted)
+    )?;
+    match what_if.uplift_pct {
+        Some(uplift) => writeln!(
+            out,
+            "{:<24}{} (+{:.1}%)",
+            "Difference:",
+            format_currency(what_if.difference),
+            uplift
+        )?,
+        None => writeln!(
+            out,
+            "{:<24}{}",
+            "Difference:",
+            format_currency(what_if.difference)
+        )?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_cost_of_waiting<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let delay = matches.get_one::<i32>("delay").copied().unwrap_or_default();
+    let cost = match cost_of_waiting(&investment, delay) {
+        Ok(cost) => cost,
+        Err(e) => {
+            writeln!(err, "Failed to calculate cost of waiting: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    writeln!(
+        out,
+        "{:<24}{}",
+        "Start now:",
+        format_currency(cost.start_now)
+    )?;
+    writeln!(
+        out,
+        "{:<24}{}",
+        format!("Start in {} years:", delay),
+        format_currency(cost.start_later)
+    )?;
+    match cost.cost_pct {
+        Some(pct) => writeln!(
+            out,
+            "{:<24}{} ({:.1}%)",
+            "Cost of waiting:",
+            format_currency(cost.cost),
+            pct
+        )?,
+        None => writeln!(
+            out,
+            "{:<24}{}",
+            "Cost of waiting:",
+            format_currency(cost.cost)
+        )?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_goal<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let mut target = matches
+        .get_one::<f64>("target")
+        .copied()
+        .unwrap_or_default();
+    if matches.get_flag("target-in-todays-dollars") {
+        target = investment.nominal_target(target);
+        writeln!(out, "{:<32}{}", "Nominal target:", format_currency(target))?;
+    }
+    let contribution = match required_contribution(&investment, target) {
+        Ok(contribution) => round_contribution(matches, contribution),
+        Err(e) => {
+            writeln!(err, "Failed to calculate required contribution: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    writeln!(
+        out,
+        "{:<32}{}",
+        "Required monthly contribution:",
+        format_currency(contribution)
+    )?;
+    if let Some(rate) = required_savings_rate(contribution, investment.salary) {
+        writeln!(
+            out,
+            "{:<32}{:.1}% of {}",
+            "Required savings rate:",
+            rate,
+            format_currency(investment.salary)
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the FIRE number, the year the investment reaches it, and the monthly contribution that
+/// reaches it within the years of the investment.
+fn run_fire<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let expenses = matches
+        .get_one::<f64>("expenses")
+        .copied()
+        .unwrap_or_default();
+    let withdrawal_rate = matches
+        .get_one::<f64>("withdrawal-rate")
+        .copied()
+        .unwrap_or(4.0);
+    let target = match fire_number(expenses, withdrawal_rate) {
+        Ok(target) => target,
+        Err(e) => {
+            writeln!(err, "Failed to calculate FIRE number: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    writeln!(out, "{:<32}{}", "FIRE number:", format_currency(target))?;
+    match years_to_target(&investment, target) {
+        Some(year) => writeln!(out, "{:<32}year {}", "Reached in:", year)?,
+        None => writeln!(out, "{:<32}not within {} years", "Reached in:", MAX_YEARS)?,
+    }
+    match required_contribution(&investment, target) {
+        Ok(contribution) => writeln!(
+            out,
+            "{:<32}{} to reach it in {} years",
+            "Required monthly contribution:",
+            format_currency(round_contribution(matches, contribution)),
+            investment.years
+        )?,
+        Err(e) => {
+            writeln!(err, "Failed to calculate required contribution: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_time_to_target<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let target = matches
+        .get_one::<f64>("target")
+        .copied()
+        .unwrap_or_default();
+    match years_and_months_to_target(&investment, target) {
+        Ok(time) => writeln!(out, "{:<32}{}", "Target reached in:", time)?,
+        Err(e) => {
+            writeln!(err, "Failed to calculate time to target: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_breakeven_fee<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let high_rate = matches
+        .get_one::<f64>("high-rate")
+        .copied()
+        .unwrap_or_default();
+    let low_rate = matches
+        .get_one::<f64>("low-rate")
+        .copied()
+        .unwrap_or_default();
+    match breakeven_fee(&investment, high_rate, low_rate) {
+        Ok(fee) => writeln!(out, "{:<32}{:.4}%", "Break-even fee:", fee)?,
+        Err(e) => {
+            writeln!(err, "Failed to calculate break-even fee: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_annualize<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let total_return = matches
+        .get_one::<f64>("total-return")
+        .copied()
+        .unwrap_or_default();
+    let years = matches.get_one::<f64>("years").copied().unwrap_or_default();
+    if years <= 0.0 {
+        writeln!(err, "The number of years must be positive")?;
+        return Ok(ExitCode::FAILURE);
+    }
+    if total_return < -100.0 {
+        writeln!(err, "The total return cannot be below -100%")?;
+        return Ok(ExitCode::FAILURE);
+    }
+    writeln!(
+        out,
+        "{:<32}{:.4}%",
+        "Annualized return:",
+        annualize_return(total_return, years)
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the APR, the APY and the rate per period for the rate given as either APR or APY.
+fn run_convert_rate<W: Write>(matches: &ArgMatches, out: &mut W) -> std::io::Result<ExitCode> {
+    let periods = matches.get_one::<u32>("periods").copied().unwrap_or(12);
+    let apr = match matches.get_one::<f64>("apr") {
+        Some(&apr) => apr,
+        None => apy_to_apr(
+            matches.get_one::<f64>("apy").copied().unwrap_or_default(),
+            periods,
+        ),
+    };
+    writeln!(out, "{:<32}{:.4}%", "APR:", apr)?;
+    writeln!(out, "{:<32}{:.4}%", "APY:", apr_to_apy(apr, periods))?;
+    writeln!(
+        out,
+        "{:<32}{:.4}% ({} periods a year)",
+        "Periodic rate:",
+        periodic_rate(apr, periods),
+        periods
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_save_for<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let price = matches.get_one::<f64>("price").copied().unwrap_or_default();
+    let down_payment_pct = matches
+        .get_one::<f64>("down-payment")
+        .copied()
+        .unwrap_or(20.0);
+    let savings = matches
+        .get_one::<f64>("savings")
+        .copied()
+        .unwrap_or_default();
+    let Some(base) = base_investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let (years, rate) = (base.years, base.rate);
+
+    match save_for_down_payment(
+        price,
+        down_payment_pct,
+        years,
+        savings,
+        rate,
+        base.compound_frequency,
+    )
+    .map(|monthly| round_contribution(matches, monthly))
+    {
+        Ok(monthly) => writeln!(
+            out,
+            "Save {}/month for {} years to put {}% ({}) down",
+            format_currency(monthly),
+            years,
+            down_payment_pct,
+            format_currency(price * down_payment_pct / 100.0)
+        )?,
+        Err(e) => {
+            writeln!(err, "Failed to calculate monthly savings: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_solve_principal<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let target = matches
+        .get_one::<f64>("target")
+        .copied()
+        .unwrap_or_default();
+    let Some(base) = base_investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    // The closed form of `required_principal` only holds for annual compounding.
+    if base.compound_frequency != CompoundFrequency::Annual {
+        writeln!(
+            err,
+            "solve-principal only supports annual compounding, but the config or environment compounds {} times a year",
+            base.compound_frequency.periods_per_year()
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+
+    // A fee or the tax of a taxable account, e.g. from CIC_FEE, lowers the rate the principal grows at.
+    let rate = base.net_rate_for_year(1) * 100.0;
+    let principal = required_principal(base.contribution, rate, base.years, target);
+    if principal == 0.0 {
+        writeln!(
+            out,
+            "The contributions alone reach {}: no principal is needed",
+            format_currency(target)
+        )?;
+    } else {
+        writeln!(
+            out,
+            "{:<32}{}",
+            "Required principal:",
+            format_currency(principal)
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_lump_vs_dca<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let amount = matches
+        .get_one::<f64>("amount")
+        .copied()
+        .unwrap_or_default();
+    let months = matches.get_one::<u32>("months").copied().unwrap_or(12);
+    let Some(base) = base_investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+
+    // A fee or the tax of a taxable account, e.g. from CIC_FEE, lowers the rate of both scenarios.
+    let rate = base.net_rate_for_year(1) * 100.0;
+    let comparison = match lump_vs_dca(amount, months, base.years, rate, base.compound_frequency) {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            writeln!(err, "Failed to compare lump sum and DCA: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let (lump_sum, dca) = (comparison.lump_sum_final(), comparison.dca_final());
+    writeln!(out, "Lump sum: {}", format_currency(lump_sum))?;
+    writeln!(
+        out,
+        "Dollar-cost averaging over {} months: {}",
+        months,
+        format_currency(dca)
+    )?;
+    let difference = comparison.difference();
+    let (winner, loser) = if difference >= 0.0 {
+        ("Lump sum", dca)
+    } else {
+        ("Dollar-cost averaging", lump_sum)
+    };
+    if difference == 0.0 {
+        writeln!(out, "Both end with the same balance")?;
+    } else if loser > 0.0 {
+        writeln!(
+            out,
+            "{} wins by {} ({:.1}%)",
+            winner,
+            format_currency(difference.abs()),
+            difference.abs() / loser * 100.0
+        )?;
+    } else {
+        writeln!(
+            out,
+            "{} wins by {}",
+            winner,
+            format_currency(difference.abs())
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_offset_expense<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let (year, amount) = matches
+        .get_one::<(i32, f64)>("expense")
+        .copied()
+        .unwrap_or_default();
+    let extra = match offset_expense(&investment, year, amount) {
+        Ok(extra) => round_contribution(matches, extra),
+        Err(e) => {
+            writeln!(err, "Failed to calculate offset contribution: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    writeln!(
+        out,
+        "{:<32}{} in year {}",
+        "Expense:",
+        format_currency(amount),
+        year
+    )?;
+    writeln!(
+        out,
+        "{:<32}{}",
+        "Extra monthly contribution:",
+        format_currency(extra)
+    )?;
+    writeln!(
+        out,
+        "{:<32}{}",
+        "New monthly contribution:",
+        format_currency(investment.contribution + extra)
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Rounds a solved contribution up to `--round-contribution`, if given.
+fn round_contribution(matches: &ArgMatches, contribution: f64) -> f64 {
+    match matches.get_one::<f64>("round-contribution") {
+        Some(&step) => round_up_to_step(contribution, step),
+        None => contribution,
+    }
+}
+
+fn run_value<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let summary = investment
+        .yearly_summary()
+        .and_then(|summary| output::check_finite(&summary).map(|()| summary));
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            writeln!(err, "Failed to calculate summary: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let value = if matches.get_flag("cagr") {
+        match cagr(&investment) {
+            Some(cagr) => cagr,
+            None => {
+                writeln!(err, "The CAGR is undefined when nothing is invested")?;
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    } else {
+        summary
+            .last()
+            .map_or(investment.principal, |s| s.total_amount)
+    };
+    writeln!(out, "{}", value)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the time-weighted and money-weighted returns of the investment.
+fn run_metrics<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let metrics = [
+        ("Time-weighted return", investment.time_weighted_return()),
+        ("Money-weighted return", investment.money_weighted_return()),
+    ];
+    let mut code = ExitCode::SUCCESS;
+    for (name, metric) in metrics {
+        match metric {
+            Ok(rate) => writeln!(out, "{}: {:.2}% per year", name, rate * 100.0)?,
+            Err(e) => {
+                writeln!(err, "{}: {}", name, e)?;
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Prints the final balance after tax of the investment in every account type.
+fn run_compare_accounts<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    match compare_account_types(&investment) {
+        Ok(results) => {
+            for (account_type, amount) in results {
+                writeln!(
+                    out,
+                    "{:<12}  {:>18}",
+                    account_type.name(),
+                    format_currency(amount)
+                )?;
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            writeln!(err, "Failed to compare accounts: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Prints the 10th, 50th and 90th percentiles of the simulated final balances.
+fn run_simulate<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let volatility = matches
+        .get_one::<f64>("volatility")
+        .copied()
+        .unwrap_or(15.0);
+    let runs = matches.get_one::<usize>("runs").copied().unwrap_or(1000);
+    let seed = seed_from_matches(matches, err)?;
+
+    let finals = match monte_carlo(&investment, volatility, runs, seed) {
+        Ok(finals) => finals,
+        Err(e) => {
+            writeln!(err, "Failed to simulate: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    for p in [10.0, 50.0, 90.0] {
+        let amount = percentile(&finals, p).unwrap_or_default();
+        writeln!(out, "{}th percentile: {}", p, format_currency(amount))?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the seed given with `--seed`, or a random seed that is reported to `err`, so that
+/// the run can be reproduced.
+fn seed_from_matches<E: Write>(matches: &ArgMatches, err: &mut E) -> std::io::Result<u64> {
+    if let Some(&seed) = matches.get_one::<u64>("seed") {
+        return Ok(seed);
+    }
+    let seed = rand::random();
+    writeln!(
+        err,
+        "Note: using seed {}. Pass --seed {} to reproduce",
+        seed, seed
+    )?;
+    Ok(seed)
+}
+
+/// Verifies a JSON report, returning exit code 1 if it does not match the recalculation.
+fn run_verify<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let path = matches
+        .get_one::<String>("file")
+        .cloned()
+        .unwrap_or_default();
+    let tolerance = matches.get_one::<f64>("tolerance").copied().unwrap_or(0.01);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) => {
+            writeln!(err, "Failed to read {}: {}", path, e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let report = match output::parse_echoed_report(&json) {
+        Ok(report) => report,
+        Err(e) => {
+            writeln!(
+                err,
+                "Failed to parse {} (was it written with --json --echo-input?): {}",
+                path, e
+            )?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    match verify_summary(&report.input, &report.summary, tolerance) {
+        Ok(mismatched) if mismatched.is_empty() => {
+            writeln!(out, "OK: all {} years match", report.summary.len())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Ok(mismatched) => {
+            let years: Vec<String> = mismatched.iter().map(|y| y.to_string()).collect();
+            writeln!(err, "Mismatch in year(s) {}", years.join(", "))?;
+            Ok(ExitCode::FAILURE)
+        }
+        Err(e) => {
+            writeln!(err, "Failed to recalculate summary: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn run_datauri<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let summary = investment
+        .yearly_summary()
+        .and_then(|summary| output::check_finite(&summary).map(|()| summary));
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            writeln!(err, "Failed to calculate summary: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    match render_png(&summary, &plot_options(matches, &investment)) {
+        Ok(png) => {
+            writeln!(out, "{}", output::data_uri(&png))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            writeln!(err, "Failed to render chart: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Reads the plot options of the command line, with the CAGR of the investment for the caption.
+fn plot_options(matches: &ArgMatches, investment: &Investment) -> PlotOptions {
+    PlotOptions {
+        cagr: cagr(investment),
+        ..PlotOptions::from_matches(matches)
+    }
+}
+
+fn run_init_config<W: Write, E: Write>(out: &mut W, err: &mut E) -> std::io::Result<ExitCode> {
+    match config::example_config() {
+        Ok(config) => {
+            write!(out, "{}", config)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            writeln!(err, "Failed to generate config: {}", e)?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Formats how far the final balance is from the goal, e.g. `Goal $5,000: short by $59 (1.2%)`.
+fn format_goal_gap(gap: &GoalGap) -> String {
+    let (verb, amount) = if gap.met {
+        ("exceeded by", gap.gap)
+    } else {
+        ("short by", -gap.gap)
+    };
+    let mut line = format!(
+        "Goal {}: {} {}",
+        format_currency(gap.target),
+        verb,
+        format_currency(amount)
+    );
+    if let Some(pct) = gap.gap_pct {
+        line.push_str(&format!(" ({:.1}%)", pct.abs()));
+    }
+    line
+}
+
+fn run_calculation<W: Write, E: Write>(
+    matches: &ArgMatches,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<ExitCode> {
+    let Some(investment) = investment_from_matches(matches, err)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    // Amounts that overflowed are reported once here, before any output is written.
+    let summary = investment
+        .yearly_summary()
+        .and_then(|summary| output::check_finite(&summary).map(|()| summary));
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            writeln!(err, "Failed to calculate summary: {}", e)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if investment.inflation > 0.0 {
+        writeln!(
+            err,
+            "Note: a rate of at least {}% is needed to preserve purchasing power",
+            investment.breakeven_rate()
+        )?;
+    }
+    if investment.inflation > 0.0 || !investment.inflation_rates.is_empty() {
+        let shrinking: Vec<String> = summary
+            .iter()
+            .filter(|s| !s.real_growth_flag)
+            .map(|s| s.year.to_string())
+            .collect();
+        if !shrinking.is_empty() {
+            writeln!(
+                err,
+                "Note: the real value shrinks in year(s) {}",
+                shrinking.join(", ")
+            )?;
+        }
+    }
+
+    if investment.debt {
+        match payoff_year(&summary) {
+            Some(year) => writeln!(err, "Debt paid off in year {}", year)?,
+            None => writeln!(err, "Debt not paid off within {} years", summary.len())?,
+        }
+    }
+
+    if let Some(milestones) = matches.get_many::<f64>("milestones") {
+        let milestones: Vec<f64> = milestones.copied().collect();
+        for (milestone, year) in milestone_years(&summary, &milestones) {
+            match year {
+                Some(year) => writeln!(
+                    err,
+                    "Milestone {}: year {}",
+                    format_currency(milestone),
+                    year
+                )?,
+                None => writeln!(err, "Milestone {}: not reached", format_currency(milestone))?,
+            }
+        }
+    }
+
+    let mut code = ExitCode::SUCCESS;
+    if let Some(&target) = matches.get_one::<f64>("goal") {
+        match goal_gap(&investment, target) {
+            Ok(gap) => writeln!(err, "{}", format_goal_gap(&gap))?,
+            Err(e) => {
+                writeln!(err, "Failed to calculate goal gap: {}", e)?;
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let format = if matches.get_flag("json") {
+        Some(OutputFormat::Json)
+    } else if matches.get_flag("flat-json") {
+        Some(OutputFormat::FlatJson)
+    } else if matches.get_flag("toml") {
+        Some(OutputFormat::Toml)
+    } else {
+        args::get_value::<OutputFormat>(matches, "format")
+    };
+    let sparkline = matches.get_flag("sparkline");
+    let oneline = matches.get_flag("oneline");
+    let output = matches.get_one::<String>("output");
+    let out_file = matches.get_one::<String>("out-file");
+    let sqlite = matches.get_one::<String>("sqlite");
+    let emits: Vec<&Emit> = matches
+        .get_many::<Emit>("emit")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    // With --out-file, the summary goes to the file in the chosen format (JSON by default), and
+    // stdout only receives what was asked for on top of it.
+    let (format, file_format) = match out_file {
+        Some(_) => (None, Some(format.unwrap_or(OutputFormat::Json))),
+        None => (format, None),
+    };
+    // Writing the chart to stdout leaves no room for any other output there.
+    let chart_to_stdout =
+        output.is_some_and(|path| path == "-") || format == Some(OutputFormat::Png);
+    let format = format.filter(|&format| format != OutputFormat::Png);
+    let no_plot = matches.get_flag("no-plot");
+    // Without the chart, the table is printed unless there is another output or --quiet was set.
+    let format = match format {
+        None if no_plot
+            && file_format.is_none()
+            && sqlite.is_none()
+            && emits.is_empty()
+            && !sparkline
+            && !oneline
+            && !matches.get_flag("quiet") =>
+        {
+            Some(OutputFormat::Table)
+        }
+        format => format,
+    };
+    let rate_range = matches.get_one::<(f64, f64, f64)>("rate-range");
+    if chart_to_stdout && rate_range.is_some() {
+        writeln!(
+            err,
+            "The fan chart of --rate-range is written to a file, so it cannot be written to stdout"
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+    if chart_to_stdout && (format.is_some() || sparkline || oneline) {
+        writeln!(
+            err,
+            "The chart is written to stdout, so it cannot be combined with another output format, --sparkline or --oneline"
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let json_options = JsonOptions {
+        final_only: matches.get_flag("final-only"),
+        timestamp: matches.get_flag("timestamp"),
+        input: matches.get_flag("echo-input").then(|| investment.clone()),
+        options: matches
+            .get_flag("with-options")
+            .then(|| CalculationOptions::from(&investment)),
+        metrics: matches
+            .get_flag("with-metrics")
+            .then(|| Metrics::new(&summary, &investment)),
+        crossover: matches.get_flag("with-crossover"),
+        case: args::get_value(matches, "json-case").unwrap_or_default(),
+        human: matches.get_flag("human"),
+    };
+    if let Some(format) = format {
+        match output::format_summary(&summary, format, &json_options) {
+            Ok(output) => out.write_all(&output)?,
+            Err(e) => {
+                writeln!(err, "{}", e)?;
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // Files get the chart with the plot options of the command line.
+    let render_file = |format| match format {
+        OutputFormat::Png => render_png(&summary, &plot_options(matches, &investment)),
+        OutputFormat::Svg => {
+            render_svg(&summary, &plot_options(matches, &investment)).map(String::into_bytes)
+        }
+        format => output::format_summary(&summary, format, &json_options),
+    };
+    if let (Some(path), Some(format)) = (out_file, file_format) {
+        let written =
+            render_file(format).and_then(|contents| output::write_file(path.as_ref(), &contents));
+        if let Err(e) = written {
+            writeln!(err, "Failed to write {}: {}", path, e)?;
+            code = ExitCode::FAILURE;
+        }
     }
 
-    if let Some(matches) = matches.subcommand_matches("server") {
-        let port = args::get_port(&matches);
-        if let Err(e) = server::start_server(port).await {
-            eprintln!("Failed to start server: {}", e);
+    for emit in &emits {
+        let written =
+            render_file(emit.format).and_then(|contents| output::write_file(&emit.path, &contents));
+        match written {
+            Ok(()) => writeln!(err, "Wrote {}", emit.path.display())?,
+            Err(e) => {
+                writeln!(err, "Failed to write {}: {}", emit.path.display(), e)?;
+                code = ExitCode::FAILURE;
+            }
         }
-        return Ok(());
     }
 
-    let investment = Investment::from_matches(&matches);
-    let summary = investment.yearly_summary();
-    if matches.get_flag("json") {
-        match to_string_pretty(&summary) {
-            Ok(json) => println!("{}", json),
-            Err(e) => eprintln!("Failed to serialize to JSON: {}", e),
+    if let Some(path) = sqlite {
+        match write_sqlite(&summary, path) {
+            Ok(run_id) => writeln!(err, "Saved the summary as run {} in {}", run_id, path)?,
+            Err(e) => {
+                writeln!(err, "Failed to write {}: {}", path, e)?;
+                code = ExitCode::FAILURE;
+            }
         }
-        return Ok(());
     }
-    match plot_summary(&summary) {
-        Ok(_) => (),
-        Err(e) => eprintln!("Failed to plot summary: {}", e),
+
+    if sparkline {
+        writeln!(out, "{}", output::sparkline(&summary))?;
+    }
+    if oneline {
+        let currency = if json_options.human {
+            humanize
+        } else {
+            format_currency
+        };
+        writeln!(
+            out,
+            "{}",
+            output::oneline_with(&summary, &investment, currency)
+        )?;
     }
-    Ok(())
+
+    if let Some(path) = matches.get_one::<String>("composition") {
+        if let Err(e) = plot_composition_pct(&summary, path.as_ref()) {
+            writeln!(err, "Failed to plot composition: {}", e)?;
+            code = ExitCode::FAILURE;
+        }
+    }
+
+    // The chart is always plotted unless --no-plot is set, or an output format, an output file, a
+    // database, a sparkline or a one-line summary was requested without an explicit --output.
+    let other_output = format.is_some()
+        || file_format.is_some()
+        || !emits.is_empty()
+        || sqlite.is_some()
+        || sparkline
+        || oneline;
+    if no_plot || other_output && output.is_none() {
+        return Ok(code);
+    }
+    let mut options = plot_options(matches, &investment);
+    if chart_to_stdout {
+        match render_png(&summary, &options) {
+            Ok(png) => out.write_all(&png)?,
+            Err(e) => {
+                writeln!(err, "Failed to plot summary: {}", e)?;
+                code = ExitCode::FAILURE;
+            }
+        }
+        return Ok(code);
+    }
+    if let Some(path) = output {
+        options.path = path.into();
+    }
+    if let Some(&(low, _, high)) = rate_range {
+        let at_rate = |rate| {
+            Investment {
+                rate,
+                ..investment.clone()
+            }
+            .yearly_summary()
+        };
+        let plotted = match (at_rate(low), at_rate(high)) {
+            (Ok(low), Ok(high)) => plot_fan([&low, &summary, &high], &options.path),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+        if let Err(e) = plotted {
+            writeln!(err, "Failed to plot fan chart: {}", e)?;
+            code = ExitCode::FAILURE;
+        }
+        return Ok(code);
+    }
+    if let Err(e) = plot_summary(&summary, &options) {
+        writeln!(err, "Failed to plot summary: {}", e)?;
+        code = ExitCode::FAILURE;
+    }
+    Ok(code)
+}
+
+fn main() -> std::io::Result<ExitCode> {
+    run(
+        std::env::args_os(),
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    )
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    run().await
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_plot() {
+        // The default chart may exist in the working directory, so check that it is not rewritten.
+        let plot = std::path::Path::new("plot.png");
+        let modified = || plot.metadata().and_then(|m| m.modified()).ok();
+        let before = modified();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            ["cic", "-p", "1000", "-y", "3", "--no-plot"],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(modified(), before);
+        assert!(err.is_empty(), "{}", String::from_utf8_lossy(&err));
+        let expected = output::format_summary(
+            &Investment {
+                principal: 1000.0,
+                years: 3,
+                ..Default::default()
+            }
+            .yearly_summary()
+            .unwrap(),
+            OutputFormat::Table,
+            &JsonOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(out, expected);
+
+        let mut out = Vec::new();
+        run(
+            ["cic", "-y", "3", "--no-plot", "--quiet"],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(modified(), before);
+        assert!(out.is_empty());
+
+        let mut out = Vec::new();
+        run(
+            ["cic", "-y", "3", "--no-plot", "--oneline"],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(modified(), before);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+
+        assert!(args::build_cli()
+            .try_get_matches_from(["cic", "--no-plot", "--output", "chart.png"])
+            .is_err());
+        assert!(args::build_cli()
+            .try_get_matches_from(["cic", "--quiet"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_human() {
+        let scenario = ["cic", "-p", "1000000", "-c", "10000", "-r", "10", "-y", "2"];
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [&scenario[..], &["--oneline", "--human"]].concat();
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "After 2y: $1.5M (contributed $240.0k, interest $222.0k, CAGR 10%)\n"
+        );
+        assert!(err.is_empty());
+
+        let mut out = Vec::new();
+        let args = [&scenario[..], &["--format", "table", "--human"]].concat();
+        run(args, &mut out, &mut err).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("$1.5M"));
+
+        // JSON keeps the full precision.
+        let mut out = Vec::new();
+        let args = [&scenario[..], &["--json", "--human"]].concat();
+        run(args, &mut out, &mut err).unwrap();
+        let summary: Vec<cic::calculations::YearlySummary> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(summary[1].total_contribution, 240000.0);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_server_port_in_use() {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run(["cic", "server", "-p", &port, "-q"], &mut out, &mut err).unwrap();
+        assert_eq!(code, ExitCode::from(EXIT_PORT_IN_USE));
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            format!("Port {} is already in use; try --port <other>\n", port)
+        );
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_allocation() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "value",
+            "--final",
+            "-p",
+            "1000",
+            "-c",
+            "0",
+            "-y",
+            "1",
+            "--allocation",
+            "stocks:60:8,bonds:40:3",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1060\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "Note: using the blended rate of 6% from stocks 60% at 8%, bonds 40% at 3%\n"
+        );
+    }
+
+    #[test]
+    fn test_cost_of_waiting() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "cost-of-waiting",
+            "-p",
+            "1000",
+            "-c",
+            "100",
+            "-y",
+            "30",
+            "--delay",
+            "5",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", out);
+        assert!(lines[0].starts_with("Start now:"));
+        assert!(lines[1].starts_with("Start in 5 years:"));
+        assert!(lines[2].starts_with("Cost of waiting:        $"));
+        assert!(err.is_empty());
+
+        // A pause during the delay only lowers the result of starting now.
+        let start_later = |out: &str| out.lines().nth(1).unwrap().to_string();
+        let mut paused = Vec::new();
+        let args = [
+            "cic",
+            "cost-of-waiting",
+            "-p",
+            "1000",
+            "-c",
+            "100",
+            "-y",
+            "30",
+            "--delay",
+            "5",
+            "--pause-years",
+            "2:5",
+        ];
+        run(args, &mut paused, &mut err).unwrap();
+        let paused = String::from_utf8(paused).unwrap();
+        assert_ne!(paused.lines().next(), out.lines().next());
+        assert_eq!(start_later(&paused), start_later(&out));
+        assert!(err.is_empty());
+
+        let mut out = Vec::new();
+        let args = ["cic", "cost-of-waiting", "-y", "5", "-d", "6"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("The delay must be between 0 and the 5 years"));
+    }
+
+    #[test]
+    fn test_offset_expense() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "offset-expense",
+            "-p",
+            "10000",
+            "-c",
+            "500",
+            "-r",
+            "6",
+            "-y",
+            "15",
+            "--expense",
+            "8:30000",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Expense:                        $30,000 in year 8\n\
+             Extra monthly contribution:     $162\n\
+             New monthly contribution:       $662\n"
+        );
+        assert!(err.is_empty());
+
+        let mut out = Vec::new();
+        let args = ["cic", "offset-expense", "-y", "5", "-e", "8:30000"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("The year of the expense must be between 1 and 5"));
+
+        assert!(args::build_cli()
+            .try_get_matches_from(["cic", "offset-expense", "-e", "8:-100"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_solve_principal() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "100000",
+            "-c",
+            "200",
+            "-r",
+            "6",
+            "-y",
+            "15",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Required principal:             $18,417\n"
+        );
+
+        let mut out = Vec::new();
+        let args = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "10000",
+            "-c",
+            "200",
+            "-y",
+            "15",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "The contributions alone reach $10,000: no principal is needed\n"
+        );
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_solve_principal_applies_the_fee() {
+        let path = std::env::temp_dir().join("cic_test_solve_principal_fee.toml");
+        std::fs::write(&path, "fee = 2.0\n").unwrap();
+        let args = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "50000",
+            "-c",
+            "100",
+            "-r",
+            "5",
+            "-y",
+            "10",
+            "--config",
+            path.to_str().unwrap(),
+        ];
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(args, &mut out, &mut err).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Required principal:             $26,968\n"
+        );
+        assert!(err.is_empty(), "{}", String::from_utf8_lossy(&err));
+    }
+
+    #[test]
+    fn test_solve_principal_rejects_other_compound_frequencies() {
+        let path = std::env::temp_dir().join("cic_test_solve_principal_monthly.toml");
+        std::fs::write(&path, "compound_frequency = \"monthly\"\n").unwrap();
+        let args = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "10000",
+            "-r",
+            "10",
+            "-y",
+            "10",
+            "--config",
+            path.to_str().unwrap(),
+        ];
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run(args, &mut out, &mut err).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("solve-principal only supports annual compounding"));
+    }
+
+    #[test]
+    fn test_standalone_subcommands_read_the_config() {
+        let path = std::env::temp_dir().join("cic_test_standalone_config.toml");
+        std::fs::write(&path, "rate = 10.0\nyears = 15\n").unwrap();
+        let config = path.to_str().unwrap();
+        let output = |args: &[&str]| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            run(args.iter().copied(), &mut out, &mut err).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let with_config = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "100000",
+            "-c",
+            "200",
+            "--config",
+            config,
+        ];
+        let with_flags = [
+            "cic",
+            "solve-principal",
+            "-t",
+            "100000",
+            "-c",
+            "200",
+            "-r",
+            "10",
+            "-y",
+            "15",
+        ];
+        assert_eq!(output(&with_config), output(&with_flags));
+        let with_config = [
+            "cic",
+            "lump-vs-dca",
+            "--amount",
+            "12000",
+            "--config",
+            config,
+        ];
+        let with_flags = [
+            "cic",
+            "lump-vs-dca",
+            "--amount",
+            "12000",
+            "-r",
+            "10",
+            "-y",
+            "15",
+        ];
+        assert_eq!(output(&with_config), output(&with_flags));
+        let with_config = ["cic", "save-for", "--price", "400000", "--config", config];
+        let with_flags = [
+            "cic", "save-for", "--price", "400000", "-r", "10", "-y", "15",
+        ];
+        assert_eq!(output(&with_config), output(&with_flags));
+        let with_config = ["cic", "portfolio", "-a", "s:1000:100:2", "--config", config];
+        let with_flags = ["cic", "portfolio", "-a", "s:1000:100:2", "-y", "15"];
+        assert_eq!(output(&with_config), output(&with_flags));
+
+        // Fields other than the rate and the years are honored too.
+        std::fs::write(&path, "compound_frequency = \"monthly\"\n").unwrap();
+        let lump_sum = output(&[
+            "cic",
+            "lump-vs-dca",
+            "--amount",
+            "12000",
+            "--config",
+            config,
+        ]);
+        let monthly = lump_vs_dca(12000.0, 12, 5, 5.0, CompoundFrequency::Monthly).unwrap();
+        assert!(lump_sum.starts_with(&format!(
+            "Lump sum: {}\n",
+            format_currency(monthly.lump_sum_final())
+        )));
+        let save_for = output(&["cic", "save-for", "--price", "400000", "--config", config]);
+        let monthly =
+            save_for_down_payment(400000.0, 20.0, 5, 0.0, 5.0, CompoundFrequency::Monthly).unwrap();
+        assert!(save_for.starts_with(&format!("Save {}/month", format_currency(monthly))));
+        assert_ne!(save_for, output(&["cic", "save-for", "--price", "400000"]));
+
+        // The fee and the tax lower both finals: 5% - 3% = 2%, of which half is taxed.
+        std::fs::write(&path, "fee = 3.0\ntax_rate = 50.0\n").unwrap();
+        let args = [
+            "cic",
+            "lump-vs-dca",
+            "--amount",
+            "12000",
+            "--config",
+            config,
+        ];
+        let net = lump_vs_dca(12000.0, 12, 5, 1.0, CompoundFrequency::Annual).unwrap();
+        let gross = lump_vs_dca(12000.0, 12, 5, 5.0, CompoundFrequency::Annual).unwrap();
+        assert!(net.lump_sum_final() < gross.lump_sum_final());
+        assert!(net.dca_final() < gross.dca_final());
+        let lines = output(&args);
+        assert!(lines.starts_with(&format!(
+            "Lump sum: {}\nDollar-cost averaging over 12 months: {}\n",
+            format_currency(net.lump_sum_final()),
+            format_currency(net.dca_final())
+        )));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_goal_gap() {
+        for (goal, expected) in [
+            ("5000", "Goal $5,000: short by $59 (1.2%)\n"),
+            ("4000", "Goal $4,000: exceeded by $941 (23.5%)\n"),
+        ] {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let args = [
+                "cic",
+                "-p",
+                "1000",
+                "-c",
+                "100",
+                "-y",
+                "3",
+                "--goal",
+                goal,
+                "--oneline",
+            ];
+            run(args, &mut out, &mut err).unwrap();
+            assert_eq!(String::from_utf8(err).unwrap(), expected);
+            assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_chart_to_stdout() {
+        for args in [
+            ["cic", "-p", "1000", "-y", "3", "--output", "-"],
+            ["cic", "-p", "1000", "-y", "3", "--format", "png"],
+        ] {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            run(args, &mut out, &mut err).unwrap();
+
+            assert!(out.starts_with(b"\x89PNG\r\n\x1a\n"), "{:?}", args);
+            assert!(image::load_from_memory(&out).is_ok());
+            assert!(err.is_empty());
+        }
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = ["cic", "-p", "1000", "-y", "3", "--output", "-", "--json"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(!err.is_empty());
+    }
+
+    #[test]
+    fn test_json_and_plot_in_one_run() {
+        let path = std::env::temp_dir().join("cic_test_json_and_plot.png");
+        let _ = std::fs::remove_file(&path);
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            [
+                "cic",
+                "--years",
+                "3",
+                "--json",
+                "--output",
+                path.to_str().unwrap(),
+            ],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_warnings_are_not_written_to_stdout() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            [
+                "cic",
+                "--years",
+                "3",
+                "--rate",
+                "0.05",
+                "--inflation",
+                "10",
+                "--json",
+            ],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        let stdout = String::from_utf8(out).unwrap();
+        let stderr = String::from_utf8(err).unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&stdout).is_ok());
+        assert!(!stdout.contains("Warning"));
+        assert!(!stdout.contains("Note"));
+        assert!(stderr.contains("Warning: rate 0.05"));
+        assert!(stderr.contains("Note: a rate of at least 10%"));
+    }
+
+    #[test]
+    fn test_query_sets_the_scenario() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            [
+                "cic",
+                "--query",
+                "principal=1000&contribution=100&rate=5&years=3&foo=1",
+                "--json",
+                "--final-only",
+            ],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["year"], 3);
+        assert_eq!(json["final_amount"], 4940.625);
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("unknown query key \"foo\""));
+    }
+
+    #[test]
+    fn test_no_args_prints_usage_to_stderr() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run(["cic"], &mut out, &mut err).unwrap();
+
+        assert_eq!(code, ExitCode::from(2));
+        assert!(out.is_empty());
+        let usage = String::from_utf8(err).unwrap();
+        assert!(usage.contains("Usage: cic [OPTIONS] [COMMAND]"));
+    }
+
+    #[test]
+    fn test_zero_years() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            ["cic", "--years", "0", "--format", "csv"],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+        assert!(err.is_empty());
+
+        let path = std::env::temp_dir().join("cic_test_zero_years.png");
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            ["cic", "--years", "0", "--output", path.to_str().unwrap()],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("There is no data to plot"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_value_prints_bare_number() {
+        let base = [
+            "cic", "value", "-p", "1000", "-c", "100", "-r", "5", "-y", "3",
+        ];
+        for (metric, expected) in [("--final", 4940.625), ("--cagr", 0.05)] {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let args = base.iter().copied().chain([metric]);
+            run(args, &mut out, &mut err).unwrap();
+
+            let stdout = String::from_utf8(out).unwrap();
+            let value: f64 = stdout.trim_end_matches('\n').parse().unwrap();
+            assert!((value - expected).abs() < 1e-6, "{}: {}", metric, value);
+            assert!(err.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_out_file() {
+        let dir = std::env::temp_dir().join("cic_test_out_file");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("results.json");
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "-p",
+            "1000",
+            "-y",
+            "3",
+            "--out-file",
+            path.to_str().unwrap(),
+        ];
+        run(args, &mut out, &mut err).unwrap();
+
+        assert!(out.is_empty());
+        assert!(err.is_empty());
+        let json = std::fs::read_to_string(&path).unwrap();
+        let summary: Vec<cic::calculations::YearlySummary> = serde_json::from_str(&json).unwrap();
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0].principal, 1000.0);
+
+        let args = [
+            "cic",
+            "-y",
+            "3",
+            "--format",
+            "csv",
+            "--out-file",
+            path.to_str().unwrap(),
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("year,"));
+
+        // A parent that is a file cannot be created as a directory.
+        let blocked = path.join("results.json");
+        let mut err = Vec::new();
+        run(
+            ["cic", "--out-file", blocked.to_str().unwrap()],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .starts_with("Failed to write"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_emit() {
+        let dir = std::env::temp_dir().join("cic_test_emit");
+        let _ = std::fs::remove_dir_all(&dir);
+        let png = dir.join("chart.png");
+        let svg = dir.join("chart.svg");
+        let csv = dir.join("data.csv");
+        let emit = |format: &str, path: &std::path::Path| format!("{}:{}", format, path.display());
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic".to_string(),
+            "-y".to_string(),
+            "3".to_string(),
+            "--emit".to_string(),
+            emit("png", &png),
+            "--emit".to_string(),
+            emit("svg", &svg),
+            "--emit".to_string(),
+            emit("csv", &csv),
+        ];
+        run(args, &mut out, &mut err).unwrap();
+
+        assert!(out.is_empty());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            format!(
+                "Wrote {}\nWrote {}\nWrote {}\n",
+                png.display(),
+                svg.display(),
+                csv.display()
+            )
+        );
+        assert!(std::fs::read(&png).unwrap().starts_with(b"\x89PNG"));
+        assert!(std::fs::read_to_string(&svg).unwrap().starts_with("<svg"));
+        let csv = std::fs::read_to_string(&csv).unwrap();
+        assert!(csv.starts_with("year,"));
+        assert_eq!(csv.lines().count(), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(args::build_cli()
+            .try_get_matches_from(["cic", "--emit", "chart.png"])
+            .is_err());
+        assert!(args::build_cli()
+            .try_get_matches_from(["cic", "--emit", "gif:chart.gif"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_time_to_target() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "time-to-target",
+            "-t",
+            "2500",
+            "-p",
+            "1000",
+            "-c",
+            "100",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Target reached in:              1 year 3 months\n"
+        );
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = ["cic", "time-to-target", "-t", "2500", "-c", "0", "-r", "0"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("is not reached within 1000 years"));
+    }
+
+    #[test]
+    fn test_annualize() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = ["cic", "annualize", "--total-return", "100", "-y", "10"];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Annualized return:              7.1773%\n"
+        );
+
+        let mut out = Vec::new();
+        let args = ["cic", "annualize", "--total-return", "-20", "-y", "0"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "The number of years must be positive\n"
+        );
+    }
+
+    #[test]
+    fn test_breakeven_fee() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "breakeven-fee",
+            "-p",
+            "10000",
+            "-y",
+            "10",
+            "--high-rate",
+            "7",
+            "--low-rate",
+            "5",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Break-even fee:                 2.0000%\n"
+        );
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_convert_rate() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(["cic", "convert-rate", "--apr", "12"], &mut out, &mut err).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "APR:                            12.0000%\n\
+             APY:                            12.6825%\n\
+             Periodic rate:                  1.0000% (12 periods a year)\n"
+        );
+
+        let mut out = Vec::new();
+        let args = ["cic", "convert-rate", "--apy", "12.6825", "-n", "12"];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("APR:                            12.0000%\n"));
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_fire() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "fire",
+            "--expenses",
+            "40000",
+            "-p",
+            "100000",
+            "-c",
+            "2000",
+            "-r",
+            "7",
+            "-y",
+            "20",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "FIRE number:                    $1,000,000");
+        assert!(lines[1].starts_with("Reached in:                     year "));
+        assert!(
+            lines[2].ends_with("to reach it in 20 years"),
+            "{}",
+            lines[2]
+        );
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_with_options() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic",
+            "-y",
+            "2",
+            "--compound-frequency",
+            "monthly",
+            "--json",
+            "--with-options",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value["options"]["compound_frequency"], "monthly");
+        assert_eq!(value["summary"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_debt() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic", "--debt", "-p", "-10000", "-c", "300", "-r", "10", "-y", "5", "--json",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+        assert!(!out.is_empty());
+        assert_eq!(String::from_utf8(err).unwrap(), "Debt paid off in year 4\n");
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(["cic", "-p", "-10000", "--json"], &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err).unwrap().contains("--debt"));
+    }
+
+    #[test]
+    fn test_oneline() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = ["cic", "-p", "1000", "-c", "100", "-y", "3", "--oneline"];
+        run(args, &mut out, &mut err).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "After 3y: $4,941 (contributed $3,600, interest $341, CAGR 5%)\n"
+        );
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_high_rate_warning() {
+        let warnings = |args: &[&str]| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            run(args.iter().copied(), &mut out, &mut err).unwrap();
+            assert!(!out.is_empty());
+            String::from_utf8(err).unwrap()
+        };
+        let high = warnings(&["cic", "-r", "50", "-y", "1", "--json"]);
+        assert!(high.contains("Warning: a rate of 50% a year is above 30%"));
+        assert!(warnings(&["cic", "-r", "7", "-y", "1", "--json"]).is_empty());
+        assert!(warnings(&["cic", "-r", "50", "-y", "1", "--json", "--no-warn"]).is_empty());
+        assert!(warnings(&["cic", "-r", "50", "-y", "1", "--json", "--max-rate", "60"]).is_empty());
+    }
+
+    #[test]
+    fn test_rate_warning() {
+        let warnings = |args: &[&str]| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            run(args.iter().copied(), &mut out, &mut err).unwrap();
+            assert!(!out.is_empty());
+            String::from_utf8(err).unwrap()
+        };
+        let warning = "Warning: rate 0.05 is interpreted as 0.05%";
+        assert!(warnings(&["cic", "-r", "0.05", "-y", "1", "--json"]).contains(warning));
+        assert!(warnings(&["cic", "value", "--final", "-r", "0.05"]).contains(warning));
+        assert!(warnings(&["cic", "metrics", "-r", "0.05"]).contains(warning));
+        assert!(warnings(&["cic", "value", "--final", "-r", "5"]).is_empty());
+        let decimal = ["cic", "metrics", "-r", "0.05", "--rate-format", "decimal"];
+        assert!(warnings(&decimal).is_empty());
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let args = [
+            "cic", "metrics", "-p", "1000", "-c", "100", "-r", "5", "-y", "3",
+        ];
+        run(args, &mut out, &mut err).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Time-weighted return: 5.00% per year\nMoney-weighted return: 5.00% per year\n"
+        );
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_same_seed_gives_identical_simulation() {
+        let simulate = |seed: &str| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let mut args = vec!["cic", "simulate", "-p", "1000", "-c", "100", "-y", "10"];
+            if !seed.is_empty() {
+                args.extend(["--seed", seed]);
+            }
+            run(args, &mut out, &mut err).unwrap();
+            (out, String::from_utf8(err).unwrap())
+        };
+
+        let (first, err) = simulate("7");
+        assert!(err.is_empty());
+        assert_eq!(first, simulate("7").0);
+        assert_ne!(first, simulate("8").0);
+        assert!(simulate("").1.starts_with("Note: using seed"));
+    }
+
+    #[test]
+    fn test_verify_report() {
+        let path = std::env::temp_dir().join("cic_test_verify.json");
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        run(
+            [
+                "cic",
+                "-p",
+                "1000",
+                "-c",
+                "100",
+                "-y",
+                "4",
+                "--json",
+                "--echo-input",
+            ],
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        std::fs::write(&path, &out).unwrap();
+
+        let verify = ["cic", "verify", path.to_str().unwrap()];
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run(verify, &mut out, &mut err).unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("all 4 years match"));
+
+        let mut report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        report["summary"][1]["total_interest"] = serde_json::json!(1.0);
+        std::fs::write(&path, report.to_string()).unwrap();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run(verify, &mut out, &mut err).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(String::from_utf8(err)
+            .unwrap()
+            .contains("Mismatch in year(s) 2"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_overflow_is_reported_before_any_output() {
+        for flag in ["--oneline", "--sparkline", "--json", "--no-plot"] {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let args = [
+                "cic",
+                "-p",
+                "1",
+                "-r",
+                "1e300",
+                "-y",
+                "3",
+                "--no-warn",
+                flag,
+            ];
+            let code = run(args, &mut out, &mut err).unwrap();
+            assert_eq!(code, ExitCode::FAILURE);
+            assert!(out.is_empty());
+            assert!(String::from_utf8(err)
+                .unwrap()
+                .contains("The amounts overflowed"));
+        }
+    }
+
+    #[test]
+    fn test_errors_exit_with_failure() {
+        let exit_code = |args: &[&str]| {
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let code = run(args.iter().copied(), &mut out, &mut err).unwrap();
+            if code == ExitCode::FAILURE {
+                assert!(!err.is_empty());
+            }
+            code
+        };
+        assert_eq!(
+            exit_code(&["cic", "value", "--final", "-y", "5000"]),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            exit_code(&["cic", "-y", "5000", "--json"]),
+            ExitCode::FAILURE
+        );
+        assert_eq!(exit_code(&["cic", "-p", "-5", "--json"]), ExitCode::FAILURE);
+        assert_eq!(
+            exit_code(&["cic", "annualize", "--total-return", "50", "-y", "0"]),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            exit_code(&["cic", "value", "--final", "-y", "5"]),
+            ExitCode::SUCCESS
+        );
+    }
 }
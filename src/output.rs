@@ -0,0 +1,1631 @@
+use crate::calculations::{
+    apr_to_apy, cagr, crossover_year, format_currency, humanize, render_png, render_svg,
+    AccountType, CompoundFrequency, ContributionFrequency, InterestMode, Investment, PlotOptions,
+    RateTier, YearlySummary,
+};
+use crate::error::CicError;
+use base64::Engine;
+use chrono::{SecondsFormat, Utc};
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Describes how the summary is written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Pretty-printed JSON, see `to_json`.
+    #[default]
+    Json,
+    /// Comma-separated values with a header row, see `to_csv`.
+    Csv,
+    /// A human-readable table, see `to_table`.
+    Table,
+    /// An Excel workbook, see `to_xlsx`.
+    Xlsx,
+    /// The chart as a PNG image, see `render_png`.
+    Png,
+    /// A flat JSON array of records with a fixed schema, see `to_flat_json`.
+    FlatJson,
+    /// TOML with one `[[year]]` table per year, see `to_toml`.
+    Toml,
+    /// The chart as an SVG image, see `render_svg`.
+    Svg,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "table" => Ok(Self::Table),
+            "xlsx" => Ok(Self::Xlsx),
+            "png" => Ok(Self::Png),
+            "flat-json" => Ok(Self::FlatJson),
+            "toml" => Ok(Self::Toml),
+            "svg" => Ok(Self::Svg),
+            _ => Err(format!(
+                "Invalid output format: {} (expected json, csv, table, xlsx, png, svg, flat-json or toml)",
+                s
+            )),
+        }
+    }
+}
+
+/// Describes how the keys of the JSON output are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonCase {
+    /// The field names as they are, e.g. `total_amount`.
+    #[default]
+    Snake,
+    /// camelCase, e.g. `totalAmount`, as preferred by JavaScript clients.
+    Camel,
+}
+
+impl std::str::FromStr for JsonCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snake" => Ok(Self::Snake),
+            "camel" => Ok(Self::Camel),
+            _ => Err(format!(
+                "Invalid JSON case: {} (expected snake or camel)",
+                s
+            )),
+        }
+    }
+}
+
+/// Options that control how the summary is rendered as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// If `true`, only the final year is rendered as a compact `FinalResult`.
+    pub final_only: bool,
+    /// If `true`, the output is wrapped in an object with a `generated_at` UTC timestamp.
+    pub timestamp: bool,
+    /// If set, the output is wrapped in an object that echoes the input as `input`, so that the
+    /// report can be verified later by recalculating it.
+    pub input: Option<Investment>,
+    /// If set, the output is wrapped in an object that lists the calculation options as `options`.
+    pub options: Option<CalculationOptions>,
+    /// If set, the output is wrapped in an object with the headline `metrics`.
+    pub metrics: Option<Metrics>,
+    /// If `true`, the output is wrapped in an object even without the other fields, so that the
+    /// `crossover_year` of the summary of every year is written.
+    pub crossover: bool,
+    /// How the keys are written. In camelCase, the keys of every object are sorted.
+    pub case: JsonCase,
+    /// If `true`, the amounts of the table are abbreviated with `humanize`, e.g. `$4.9M`. The
+    /// other formats keep the full precision.
+    pub human: bool,
+}
+
+/// Represents the headline metrics of a summary, computed once for the whole horizon.
+///
+/// The rates are percentages. An empty summary has zero amounts and no CAGR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metrics {
+    /// The total amount at the end of the final year.
+    pub final_amount: f64,
+    /// The contributions made over all years, without the principal.
+    pub total_contributed: f64,
+    /// The interest earned over all years.
+    pub total_interest: f64,
+    /// The compound annual growth rate (in %), see `cagr`, or `None` if nothing was invested.
+    pub cagr: Option<f64>,
+    /// The overall return (in %), see `YearlySummary::total_return_pct`.
+    pub total_return_pct: f64,
+    /// The effective annual yield (in %) of the rate at the compounding frequency, see `apr_to_apy`.
+    pub apy: f64,
+}
+
+impl Metrics {
+    /// Computes the metrics of a summary calculated from the investment.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::calculations::Investment;
+    /// use cic::output::Metrics;
+    ///
+    /// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() };
+    /// let metrics = Metrics::new(&investment.yearly_summary().unwrap(), &investment);
+    /// assert_eq!(metrics.total_contributed, 3600.0);
+    /// assert_eq!(metrics.apy, 5.0);
+    /// ```
+    pub fn new(summary: &[YearlySummary], investment: &Investment) -> Self {
+        let last = summary.last().cloned().unwrap_or_default();
+        Self {
+            final_amount: last.total_amount,
+            total_contributed: last.total_contribution,
+            total_interest: last.total_interest,
+            cagr: summary
+                .last()
+                .and_then(|_| cagr(investment))
+                .map(|cagr| cagr * 100.0),
+            total_return_pct: last.total_return_pct(),
+            apy: apr_to_apy(
+                investment.rate_per_period() * 100.0,
+                investment.compound_frequency.periods_per_year(),
+            ),
+        }
+    }
+}
+
+/// Represents the calculation options that were applied to a summary, e.g. the compounding
+/// frequency, fees and taxes.
+///
+/// Unlike the echoed input, every mode is listed with its effective value, including the defaults
+/// and the values derived from other inputs, so a results file says exactly what was applied.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalculationOptions {
+    /// How often interest is compounded.
+    pub compound_frequency: CompoundFrequency,
+    /// How often the contribution is made.
+    pub contribution_frequency: ContributionFrequency,
+    /// Whether the contributions earn half of the interest of the period they are made in.
+    pub mid_year_contribution: bool,
+    /// Whether interest is compounded or simple.
+    pub interest_mode: InterestMode,
+    /// The number of years with their own rate, e.g. from a glide path. 0 means `rate` is used.
+    pub variable_rate_years: usize,
+    /// The annual fee (in %) that is subtracted from the rate. 0 if the rate is net of fees.
+    pub fee: f64,
+    /// Whether the rate is already the net return after fees.
+    pub rate_is_net: bool,
+    /// How the investment is taxed.
+    pub account_type: AccountType,
+    /// The tax rate (in %).
+    pub tax_rate: f64,
+    /// The annual inflation rate (in %) used where no series is given.
+    pub inflation: f64,
+    /// The number of years covered by an inflation series. 0 means `inflation` is used.
+    pub inflation_series_years: usize,
+    /// Whether the fixed contribution grows with inflation.
+    pub index_contributions_to_inflation: bool,
+    /// Whether the contribution is derived from a salary.
+    pub salary_based_contribution: bool,
+    /// The number of months contributions are made for, or `None` for every year.
+    pub contribution_months: Option<u32>,
+    /// The months of the year in which no contribution is made.
+    pub skip_months: Vec<u32>,
+    /// The first and last year without contributions, or `None` if there is no pause.
+    pub pause_years: Option<(i32, i32)>,
+    /// The amounts added (or withdrawn, if negative) at the end of a year, as `(year, amount)`.
+    pub lump_sums: Vec<(i32, f64)>,
+    /// The minimum-balance interest tier, or `None` if the rate applies to the whole balance.
+    pub tier: Option<RateTier>,
+    /// The employer match (in %) of the contributions.
+    pub match_rate: f64,
+    /// The maximum employer match per year, or `None` if it is not capped.
+    pub match_cap: Option<f64>,
+    /// Whether a debt is paid down.
+    pub debt: bool,
+}
+
+impl From<&Investment> for CalculationOptions {
+    fn from(investment: &Investment) -> Self {
+        Self {
+            compound_frequency: investment.compound_frequency,
+            contribution_frequency: investment.contribution_frequency,
+            mid_year_contribution: investment.mid_year_contribution,
+            interest_mode: investment.interest_mode,
+            variable_rate_years: investment.variable_rates.len(),
+            fee: investment.fee_per_period() * 100.0,
+            rate_is_net: investment.rate_is_net,
+            account_type: investment.account_type,
+            tax_rate: investment.tax_rate,
+            inflation: investment.inflation,
+            inflation_series_years: investment.inflation_rates.len(),
+            index_contributions_to_inflation: investment.index_contributions_to_inflation,
+            salary_based_contribution: investment.salary > 0.0,
+            contribution_months: investment.contribution_months,
+            skip_months: investment.skip_months.clone(),
+            pause_years: investment.pause_years,
+            lump_sums: investment.lump_sums.clone(),
+            tier: investment.tier,
+            match_rate: investment.match_rate,
+            match_cap: investment
+                .match_cap
+                .is_finite()
+                .then_some(investment.match_cap),
+            debt: investment.debt,
+        }
+    }
+}
+
+/// Represents the top-level JSON object used when metadata is added to the summary.
+#[derive(Debug, Serialize)]
+struct Report<T: Serialize> {
+    /// The RFC3339 UTC timestamp of when the report was generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated_at: Option<String>,
+    /// The input the summary was calculated from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input: Option<Investment>,
+    /// The calculation options that were applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<CalculationOptions>,
+    /// The headline metrics.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<Metrics>,
+    /// The summary, either all years or the final result.
+    summary: T,
+    /// The first year the interest exceeds the contribution, see `crossover_year`. Only set along
+    /// with the summary of every year, as the final result has its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crossover_year: Option<i32>,
+}
+
+/// Represents the compact result of the final year of an investment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FinalResult {
+    /// The final year of the investment.
+    pub year: i32,
+    /// The total amount of money at the end of the final year.
+    pub final_amount: f64,
+    /// The cumulative total contribution up to the end of the final year.
+    pub total_contribution: f64,
+    /// The cumulative total interest earned up to the end of the final year.
+    pub total_interest: f64,
+    /// The overall return up to the end of the final year as a percentage.
+    pub total_return_pct: f64,
+    /// The first year the interest exceeds the contribution, see `crossover_year`.
+    pub crossover_year: Option<i32>,
+}
+
+impl FinalResult {
+    /// Creates the `FinalResult` of a summary, or `None` if the summary is empty.
+    pub fn from_summary(summary: &[YearlySummary]) -> Option<Self> {
+        let last = summary.last()?;
+        Some(Self {
+            year: last.year,
+            final_amount: last.total_amount,
+            total_contribution: last.total_contribution,
+            total_interest: last.total_interest,
+            total_return_pct: last.total_return_pct(),
+            crossover_year: crossover_year(summary),
+        })
+    }
+}
+
+/// Returns the JSON Schema of the default JSON output of `to_json`: an array of `YearlySummary`
+/// objects.
+///
+/// Consumers of the output can validate it against the schema. Every field is required, so a
+/// renamed or removed field fails the validation.
+///
+/// # Example
+///
+/// ```
+/// use cic::output::summary_schema;
+///
+/// let schema = summary_schema();
+/// assert_eq!(schema["type"], "array");
+/// ```
+pub fn summary_schema() -> serde_json::Value {
+    schemars::schema_for!(Vec<YearlySummary>).to_value()
+}
+
+/// Renders the summary as pretty-printed JSON.
+///
+/// By default, the output is the bare summary so that it stays deterministic, and an array has no
+/// room for the `crossover_year`. With `timestamp`, `input` or `crossover`, the summary is wrapped
+/// in an object as `{"generated_at": ..., "input": ..., "summary": ...}`, followed by a
+/// `crossover_year` footer once the interest of a year exceeds its contribution.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `options` - The `JsonOptions` controlling what is rendered.
+///
+/// # Returns
+///
+/// Returns the JSON as a `String`. With `final_only`, a single `FinalResult` object is rendered, or
+/// `null` if the summary is empty.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::{to_json, JsonOptions};
+///
+/// let summary = Investment::default().yearly_summary().unwrap();
+/// let options = JsonOptions { final_only: true, ..Default::default() };
+/// let json = to_json(&summary, &options).unwrap();
+/// assert!(json.contains("final_amount"));
+/// ```
+pub fn to_json(summary: &[YearlySummary], options: &JsonOptions) -> serde_json::Result<String> {
+    if options.final_only {
+        // The final result carries the crossover year itself.
+        return render(FinalResult::from_summary(summary), None, options);
+    }
+    render(summary, crossover_year(summary), options)
+}
+
+fn render<T: Serialize>(
+    summary: T,
+    crossover_year: Option<i32>,
+    options: &JsonOptions,
+) -> serde_json::Result<String> {
+    if !options.timestamp
+        && options.input.is_none()
+        && options.options.is_none()
+        && options.metrics.is_none()
+        && !options.crossover
+    {
+        return to_string_in_case(&summary, options.case);
+    }
+    let report = Report {
+        generated_at: options
+            .timestamp
+            .then(|| Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+        input: options.input.clone(),
+        crossover_year,
+        options: options.options.clone(),
+        metrics: options.metrics.clone(),
+        summary,
+    };
+    to_string_in_case(&report, options.case)
+}
+
+/// Renders pretty-printed JSON with its keys written in the given case.
+fn to_string_in_case<T: Serialize>(value: &T, case: JsonCase) -> serde_json::Result<String> {
+    match case {
+        JsonCase::Snake => serde_json::to_string_pretty(value),
+        JsonCase::Camel => {
+            serde_json::to_string_pretty(&camel_case_keys(serde_json::to_value(value)?))
+        }
+    }
+}
+
+/// Renames the keys of every object in the value from snake_case to camelCase.
+fn camel_case_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (to_camel_case(&key), camel_case_keys(value)))
+            .collect(),
+        serde_json::Value::Array(values) => values.into_iter().map(camel_case_keys).collect(),
+        value => value,
+    }
+}
+
+/// Converts a snake_case name to camelCase, e.g. `real_total_amount` to `realTotalAmount`.
+fn to_camel_case(name: &str) -> String {
+    let mut words = name.split('_');
+    let mut camel = words.next().unwrap_or_default().to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+/// Represents a JSON report written with an echoed input, read back for verification.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EchoedReport {
+    /// The input the summary was calculated from.
+    pub input: Investment,
+    /// The summary of every year.
+    pub summary: Vec<YearlySummary>,
+}
+
+/// Parses a JSON report that was written with `--echo-input`.
+///
+/// # Errors
+///
+/// Returns a `serde_json::Error` if the JSON has no `input`, or if `summary` is not the summary of
+/// every year (e.g. because the report was written with `--final-only`).
+pub fn parse_echoed_report(json: &str) -> serde_json::Result<EchoedReport> {
+    serde_json::from_str(json)
+}
+
+/// The keys of every record of the flat JSON output, in order. They are also the CSV columns.
+pub const FLAT_JSON_KEYS: [&str; 11] = [
+    "year",
+    "principal",
+    "annual_contribution",
+    "total_contribution",
+    "annual_match",
+    "total_match",
+    "annual_interest",
+    "total_interest",
+    "total_amount",
+    "real_total_amount",
+    "real_growth_flag",
+];
+
+/// A record of the flat JSON output, one per year.
+///
+/// This is the stable schema for ETL and BI tools: the keys are `FLAT_JSON_KEYS`, every value is
+/// a number except the boolean `real_growth_flag`, and nothing is nested. Unlike the regular JSON,
+/// it does not follow new fields of `YearlySummary`; a change to it is a breaking change.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlatRecord {
+    /// The year, starting at 1.
+    pub year: i32,
+    /// The principal.
+    pub principal: f64,
+    /// The contribution made during the year.
+    pub annual_contribution: f64,
+    /// The contributions made up to the end of the year.
+    pub total_contribution: f64,
+    /// The employer match paid during the year.
+    pub annual_match: f64,
+    /// The employer match paid up to the end of the year.
+    pub total_match: f64,
+    /// The interest earned during the year.
+    pub annual_interest: f64,
+    /// The interest earned up to the end of the year.
+    pub total_interest: f64,
+    /// The balance at the end of the year.
+    pub total_amount: f64,
+    /// The balance at the end of the year in today's money.
+    pub real_total_amount: f64,
+    /// Whether the balance grew faster than inflation.
+    pub real_growth_flag: bool,
+}
+
+impl From<&YearlySummary> for FlatRecord {
+    fn from(s: &YearlySummary) -> Self {
+        Self {
+            year: s.year,
+            principal: s.principal,
+            annual_contribution: s.annual_contribution,
+            total_contribution: s.total_contribution,
+            annual_match: s.annual_match,
+            total_match: s.total_match,
+            annual_interest: s.annual_interest,
+            total_interest: s.total_interest,
+            total_amount: s.total_amount,
+            real_total_amount: s.real_total_amount,
+            real_growth_flag: s.real_growth_flag,
+        }
+    }
+}
+
+/// Renders the summary as a flat JSON array of `FlatRecord`s, one per year.
+///
+/// There is no metadata such as a timestamp, and an empty summary renders as `[]`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::to_flat_json;
+///
+/// let summary = Investment { years: 2, ..Default::default() }.yearly_summary().unwrap();
+/// let json = to_flat_json(&summary).unwrap();
+/// assert!(json.starts_with("[{\"year\":1,\"principal\":0.0,"));
+/// ```
+pub fn to_flat_json(summary: &[YearlySummary]) -> serde_json::Result<String> {
+    let records: Vec<FlatRecord> = summary.iter().map(FlatRecord::from).collect();
+    serde_json::to_string(&records)
+}
+
+/// The top-level table of the TOML output. TOML has no top-level arrays, so the years are an
+/// array of tables named `year`.
+#[derive(Serialize)]
+struct TomlSummary<'a> {
+    year: &'a [YearlySummary],
+}
+
+/// Renders the summary as TOML, with one `[[year]]` table per year.
+///
+/// The keys of every table are the JSON field names. An empty summary renders as `year = []`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::to_toml;
+///
+/// let summary = Investment { years: 2, ..Default::default() }.yearly_summary().unwrap();
+/// let toml = to_toml(&summary).unwrap();
+/// assert!(toml.starts_with("[[year]]\nyear = 1\n"));
+/// assert_eq!(toml.matches("[[year]]").count(), 2);
+/// ```
+pub fn to_toml(summary: &[YearlySummary]) -> Result<String, toml::ser::Error> {
+    toml::to_string(&TomlSummary { year: summary })
+}
+
+/// The header row of the CSV output.
+const CSV_HEADER: &str = "year,principal,annual_contribution,total_contribution,annual_match,\
+total_match,annual_interest,total_interest,total_amount,real_total_amount,real_growth_flag";
+
+/// Renders the summary as CSV with a header row and one row per year.
+///
+/// The columns match the JSON field names and the values keep their full precision. An empty
+/// summary renders as the header row only.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::to_csv;
+///
+/// let summary = Investment { years: 2, ..Default::default() }.yearly_summary().unwrap();
+/// assert_eq!(to_csv(&summary).lines().count(), 3);
+/// ```
+pub fn to_csv(summary: &[YearlySummary]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for s in summary {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            s.year,
+            s.principal,
+            s.annual_contribution,
+            s.total_contribution,
+            s.annual_match,
+            s.total_match,
+            s.annual_interest,
+            s.total_interest,
+            s.total_amount,
+            s.real_total_amount,
+            s.real_growth_flag
+        ));
+    }
+    csv
+}
+
+/// Renders the summary as a human-readable table of the yearly totals.
+///
+/// An empty summary (e.g. `years = 0`) renders as a message saying there is nothing to show, so
+/// the output is never confusingly blank. The row of the `crossover_year` is marked with `*` and
+/// explained below the table.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::to_table;
+///
+/// let summary = Investment { years: 2, ..Default::default() }.yearly_summary().unwrap();
+/// assert!(to_table(&summary).starts_with("Year"));
+/// ```
+pub fn to_table(summary: &[YearlySummary]) -> String {
+    to_table_with(summary, format_currency)
+}
+
+/// Renders the summary as a table like `to_table`, formatting the amounts with `currency`, e.g.
+/// `humanize`.
+pub fn to_table_with(summary: &[YearlySummary], currency: fn(f64) -> String) -> String {
+    if summary.is_empty() {
+        return String::from("No years to show: the investment runs for 0 years\n");
+    }
+    let mut table = format!(
+        "{:>4}  {:>18}  {:>18}  {:>18}\n",
+        "Year", "Contribution", "Interest", "Total"
+    );
+    let crossover = crossover_year(summary);
+    for s in summary {
+        let marker = if Some(s.year) == crossover { " *" } else { "" };
+        table.push_str(&format!(
+            "{:>4}  {:>18}  {:>18}  {:>18}{}\n",
+            s.year,
+            currency(s.total_contribution),
+            currency(s.total_interest),
+            currency(s.total_amount),
+            marker
+        ));
+    }
+    if let Some(year) = crossover {
+        table.push_str(&format!(
+            "* Year {} is the first in which the interest exceeds the contribution\n",
+            year
+        ));
+    }
+    table
+}
+
+/// The block characters of a sparkline, from the lowest to the highest value.
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Renders the total amounts as a one-line sparkline, one block character per year.
+///
+/// The lowest total maps to `▁` and the highest to `█`. A flat series renders as `▁` only.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::sparkline;
+///
+/// let summary = Investment { principal: 1000.0, years: 8, ..Default::default() }.yearly_summary().unwrap();
+/// let line = sparkline(&summary);
+/// assert!(line.starts_with('▁') && line.ends_with('█'));
+/// ```
+pub fn sparkline(summary: &[YearlySummary]) -> String {
+    let totals = summary.iter().map(|s| s.total_amount);
+    let min = totals.clone().fold(f64::INFINITY, f64::min);
+    let max = totals.clone().fold(f64::NEG_INFINITY, f64::max);
+    let top = (SPARK_BLOCKS.len() - 1) as f64;
+    totals
+        .map(|total| {
+            let level = if max > min {
+                ((total - min) / (max - min) * top).round() as usize
+            } else {
+                0
+            };
+            SPARK_BLOCKS[level.min(SPARK_BLOCKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Renders the final year as a single status line, e.g.
+/// `After 3y: $4,941 (contributed $3,600, interest $341, CAGR 5%)`.
+///
+/// The contributions exclude the principal. The CAGR of the investment is shown as `n/a` if
+/// nothing was invested, see `cagr`.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::oneline;
+///
+/// let investment = Investment { principal: 1000.0, contribution: 100.0, years: 3, ..Default::default() };
+/// let summary = investment.yearly_summary().unwrap();
+/// assert_eq!(oneline(&summary, &investment), "After 3y: $4,941 (contributed $3,600, interest $341, CAGR 5%)");
+/// ```
+pub fn oneline(summary: &[YearlySummary], investment: &Investment) -> String {
+    oneline_with(summary, investment, format_currency)
+}
+
+/// Renders the final year as a status line like `oneline`, formatting the amounts with
+/// `currency`, e.g. `humanize`.
+pub fn oneline_with(
+    summary: &[YearlySummary],
+    investment: &Investment,
+    currency: fn(f64) -> String,
+) -> String {
+    let Some(last) = summary.last() else {
+        return "After 0y: nothing invested".to_string();
+    };
+    let cagr = cagr(investment).map_or("n/a".to_string(), |cagr| format!("{:.0}%", cagr * 100.0));
+    format!(
+        "After {}y: {} (contributed {}, interest {}, CAGR {})",
+        last.year,
+        currency(last.total_amount),
+        currency(last.total_contribution),
+        currency(last.total_interest),
+        cagr
+    )
+}
+
+/// The name of the worksheet in the xlsx output.
+pub const XLSX_SHEET_NAME: &str = "Summary";
+
+/// The header row of the xlsx output.
+const XLSX_HEADER: [&str; 8] = [
+    "Year",
+    "Principal",
+    "Annual Contribution",
+    "Total Contribution",
+    "Annual Match",
+    "Annual Interest",
+    "Total Interest",
+    "Total Amount",
+];
+
+/// Renders the summary as an Excel workbook with a single `Summary` worksheet.
+///
+/// The worksheet has a bold header row, one row per year with the amounts formatted as currency,
+/// and a bold `Total` row with the sums of the annual columns and the final total amount.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::to_xlsx;
+///
+/// let summary = Investment { years: 2, ..Default::default() }.yearly_summary().unwrap();
+/// assert!(to_xlsx(&summary).unwrap().starts_with(b"PK"));
+/// ```
+pub fn to_xlsx(summary: &[YearlySummary]) -> Result<Vec<u8>, CicError> {
+    write_xlsx(summary).map_err(|e| CicError::Output(e.to_string()))
+}
+
+fn write_xlsx(summary: &[YearlySummary]) -> Result<Vec<u8>, XlsxError> {
+    let header = Format::new().set_bold();
+    let currency = Format::new().set_num_format("$#,##0.00");
+    let total_currency = Format::new().set_bold().set_num_format("$#,##0.00");
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(XLSX_SHEET_NAME)?;
+    sheet.set_freeze_panes(1, 0)?;
+    for (col, title) in (0..).zip(XLSX_HEADER) {
+        sheet.write_string_with_format(0, col, title, &header)?;
+        sheet.set_column_width(col, if col == 0 { 8 } else { 20 })?;
+    }
+
+    let mut row = 1;
+    for s in summary {
+        sheet.write_number(row, 0, s.year)?;
+        let amounts = [
+            s.principal,
+            s.annual_contribution,
+            s.total_contribution,
+            s.annual_match,
+            s.annual_interest,
+            s.total_interest,
+            s.total_amount,
+        ];
+        for (col, amount) in (1..).zip(amounts) {
+            sheet.write_number_with_format(row, col, amount, &currency)?;
+        }
+        row += 1;
+    }
+
+    let sum = |f: fn(&YearlySummary) -> f64| summary.iter().map(f).sum::<f64>();
+    let last = summary.last().cloned().unwrap_or_default();
+    sheet.write_string_with_format(row, 0, "Total", &header)?;
+    let totals = [
+        (1, last.principal),
+        (2, sum(|s| s.annual_contribution)),
+        (4, sum(|s| s.annual_match)),
+        (5, sum(|s| s.annual_interest)),
+        (7, last.total_amount),
+    ];
+    for (col, amount) in totals {
+        sheet.write_number_with_format(row, col, amount, &total_currency)?;
+    }
+
+    workbook.save_to_buffer()
+}
+
+/// Renders the summary in the given format.
+///
+/// This is the single place that decides what is written to stdout, so every format handles the
+/// edge cases the same way. In particular, an empty summary renders as `[]` in JSON (`null` with
+/// `final_only`) and in flat JSON, as `year = []` in TOML, as the header row in CSV, as an informative message in a table,
+/// as a worksheet with the header and a zero `Total` row in xlsx, and as an error in PNG and SVG.
+/// The chart is rendered with the default `PlotOptions`; use `render_png` or `render_svg` to
+/// customize it.
+///
+/// # Arguments
+///
+/// * `summary` - A slice of `YearlySummary` structs representing the investment's progress over time.
+/// * `format` - The output format.
+/// * `options` - The `JsonOptions`. `final_only` applies to every format, `timestamp` only to JSON.
+///
+/// # Returns
+///
+/// Returns the rendered bytes, or a `CicError::Output` if serialization fails. Returns a
+/// `CicError::Calculation` if an amount overflowed, see `check_finite`.
+pub fn format_summary(
+    summary: &[YearlySummary],
+    format: OutputFormat,
+    options: &JsonOptions,
+) -> Result<Vec<u8>, CicError> {
+    check_finite(summary)?;
+    let rows = if options.final_only {
+        &summary[summary.len().saturating_sub(1)..]
+    } else {
+        summary
+    };
+    match format {
+        OutputFormat::Json => to_json(summary, options)
+            .map(|json| (json + "\n").into_bytes())
+            .map_err(|e| CicError::Output(format!("Failed to serialize to JSON: {}", e))),
+        OutputFormat::FlatJson => to_flat_json(rows)
+            .map(|json| (json + "\n").into_bytes())
+            .map_err(|e| CicError::Output(format!("Failed to serialize to JSON: {}", e))),
+        OutputFormat::Toml => to_toml(rows)
+            .map(String::into_bytes)
+            .map_err(|e| CicError::Output(format!("Failed to serialize to TOML: {}", e))),
+        OutputFormat::Csv => Ok(to_csv(rows).into_bytes()),
+        OutputFormat::Table => {
+            let currency = if options.human {
+                humanize
+            } else {
+                format_currency
+            };
+            Ok(to_table_with(rows, currency).into_bytes())
+        }
+        OutputFormat::Xlsx => to_xlsx(rows),
+        OutputFormat::Png => render_png(rows, &PlotOptions::default()),
+        OutputFormat::Svg => render_svg(rows, &PlotOptions::default()).map(String::into_bytes),
+    }
+}
+
+/// An output file requested with `--emit`: the summary rendered in `format` and written to `path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Emit {
+    /// The format the summary is rendered in.
+    pub format: OutputFormat,
+    /// The path of the file.
+    pub path: std::path::PathBuf,
+}
+
+impl std::str::FromStr for Emit {
+    type Err = String;
+
+    /// Parses `FORMAT:PATH`, e.g. `svg:chart.svg`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid emit: {} (expected FORMAT:PATH)", s))?;
+        if path.is_empty() {
+            return Err(format!("Invalid emit: {} (the path is empty)", s));
+        }
+        Ok(Self {
+            format: format.parse()?,
+            path: path.into(),
+        })
+    }
+}
+
+/// Checks that every amount of the summary is a finite number.
+///
+/// A pathological input, e.g. a rate of `1e300`, overflows the amounts to infinity. JSON has no
+/// infinity, so `serde_json` would silently write `null`; this check reports the overflow instead,
+/// before any output is written.
+///
+/// # Errors
+///
+/// Returns a `CicError::Calculation` naming the first year and field that is not finite.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::check_finite;
+///
+/// let investment = Investment { principal: 1.0, rate: 1e300, years: 3, ..Default::default() };
+/// let summary = investment.yearly_summary().unwrap();
+/// assert!(check_finite(&summary).is_err());
+/// ```
+pub fn check_finite(summary: &[YearlySummary]) -> Result<(), CicError> {
+    for s in summary {
+        let amounts = [
+            ("principal", s.principal),
+            ("annual_contribution", s.annual_contribution),
+            ("total_contribution", s.total_contribution),
+            ("annual_match", s.annual_match),
+            ("total_match", s.total_match),
+            ("annual_interest", s.annual_interest),
+            ("total_interest", s.total_interest),
+            ("total_amount", s.total_amount),
+            ("real_total_amount", s.real_total_amount),
+        ];
+        if let Some((field, value)) = amounts.into_iter().find(|(_, value)| !value.is_finite()) {
+            return Err(CicError::Calculation(format!(
+                "The amounts overflowed: the {} of year {} is {}. Lower the rate or the number of years",
+                field, s.year, value
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Writes the rendered output to a file, creating its parent directories if needed.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file. An existing file is overwritten.
+/// * `contents` - The rendered bytes, e.g. from `format_summary`.
+///
+/// # Returns
+///
+/// Returns a `CicError::Io` if a directory or the file cannot be created.
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), CicError> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// The table `write_sqlite` stores the yearly summaries in.
+#[cfg(feature = "sqlite")]
+pub const SQLITE_TABLE: &str = "yearly_summary";
+
+/// Appends the summary to the `yearly_summary` table of a SQLite database, e.g. to track the
+/// results of several runs over time.
+///
+/// The database and the table are created if needed. Every call stores its rows under a new
+/// `run_id`, one more than the highest one in the table, so the runs coexist and can be compared
+/// with SQL. The other columns are the fields of `YearlySummary`, with `real_growth_flag` as 0 or 1.
+///
+/// # Arguments
+///
+/// * `summary` - The summary to store.
+/// * `path` - The path of the database file.
+///
+/// # Returns
+///
+/// Returns the `run_id` of the stored rows, or a `CicError::Output` if the database cannot be
+/// opened or written.
+///
+/// # Example
+///
+/// ```
+/// use cic::calculations::Investment;
+/// use cic::output::write_sqlite;
+///
+/// let path = std::env::temp_dir().join("cic_doctest_write_sqlite.db");
+/// # let _ = std::fs::remove_file(&path);
+/// let summary = Investment { years: 3, ..Default::default() }.yearly_summary().unwrap();
+/// assert_eq!(write_sqlite(&summary, &path).unwrap(), 1);
+/// assert_eq!(write_sqlite(&summary, &path).unwrap(), 2);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[cfg(feature = "sqlite")]
+pub fn write_sqlite(summary: &[YearlySummary], path: &Path) -> Result<i64, CicError> {
+    insert_sqlite(summary, path).map_err(|e| CicError::Output(e.to_string()))
+}
+
+#[cfg(feature = "sqlite")]
+fn insert_sqlite(summary: &[YearlySummary], path: &Path) -> rusqlite::Result<i64> {
+    let mut connection = rusqlite::Connection::open(path)?;
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {SQLITE_TABLE} (
+                run_id INTEGER NOT NULL,
+                year INTEGER NOT NULL,
+                principal REAL NOT NULL,
+                annual_contribution REAL NOT NULL,
+                total_contribution REAL NOT NULL,
+                annual_match REAL NOT NULL,
+                total_match REAL NOT NULL,
+                annual_interest REAL NOT NULL,
+                total_interest REAL NOT NULL,
+                total_amount REAL NOT NULL,
+                real_total_amount REAL NOT NULL,
+                real_growth_flag INTEGER NOT NULL,
+                PRIMARY KEY (run_id, year)
+            )"
+        ),
+        [],
+    )?;
+    let run_id: i64 = transaction.query_row(
+        &format!("SELECT COALESCE(MAX(run_id), 0) + 1 FROM {SQLITE_TABLE}"),
+        [],
+        |row| row.get(0),
+    )?;
+    {
+        let mut insert = transaction.prepare(&format!(
+            "INSERT INTO {SQLITE_TABLE} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+        ))?;
+        for s in summary {
+            insert.execute(rusqlite::params![
+                run_id,
+                s.year,
+                s.principal,
+                s.annual_contribution,
+                s.total_contribution,
+                s.annual_match,
+                s.total_match,
+                s.annual_interest,
+                s.total_interest,
+                s.total_amount,
+                s.real_total_amount,
+                s.real_growth_flag,
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(run_id)
+}
+
+/// Encodes PNG bytes as a `data:image/png;base64,...` URI.
+///
+/// The URI can be pasted into a browser or used as an image source in Markdown or HTML.
+///
+/// # Arguments
+///
+/// * `png` - The PNG-encoded image, e.g. from `render_png`.
+///
+/// # Example
+///
+/// ```
+/// use cic::output::data_uri;
+///
+/// assert_eq!(data_uri(b"png"), "data:image/png;base64,cG5n");
+/// ```
+pub fn data_uri(png: &[u8]) -> String {
+    format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculations::{render_png, Investment, PlotOptions};
+
+    #[test]
+    fn test_to_json_final_only() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        let options = JsonOptions {
+            final_only: true,
+            ..Default::default()
+        };
+        let json = to_json(&summary, &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(value.is_object());
+        assert_eq!(value["year"], 3);
+        assert_eq!(value["total_contribution"], 3600.0);
+        assert!((value["final_amount"].as_f64().unwrap() - 4940.625).abs() < 1e-2);
+        assert!((value["total_interest"].as_f64().unwrap() - 340.625).abs() < 1e-2);
+        assert!((value["total_return_pct"].as_f64().unwrap() - 7.4049).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_check_finite() {
+        let investment = Investment {
+            principal: 1.0,
+            rate: 1e300,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        assert!(!summary[1].total_amount.is_finite());
+
+        let error =
+            format_summary(&summary, OutputFormat::Json, &JsonOptions::default()).unwrap_err();
+        assert!(matches!(error, CicError::Calculation(_)));
+        assert_eq!(
+            error.to_string(),
+            "Calculation failed: The amounts overflowed: the annual_interest of year 2 is NaN. \
+             Lower the rate or the number of years"
+        );
+        assert!(check_finite(&summary[..1]).is_ok());
+    }
+
+    #[test]
+    fn test_json_matches_summary_schema() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&summary, &JsonOptions::default()).unwrap()).unwrap();
+        let validator = jsonschema::validator_for(&summary_schema()).unwrap();
+        assert!(validator.is_valid(&json));
+        assert!(validator.is_valid(&serde_json::json!([])));
+
+        // A renamed field is missing under its old name.
+        let mut renamed = json.clone();
+        let year = renamed[0].as_object_mut().unwrap();
+        let total_amount = year.remove("total_amount").unwrap();
+        year.insert("total".into(), total_amount);
+        assert!(!validator.is_valid(&renamed));
+
+        let mut retyped = json.clone();
+        retyped[1]["real_growth_flag"] = serde_json::json!("yes");
+        assert!(!validator.is_valid(&retyped));
+    }
+
+    #[test]
+    fn test_flat_json_schema() {
+        let summary = Investment {
+            principal: 1000.0,
+            years: 3,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        let json = to_flat_json(&summary).unwrap();
+        let records: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(&json).unwrap();
+
+        // The schema is fixed: changing it breaks the pipelines that read it.
+        let expected = [
+            "year",
+            "principal",
+            "annual_contribution",
+            "total_contribution",
+            "annual_match",
+            "total_match",
+            "annual_interest",
+            "total_interest",
+            "total_amount",
+            "real_total_amount",
+            "real_growth_flag",
+        ];
+        assert_eq!(FLAT_JSON_KEYS, expected);
+        assert_eq!(records.len(), 3);
+        for record in &records {
+            let mut keys: Vec<&str> = record.keys().map(String::as_str).collect();
+            let mut expected = expected.to_vec();
+            keys.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(keys, expected);
+            assert!(record
+                .values()
+                .all(|value| value.is_number() || value.is_boolean()));
+        }
+        assert_eq!(CSV_HEADER, FLAT_JSON_KEYS.join(","));
+        assert_eq!(to_flat_json(&[]).unwrap(), "[]");
+
+        let options = JsonOptions {
+            final_only: true,
+            ..Default::default()
+        };
+        let output = format_summary(&summary, OutputFormat::FlatJson, &options).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 1);
+        assert_eq!(value[0]["year"], 3);
+    }
+
+    #[test]
+    fn test_to_toml_parses_back() {
+        #[derive(Deserialize)]
+        struct Parsed {
+            year: Vec<YearlySummary>,
+        }
+
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let toml = to_toml(&summary).unwrap();
+        let parsed: Parsed = toml::from_str(&toml).unwrap();
+
+        assert_eq!(parsed.year.len(), summary.len());
+        for (parsed, expected) in parsed.year.iter().zip(&summary) {
+            assert_eq!(parsed.year, expected.year);
+            assert_eq!(parsed.total_contribution, expected.total_contribution);
+            assert_eq!(parsed.total_amount, expected.total_amount);
+            assert_eq!(parsed.real_growth_flag, expected.real_growth_flag);
+        }
+        let parsed: Parsed = toml::from_str(&to_toml(&[]).unwrap()).unwrap();
+        assert!(parsed.year.is_empty());
+    }
+
+    #[test]
+    fn test_to_json_all_years() {
+        let summary = Investment::default().yearly_summary().unwrap();
+
+        let json = to_json(&summary, &JsonOptions::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), summary.len());
+    }
+
+    #[test]
+    fn test_to_json_timestamp() {
+        let summary = Investment::default().yearly_summary().unwrap();
+
+        let json = to_json(&summary, &JsonOptions::default()).unwrap();
+        assert!(!json.contains("generated_at"));
+
+        let options = JsonOptions {
+            timestamp: true,
+            ..Default::default()
+        };
+        let json = to_json(&summary, &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let generated_at = value["generated_at"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(generated_at).is_ok());
+        assert!(generated_at.ends_with('Z'));
+        assert_eq!(value["summary"].as_array().unwrap().len(), summary.len());
+    }
+
+    #[test]
+    fn test_to_json_crossover_year() {
+        let summary = Investment {
+            contribution: 100.0,
+            rate: 10.0,
+            years: 10,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+
+        // The default bare array has no room for it.
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&summary, &JsonOptions::default()).unwrap()).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 10);
+
+        for options in [
+            JsonOptions {
+                timestamp: true,
+                ..Default::default()
+            },
+            JsonOptions {
+                crossover: true,
+                ..Default::default()
+            },
+        ] {
+            let value: serde_json::Value =
+                serde_json::from_str(&to_json(&summary, &options).unwrap()).unwrap();
+            assert_eq!(value["crossover_year"], 9);
+            assert_eq!(value["summary"].as_array().unwrap().len(), 10);
+        }
+
+        let options = JsonOptions {
+            final_only: true,
+            ..Default::default()
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&summary, &options).unwrap()).unwrap();
+        assert_eq!(value["crossover_year"], 9);
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&summary[..8], &options).unwrap()).unwrap();
+        assert_eq!(value["crossover_year"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_to_json_options() {
+        let investment = Investment {
+            compound_frequency: CompoundFrequency::Monthly,
+            fee: 0.5,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        let options = JsonOptions {
+            options: Some(CalculationOptions::from(&investment)),
+            ..Default::default()
+        };
+        let json = to_json(&summary, &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["options"]["compound_frequency"], "monthly");
+        assert_eq!(value["options"]["interest_mode"], "compound");
+        assert_eq!(value["options"]["fee"], 0.5);
+        assert_eq!(value["options"]["match_cap"], serde_json::Value::Null);
+        assert_eq!(value["summary"].as_array().unwrap().len(), summary.len());
+        assert!(value.get("input").is_none());
+    }
+
+    #[test]
+    fn test_to_json_case() {
+        let investment = Investment {
+            principal: 1000.0,
+            years: 2,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        let snake = to_json(&summary, &JsonOptions::default()).unwrap();
+        assert!(snake.contains("\"total_amount\""));
+        assert!(!snake.contains("totalAmount"));
+
+        let camel = JsonOptions {
+            case: JsonCase::Camel,
+            ..Default::default()
+        };
+        let json = to_json(&summary, &camel).unwrap();
+        assert!(json.contains("\"totalAmount\""));
+        assert!(!json.contains("total_amount"));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[1]["realTotalAmount"], summary[1].real_total_amount);
+        assert_eq!(value[1]["realGrowthFlag"], summary[1].real_growth_flag);
+
+        // The keys of nested objects are renamed as well.
+        let report = JsonOptions {
+            options: Some(CalculationOptions::from(&investment)),
+            final_only: true,
+            ..camel
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&summary, &report).unwrap()).unwrap();
+        assert_eq!(value["options"]["compoundFrequency"], "annual");
+        assert_eq!(value["summary"]["finalAmount"], summary[1].total_amount);
+        assert_eq!(to_camel_case("year"), "year");
+    }
+
+    #[test]
+    fn test_to_json_metrics() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 12.0,
+            years: 10,
+            compound_frequency: CompoundFrequency::Monthly,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let metrics = Metrics::new(&summary, &investment);
+
+        let last = summary.last().unwrap();
+        assert_eq!(metrics.final_amount, last.total_amount);
+        assert_eq!(metrics.total_contributed, last.total_contribution);
+        assert_eq!(metrics.total_interest, last.total_interest);
+        assert_eq!(metrics.total_return_pct, last.total_return_pct());
+        assert_eq!(metrics.cagr, Some(cagr(&investment).unwrap() * 100.0));
+        assert!((metrics.cagr.unwrap() - metrics.apy).abs() < 1e-6);
+        assert!((metrics.apy - 12.6825).abs() < 1e-4);
+
+        let options = JsonOptions {
+            metrics: Some(metrics),
+            ..Default::default()
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&to_json(&summary, &options).unwrap()).unwrap();
+        let json = &value["metrics"];
+        assert!((json["final_amount"].as_f64().unwrap() - last.total_amount).abs() < 1e-9);
+        assert_eq!(json["total_contributed"], 12000.0);
+        assert_eq!(value["summary"].as_array().unwrap().len(), 10);
+
+        let empty = Metrics::new(&[], &investment);
+        assert_eq!((empty.final_amount, empty.cagr), (0.0, None));
+    }
+
+    #[test]
+    fn test_data_uri_decodes_to_png() {
+        let summary = Investment::default().yearly_summary().unwrap();
+        let png = render_png(&summary, &PlotOptions::default()).unwrap();
+
+        let uri = data_uri(&png);
+        let encoded = uri.strip_prefix("data:image/png;base64,").unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        assert_eq!(decoded, png);
+        assert!(decoded.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn test_csv_and_table() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 3,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+
+        let csv = to_csv(&summary);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("year,principal,"));
+        assert_eq!(
+            lines[3],
+            "3,1000,1200,3600,0,0,178.125,340.625,4940.625,4940.625,true"
+        );
+
+        let table = to_table(&summary);
+        assert_eq!(table.lines().count(), 4);
+        assert!(table.lines().last().unwrap().ends_with("$4,941"));
+
+        let crossing = Investment {
+            contribution: 100.0,
+            rate: 10.0,
+            years: 10,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        let table = to_table(&crossing);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[9].starts_with("   9") && lines[9].ends_with(" *"));
+        assert!(!lines[10].ends_with(" *"));
+        assert_eq!(
+            lines[11],
+            "* Year 9 is the first in which the interest exceeds the contribution"
+        );
+
+        let options = JsonOptions {
+            final_only: true,
+            ..Default::default()
+        };
+        let csv = format_summary(&summary, OutputFormat::Csv, &options).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_human_amounts() {
+        let investment = Investment {
+            principal: 1_000_000.0,
+            contribution: 10_000.0,
+            rate: 10.0,
+            years: 2,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let options = JsonOptions {
+            human: true,
+            ..Default::default()
+        };
+        let table =
+            String::from_utf8(format_summary(&summary, OutputFormat::Table, &options).unwrap())
+                .unwrap();
+        assert!(table.contains("$240.0k"), "{}", table);
+        assert!(table.contains("$1.5M"), "{}", table);
+        assert_eq!(
+            oneline_with(&summary, &investment, humanize),
+            "After 2y: $1.5M (contributed $240.0k, interest $222.0k, CAGR 10%)"
+        );
+
+        // The other formats keep the full precision.
+        let csv = String::from_utf8(format_summary(&summary, OutputFormat::Csv, &options).unwrap())
+            .unwrap();
+        assert_eq!(csv, to_csv(&summary));
+    }
+
+    #[test]
+    fn test_oneline() {
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            rate: 5.0,
+            years: 10,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        let line = oneline(&summary, &investment);
+        let last = summary.last().unwrap();
+        assert!(line.starts_with("After 10y: "), "{}", line);
+        assert!(
+            line.contains(&format_currency(last.total_amount)),
+            "{}",
+            line
+        );
+        assert!(line.ends_with("CAGR 5%)"), "{}", line);
+
+        let nothing = Investment {
+            contribution: 0.0,
+            years: 2,
+            ..Default::default()
+        };
+        let summary = nothing.yearly_summary().unwrap();
+        assert!(oneline(&summary, &nothing).ends_with("CAGR n/a)"));
+        assert_eq!(oneline(&[], &nothing), "After 0y: nothing invested");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        let summary = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 20,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        let line = sparkline(&summary);
+        assert_eq!(line.chars().count(), summary.len());
+        assert!(line.starts_with('▁'));
+        assert!(line.ends_with('█'));
+
+        let flat = Investment {
+            principal: 1000.0,
+            contribution: 0.0,
+            rate: 0.0,
+            years: 5,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        assert_eq!(sparkline(&flat), "▁▁▁▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_xlsx() {
+        let summary = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 3,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        let xlsx = format_summary(&summary, OutputFormat::Xlsx, &JsonOptions::default()).unwrap();
+        assert!(xlsx.starts_with(b"PK"));
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut workbook = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("xl/workbook.xml").unwrap(),
+            &mut workbook,
+        )
+        .unwrap();
+        assert!(workbook.contains(&format!("name=\"{}\"", XLSX_SHEET_NAME)));
+        let mut strings = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("xl/sharedStrings.xml").unwrap(),
+            &mut strings,
+        )
+        .unwrap();
+        assert!(strings.contains("Annual Interest"));
+        assert!(strings.contains("Total"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_write_sqlite() {
+        let path = std::env::temp_dir().join("cic_test_write_sqlite.db");
+        let _ = std::fs::remove_file(&path);
+        let investment = Investment {
+            principal: 1000.0,
+            contribution: 100.0,
+            years: 7,
+            ..Default::default()
+        };
+        let summary = investment.yearly_summary().unwrap();
+        assert_eq!(write_sqlite(&summary, &path).unwrap(), 1);
+        assert_eq!(write_sqlite(&summary[..3], &path).unwrap(), 2);
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let count = |run_id: i64| -> i64 {
+            connection
+                .query_row(
+                    "SELECT COUNT(*) FROM yearly_summary WHERE run_id = ?1",
+                    [run_id],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert_eq!(count(1), i64::from(investment.years));
+        assert_eq!(count(2), 3);
+        let (year, total_amount, real_growth_flag): (i32, f64, bool) = connection
+            .query_row(
+                "SELECT year, total_amount, real_growth_flag FROM yearly_summary \
+                 WHERE run_id = 1 ORDER BY year DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(year, 7);
+        assert_eq!(total_amount, summary[6].total_amount);
+        assert!(real_growth_flag);
+        drop(connection);
+        std::fs::remove_file(&path).unwrap();
+
+        let directory = std::env::temp_dir();
+        assert!(matches!(
+            write_sqlite(&summary, &directory),
+            Err(CicError::Output(_))
+        ));
+    }
+
+    #[test]
+    fn test_zero_years_in_every_format() {
+        let summary = Investment {
+            years: 0,
+            ..Default::default()
+        }
+        .yearly_summary()
+        .unwrap();
+        let options = JsonOptions::default();
+
+        let render = |format| {
+            String::from_utf8(format_summary(&summary, format, &options).unwrap()).unwrap()
+        };
+        assert_eq!(render(OutputFormat::Json), "[]\n");
+        assert_eq!(render(OutputFormat::Csv), format!("{}\n", CSV_HEADER));
+        assert!(render(OutputFormat::Table).contains("0 years"));
+        assert!(format_summary(&summary, OutputFormat::Xlsx, &options).is_ok());
+        assert!(format_summary(&summary, OutputFormat::Png, &options).is_err());
+        assert!(format_summary(&summary, OutputFormat::Svg, &options).is_err());
+        assert!(matches!(
+            render_png(&summary, &PlotOptions::default()),
+            Err(crate::error::CicError::Plot(
+                crate::calculations::PlotError::EmptyData
+            ))
+        ));
+    }
+}
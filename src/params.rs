@@ -0,0 +1,280 @@
+use crate::calculations::{AccountType, CompoundFrequency};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// The prefix of the environment variables that set parameters, e.g. `CIC_RATE` for `rate`.
+pub const ENV_PREFIX: &str = "CIC_";
+
+#[derive(Debug, Deserialize, Serialize)]
+/// Represents the parameters required for calculating an investment.
+///
+/// This struct is used to deserialize JSON payloads sent to the `/compound-interests` endpoint, and
+/// config files holding the defaults of the server.
+///
+/// # Fields
+///
+/// * `principal` - The initial amount of money invested (default: 0.0).
+/// * `contribution` - The monthly contribution added to the investment (default: 1.0).
+/// * `rate` - The annual interest rate as a percentage (default: 5.0).
+/// * `years` - The number of years the money is invested for (default: 5).
+/// * `compound_frequency` - How often interest is compounded, `annual`, `semi-annual` or
+///   `monthly` (default: annual).
+/// * `debt` - Whether a negative principal is a debt being paid down (default: false).
+/// * `inflation` - The annual inflation rate as a percentage, used for the inflation-adjusted
+///   amounts (default: 0.0).
+/// * `fee` - The annual fee as a percentage subtracted from the rate (default: 0.0).
+/// * `tax_rate` - The tax rate as a percentage, applied as described by `account_type`
+///   (default: 0.0).
+/// * `account_type` - How the investment is taxed, `taxable`, `tax-deferred` or `tax-free`
+///   (default: taxable).
+pub struct InvestmentParams {
+    #[serde(default = "default_principal")]
+    pub principal: f64,
+    #[serde(default = "default_contribution")]
+    pub contribution: f64,
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+    #[serde(default = "default_years")]
+    pub years: i32,
+    #[serde(default)]
+    pub compound_frequency: CompoundFrequency,
+    #[serde(default)]
+    pub debt: bool,
+    #[serde(default)]
+    pub inflation: f64,
+    #[serde(default)]
+    pub fee: f64,
+    #[serde(default)]
+    pub tax_rate: f64,
+    #[serde(default)]
+    pub account_type: AccountType,
+}
+
+impl Default for InvestmentParams {
+    fn default() -> Self {
+        Self {
+            principal: default_principal(),
+            contribution: default_contribution(),
+            rate: default_rate(),
+            years: default_years(),
+            compound_frequency: CompoundFrequency::default(),
+            debt: false,
+            inflation: 0.0,
+            fee: 0.0,
+            tax_rate: 0.0,
+            account_type: AccountType::default(),
+        }
+    }
+}
+
+impl InvestmentParams {
+    /// Creates new parameters by applying the given fields over these parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - The fields to override, e.g. the body of a request. Fields that are not present
+    ///   keep their value from `self`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the merged `InvestmentParams`, or a `serde_json::Error` if an override has an invalid type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::params::InvestmentParams;
+    ///
+    /// let defaults = InvestmentParams { rate: 7.0, ..Default::default() };
+    /// let overrides = serde_json::json!({"principal": 1000.0});
+    /// let params = defaults.with_overrides(overrides.as_object().unwrap().clone()).unwrap();
+    /// assert_eq!(params.principal, 1000.0);
+    /// assert_eq!(params.rate, 7.0);
+    /// ```
+    pub fn with_overrides(
+        &self,
+        overrides: serde_json::Map<String, serde_json::Value>,
+    ) -> serde_json::Result<Self> {
+        let mut merged = match serde_json::to_value(self)? {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        };
+        merged.extend(overrides);
+        serde_json::from_value(serde_json::Value::Object(merged))
+    }
+
+    /// Creates new parameters by applying the fields of a URL query string over these parameters.
+    ///
+    /// This is how both the `GET /compound-interests` endpoint and the `--query` CLI option read
+    /// their input, so a scenario can be copied between them.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query string, e.g. `principal=1000&rate=5`. A leading `?` is ignored.
+    ///
+    /// # Returns
+    ///
+    /// Returns the merged `InvestmentParams` together with the keys of the query that are not
+    /// parameters, or an error message if the query is malformed or a value has an invalid type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::params::InvestmentParams;
+    ///
+    /// let (params, unknown) = InvestmentParams::default()
+    ///     .with_query("principal=1000&years=10&color=red")
+    ///     .unwrap();
+    /// assert_eq!(params.principal, 1000.0);
+    /// assert_eq!(params.years, 10);
+    /// assert_eq!(unknown, vec!["color".to_string()]);
+    /// ```
+    pub fn with_query(&self, query: &str) -> Result<(Self, Vec<String>), String> {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(query.trim_start_matches('?')).map_err(|e| e.to_string())?;
+        let fields = match serde_json::to_value(self).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        };
+
+        let mut overrides = serde_json::Map::new();
+        let mut unknown = Vec::new();
+        for (key, value) in pairs {
+            if !fields.contains_key(&key) {
+                unknown.push(key);
+                continue;
+            }
+            let value = parse_value(&key, &value)?;
+            overrides.insert(key, value);
+        }
+
+        let params = self.with_overrides(overrides).map_err(|e| e.to_string())?;
+        Ok((params, unknown))
+    }
+
+    /// Creates new parameters by applying environment variables over these parameters.
+    ///
+    /// Every field can be set by the variable named `ENV_PREFIX` followed by the field name in
+    /// upper case, e.g. `CIC_RATE=7` or `CIC_COMPOUND_FREQUENCY=monthly`.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - Looks up an environment variable, e.g. `|key| std::env::var(key).ok()`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the merged `InvestmentParams`, or an error message if a value has an invalid type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cic::params::InvestmentParams;
+    ///
+    /// let env = |key: &str| (key == "CIC_RATE").then(|| "7".to_string());
+    /// let params = InvestmentParams::default().with_env(env).unwrap();
+    /// assert_eq!(params.rate, 7.0);
+    /// assert_eq!(params.years, 5);
+    /// ```
+    pub fn with_env<F: Fn(&str) -> Option<String>>(&self, env: F) -> Result<Self, String> {
+        let fields = match serde_json::to_value(self).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(map) => map,
+            _ => serde_json::Map::new(),
+        };
+
+        let mut overrides = serde_json::Map::new();
+        for key in fields.keys() {
+            let name = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Some(value) = env(&name) {
+                let value = parse_value(&name, &value)
+                    .map_err(|e| format!("Invalid environment variable: {}", e))?;
+                overrides.insert(key.clone(), value);
+            }
+        }
+        self.with_overrides(overrides)
+            .map_err(|e| format!("Invalid environment variable: {}", e))
+    }
+}
+
+/// Converts a value given as text, e.g. in a query string, to the JSON type it looks like, so
+/// that it deserializes into numeric and boolean fields.
+///
+/// `NaN` and `inf` are rejected with an error naming `key`, since JSON has no such numbers.
+fn parse_value(key: &str, value: &str) -> Result<serde_json::Value, String> {
+    if let Ok(n) = value.parse::<i64>() {
+        Ok(json!(n))
+    } else if let Ok(n) = value.parse::<f64>() {
+        if !n.is_finite() {
+            return Err(format!("{} must be a finite number, got {}", key, value));
+        }
+        Ok(json!(n))
+    } else if let Ok(b) = value.parse::<bool>() {
+        Ok(json!(b))
+    } else {
+        Ok(json!(value))
+    }
+}
+
+fn default_principal() -> f64 {
+    0.0
+}
+
+fn default_contribution() -> f64 {
+    1.0
+}
+
+fn default_rate() -> f64 {
+    5.0
+}
+
+fn default_years() -> i32 {
+    5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_query_parses_params() {
+        let (params, unknown) = InvestmentParams::default()
+            .with_query("principal=1000&contribution=100&rate=5&years=10")
+            .unwrap();
+        assert_eq!(params.principal, 1000.0);
+        assert_eq!(params.contribution, 100.0);
+        assert_eq!(params.rate, 5.0);
+        assert_eq!(params.years, 10);
+        assert!(unknown.is_empty());
+
+        let (params, unknown) = InvestmentParams::default()
+            .with_query("?rate=2.5&foo=bar")
+            .unwrap();
+        assert_eq!(params.rate, 2.5);
+        assert_eq!(params.years, 5);
+        assert_eq!(unknown, vec!["foo".to_string()]);
+
+        let (params, _) = InvestmentParams::default()
+            .with_query("compound_frequency=monthly")
+            .unwrap();
+        assert_eq!(params.compound_frequency, CompoundFrequency::Monthly);
+
+        let (params, _) = InvestmentParams::default()
+            .with_query("account_type=tax-free")
+            .unwrap();
+        assert_eq!(params.account_type, AccountType::TaxFree);
+
+        assert!(InvestmentParams::default().with_query("years=ten").is_err());
+        assert_eq!(
+            InvestmentParams::default()
+                .with_query("rate=NaN")
+                .unwrap_err(),
+            "rate must be a finite number, got NaN"
+        );
+        let env = |key: &str| (key == "CIC_PRINCIPAL").then(|| "inf".to_string());
+        assert_eq!(
+            InvestmentParams::default().with_env(env).unwrap_err(),
+            "Invalid environment variable: CIC_PRINCIPAL must be a finite number, got inf"
+        );
+        assert!(InvestmentParams::default()
+            .with_query("compound_frequency=daily")
+            .is_err());
+    }
+}
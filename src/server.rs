@@ -1,96 +1,1105 @@
-use crate::calculations::Investment;
-use actix_web::{web, App, HttpResponse, HttpServer, Result};
-use serde::Deserialize;
+use crate::calculations::{
+    render_svg, AccountType, CompoundFrequency, Investment, PlotOptions, YearlySummary, MAX_YEARS,
+};
+use crate::error::CicError;
+use crate::output::check_finite;
+use crate::params::InvestmentParams;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, ResponseError, Result};
 use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Describes how the server writes its request logs to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// One human-readable line per request.
+    #[default]
+    Text,
+    /// One JSON object per request, for log aggregators.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid log format: {} (expected text or json)", s)),
+        }
+    }
+}
 
 /// Starts an HTTP server that listens on the specified port.
 ///
 /// # Arguments
 ///
 /// * `port` - The port number on which the server will listen for incoming requests.
+/// * `quiet` - If `true`, the startup banner is not printed.
+/// * `defaults` - The parameters used for the fields a request omits.
+/// * `log_format` - The format of the banner and the request logs written to stderr.
+/// * `rate_limit` - The number of requests a client IP may make per minute, or `None` for no limit.
 ///
 /// # Returns
 ///
-/// Returns a `std::io::Result<()>`. On success, returns `Ok(())`, indicating that the server has started successfully.
-/// On failure, returns an `std::io::Error`.
+/// Returns `Ok(())` once the server has stopped.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function may panic if the server fails to bind to the specified address or port.
-pub async fn start_server(port: u16) -> std::io::Result<()> {
-    println!("Starting server, port: {}", port);
-    println!("POST /compound-interests");
-
-    HttpServer::new(|| {
-        App::new().route("/compound-interests", web::post().to(calculate_investment))
+/// Returns a `CicError::Io` of kind `std::io::ErrorKind::AddrInUse` if the port is already taken.
+/// The port is bound before anything else, so the banner is not printed in that case. Returns a
+/// `CicError::Server` if the request logging cannot be set up, e.g. because another logger is
+/// already installed.
+pub async fn start_server(
+    port: u16,
+    quiet: bool,
+    defaults: InvestmentParams,
+    log_format: LogFormat,
+    rate_limit: Option<NonZeroU32>,
+) -> Result<(), CicError> {
+    let defaults = web::Data::new(defaults);
+    let limiter = rate_limit.map(|per_minute| web::Data::new(RateLimiter::new(per_minute)));
+    let server = HttpServer::new(move || {
+        let mut app = App::new()
+            .wrap(from_fn(limit_rate))
+            .wrap(from_fn(log_request))
+            .app_data(defaults.clone());
+        if let Some(limiter) = &limiter {
+            app = app.app_data(limiter.clone());
+        }
+        app.configure(configure)
     })
-    .bind(("127.0.0.1", port))?
-    .run()
-    .await
+    .bind(("127.0.0.1", port))?;
+
+    tracing::subscriber::set_global_default(log_subscriber(log_format, std::io::stderr))
+        .map_err(|e| CicError::Server(format!("Failed to set up the request logs: {}", e)))?;
+    // A plain banner would break the one-object-per-line output of the JSON logs.
+    match log_format {
+        LogFormat::Text => print_banner(&mut std::io::stderr(), port, quiet)?,
+        LogFormat::Json if !quiet => log_banner(port),
+        LogFormat::Json => {}
+    }
+    Ok(server.run().await?)
 }
 
-#[derive(Debug, Deserialize)]
-/// Represents the parameters required for calculating an investment.
+/// Builds the subscriber that writes the request logs in the given format.
+///
+/// In JSON, the fields of every request (`method`, `path`, `status` and `latency_ms`) are written
+/// at the top level of the object, next to `timestamp`, `level` and `message`.
 ///
-/// This struct is used to deserialize JSON payloads sent to the `/compound-interests` endpoint.
+/// # Arguments
 ///
-/// # Fields
+/// * `format` - The format of the logs.
+/// * `writer` - Where the logs are written to, e.g. `std::io::stderr`.
+pub fn log_subscriber<W>(format: LogFormat, writer: W) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    // Only the logs of cic are written, not the startup messages of actix.
+    let requests_only =
+        filter_fn(|metadata| [REQUEST_LOG_TARGET, STARTUP_LOG_TARGET].contains(&metadata.target()));
+    let builder = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_target(false);
+    match format {
+        LogFormat::Text => Box::new(builder.finish().with(requests_only)),
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .finish()
+                .with(requests_only),
+        ),
+    }
+}
+
+/// The target of the request logs, used to keep other logs out of the output.
+const REQUEST_LOG_TARGET: &str = "cic::request";
+
+/// The target of the startup log that replaces the banner with `LogFormat::Json`.
+const STARTUP_LOG_TARGET: &str = "cic::startup";
+
+/// Logs every request with its method, path, response status and latency.
+async fn log_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>> {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_owned();
+    let res = next.call(req).await?;
+    tracing::info!(
+        target: REQUEST_LOG_TARGET,
+        method,
+        path,
+        status = res.status().as_u16(),
+        latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+        "request"
+    );
+    Ok(res)
+}
+
+/// A token-bucket rate limiter keyed by client IP, shared by the workers as `web::Data`.
 ///
-/// * `principal` - The initial amount of money invested (default: 0.0).
-/// * `contribution` - The monthly contribution added to the investment (default: 1.0).
-/// * `rate` - The annual interest rate as a percentage (default: 5.0).
-/// * `years` - The number of years the money is invested for (default: 5).
-pub struct InvestmentParams {
-    #[serde(default = "default_principal")]
-    pub principal: f64,
-    #[serde(default = "default_contribution")]
-    pub contribution: f64,
-    #[serde(default = "default_rate")]
-    pub rate: f64,
-    #[serde(default = "default_years")]
-    pub years: i32,
+/// Every client starts with a full bucket of `per_minute` tokens, and every request takes one.
+/// The bucket refills continuously at `per_minute` tokens a minute, so a client can burst up to
+/// the limit and then make one request every `60 / per_minute` seconds.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: NonZeroU32,
+    buckets: Mutex<HashMap<Option<IpAddr>, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
 }
 
-fn default_principal() -> f64 {
-    0.0
+/// The number of clients above which the full buckets are dropped, to bound the memory.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+impl RateLimiter {
+    /// Creates a rate limiter that allows `per_minute` requests a minute per client IP. A limit
+    /// of 0 would never refill, so it is ruled out by the type.
+    pub fn new(per_minute: NonZeroU32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the bucket of the client at the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The IP of the client, or `None` if it is unknown. Unknown clients share one bucket.
+    /// * `now` - The time of the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CicError::RateLimited` with the seconds until the next token if the bucket is
+    /// empty.
+    pub fn check(&self, client: Option<IpAddr>, now: Instant) -> Result<(), CicError> {
+        let capacity = f64::from(self.per_minute.get());
+        let per_second = capacity / 60.0;
+        let refilled = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * per_second).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() > MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| refilled(bucket) < capacity);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let tokens = refilled(bucket);
+        *bucket = Bucket {
+            tokens,
+            updated: now,
+        };
+        if tokens < 1.0 {
+            let wait = Duration::from_secs_f64((1.0 - tokens) / per_second);
+            return Err(CicError::RateLimited(wait.as_secs_f64().ceil() as u64));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Rejects a request with `429 Too Many Requests` if its client exceeds the `RateLimiter`.
+///
+/// Without a `RateLimiter` in the app data, every request is passed on.
+async fn limit_rate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>> {
+    if let Some(limiter) = req.app_data::<web::Data<RateLimiter>>() {
+        let client = req.peer_addr().map(|addr| addr.ip());
+        if let Err(e) = limiter.check(client, Instant::now()) {
+            return Ok(req.error_response(e).map_into_right_body());
+        }
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Registers the routes of the server.
+///
+/// The handlers expect the default `InvestmentParams` to be registered as `web::Data`.
+///
+/// # Arguments
+///
+/// * `cfg` - The service config the routes are added to.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(index))
+        .route("/options", web::get().to(options))
+        .route("/compound-interests", web::post().to(calculate_investment))
+        .route("/compound-interests/plot.svg", web::post().to(plot_svg))
+        .route("/rpc", web::post().to(rpc))
+        .route(
+            "/compound-interests",
+            web::get().to(calculate_investment_query),
+        );
+}
+
+/// Writes the startup banner listing the port and the available routes.
+///
+/// # Arguments
+///
+/// * `out` - The writer the banner is written to (stderr when starting the server).
+/// * `port` - The port number the server listens on.
+/// * `quiet` - If `true`, nothing is written.
+///
+/// # Returns
+///
+/// Returns a `std::io::Result<()>` indicating whether writing the banner succeeded.
+pub fn print_banner<W: Write>(out: &mut W, port: u16, quiet: bool) -> std::io::Result<()> {
+    if quiet {
+        return Ok(());
+    }
+    writeln!(out, "Starting server, port: {}", port)?;
+    for (method, route) in ROUTES {
+        writeln!(out, "{:<4} {}", method, route)?;
+    }
+    Ok(())
 }
 
-fn default_contribution() -> f64 {
-    1.0
+/// Logs the port and the available routes as a single event, the structured form of the banner.
+///
+/// # Arguments
+///
+/// * `port` - The port number the server listens on.
+pub fn log_banner(port: u16) {
+    let routes: Vec<String> = ROUTES
+        .iter()
+        .map(|(method, route)| format!("{} {}", method, route))
+        .collect();
+    tracing::info!(
+        target: STARTUP_LOG_TARGET,
+        port,
+        routes = routes.join(", "),
+        "Starting server"
+    );
 }
 
-fn default_rate() -> f64 {
-    5.0
+/// The methods and routes listed by the banner.
+const ROUTES: [(&str, &str); 6] = [
+    ("GET", "/"),
+    ("GET", "/options"),
+    ("POST", "/compound-interests"),
+    ("POST", "/compound-interests/plot.svg"),
+    ("POST", "/rpc"),
+    (
+        "GET",
+        "/compound-interests?principal=...&contribution=...&rate=...&years=...",
+    ),
+];
+
+/// The HTML form served at `/`.
+const INDEX_HTML: &str = include_str!("index.html");
+
+/// Handles HTTP GET requests to `/`.
+///
+/// Serves a minimal HTML form that posts to `/compound-interests` and displays the JSON result, so
+/// the server can be used from a browser without a separate frontend.
+pub async fn index() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(INDEX_HTML)
 }
 
-fn default_years() -> i32 {
-    5
+/// Handles HTTP GET requests to `/options`.
+///
+/// Lists the values the request parameters accept, so a frontend can build its form without
+/// hard-coding them: the compound frequencies, the account types, the boolean parameters and the
+/// range of every numeric parameter. A `min` or `max` is inclusive and an `exclusive_min` is not;
+/// `min_with_debt` is the bound that applies with `debt`, where `null` means any value.
+/// Every field of `InvestmentParams` is listed. There is no currency option: amounts are plain
+/// numbers, formatted in dollars.
+pub async fn options() -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "compound_frequencies": CompoundFrequency::ALL,
+        "account_types": AccountType::ALL,
+        "booleans": ["debt"],
+        "ranges": {
+            "principal": {"min": 0.0, "min_with_debt": null},
+            "contribution": {"min": 0.0},
+            "rate": {"min": 0.0},
+            "years": {"min": 0, "max": MAX_YEARS},
+            "inflation": {"exclusive_min": -100.0},
+            "fee": {"min": 0.0},
+            "tax_rate": {"min": 0.0, "max": 100.0},
+        },
+    }))
 }
 
 /// Handles HTTP POST requests to the `/compound-interests` endpoint.
 ///
 /// This function extracts investment parameters from the request body, calculates the investment summary,
-/// and returns the result as a JSON response.
+/// and returns the result as a JSON response. Fields omitted from the request fall back to the
+/// configured defaults; fields provided by the request always win.
 ///
 /// # Arguments
 ///
+/// * `defaults` - The default `InvestmentParams` of the server.
 /// * `params` - The incoming JSON payload containing the investment parameters.
 ///
 /// # Returns
 ///
-/// Returns a `Result<HttpResponse>`. On success, returns an `HttpResponse` with status `200 OK` and a JSON payload
+/// Returns a `Result<HttpResponse, CicError>`. On success, returns an `HttpResponse` with status `200 OK` and a JSON payload
 /// representing the yearly summary of the investment. On failure, returns an error response with the appropriate HTTP status code.
 ///
 /// # Errors
 ///
-/// Returns a `BadRequest` error if the parameters are invalid or cannot be parsed, and an `InternalServerError`
-/// if serialization of the summary fails.
-pub async fn calculate_investment(params: web::Json<InvestmentParams>) -> Result<HttpResponse> {
-    let investment = Investment::from_params(params.into_inner())
-        .map_err(|e| actix_web::error::ErrorBadRequest(e))?;
-
-    let summary = investment.yearly_summary();
+/// Returns a `CicError::Validation` (`400 Bad Request`) if the parameters are invalid or cannot be parsed,
+/// or a `CicError::Calculation` (`422 Unprocessable Entity`) if the amounts overflow.
+pub async fn calculate_investment(
+    defaults: web::Data<InvestmentParams>,
+    params: web::Json<serde_json::Map<String, serde_json::Value>>,
+) -> Result<HttpResponse, CicError> {
+    let summary = summary_from_body(&defaults, params.into_inner())?;
     let json = json!(summary);
 
     Ok(HttpResponse::Ok().json(json))
 }
+
+/// Handles HTTP POST requests to the `/compound-interests/plot.svg` endpoint.
+///
+/// The body is read like in `calculate_investment`, and the chart of the summary is returned as
+/// `image/svg+xml`, e.g. to inline it in a web page.
+///
+/// # Errors
+///
+/// Returns the errors of `calculate_investment`, a `CicError::Calculation` (`422 Unprocessable
+/// Entity`) if there is no year to plot, or a `CicError::Plot` (`500`) if rendering fails.
+pub async fn plot_svg(
+    defaults: web::Data<InvestmentParams>,
+    params: web::Json<serde_json::Map<String, serde_json::Value>>,
+) -> Result<HttpResponse, CicError> {
+    let summary = summary_from_body(&defaults, params.into_inner())?;
+    if summary.is_empty() {
+        return Err(CicError::Calculation(
+            "There is no year to plot: years must be at least 1".into(),
+        ));
+    }
+    let svg = render_svg(&summary, &PlotOptions::default())?;
+
+    Ok(HttpResponse::Ok().content_type("image/svg+xml").body(svg))
+}
+
+/// Calculates the summary of a JSON request body, falling back to the defaults for omitted fields.
+fn summary_from_body(
+    defaults: &InvestmentParams,
+    params: serde_json::Map<String, serde_json::Value>,
+) -> Result<Vec<YearlySummary>, CicError> {
+    let params = defaults
+        .with_overrides(params)
+        .map_err(|e| CicError::Validation(e.to_string()))?;
+    let investment = Investment::from_params(params)?;
+
+    let summary = investment.yearly_summary()?;
+    check_finite(&summary)?;
+    Ok(summary)
+}
+
+/// The error code of a request body that is not valid JSON.
+const RPC_PARSE_ERROR: i32 = -32700;
+/// The error code of a JSON value that is not a JSON-RPC 2.0 request.
+const RPC_INVALID_REQUEST: i32 = -32600;
+/// The error code of an unknown method.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+/// The error code of invalid method parameters, e.g. a negative principal.
+const RPC_INVALID_PARAMS: i32 = -32602;
+/// The error code of a failure of the server.
+const RPC_INTERNAL_ERROR: i32 = -32603;
+/// The server-defined error code of valid parameters without a result, e.g. overflowing amounts.
+const RPC_CALCULATION_ERROR: i32 = -32000;
+
+/// Handles JSON-RPC 2.0 requests to the `/rpc` endpoint.
+///
+/// The only method is `calculate`, whose `params` object holds the fields of `InvestmentParams`
+/// like the body of `calculate_investment`. The `result` is the yearly summary. Batch requests are
+/// not supported.
+///
+/// # Returns
+///
+/// Returns `200 OK` with the JSON-RPC response, which holds an `error` object with one of the
+/// standard codes if the request fails, or `-32000` if the amounts overflow. A notification (a
+/// request without an `id`) is answered with `204 No Content`.
+pub async fn rpc(defaults: web::Data<InvestmentParams>, body: web::Bytes) -> HttpResponse {
+    let response = match serde_json::from_slice(&body) {
+        Ok(request) => rpc_response(&defaults, request),
+        Err(e) => Some(rpc_error(
+            serde_json::Value::Null,
+            RPC_PARSE_ERROR,
+            format!("Parse error: {}", e),
+        )),
+    };
+    match response {
+        Some(response) => HttpResponse::Ok().json(response),
+        None => HttpResponse::NoContent().finish(),
+    }
+}
+
+/// Answers a single JSON-RPC request, or returns `None` for a valid notification.
+fn rpc_response(
+    defaults: &InvestmentParams,
+    request: serde_json::Value,
+) -> Option<serde_json::Value> {
+    let id = request.get("id").cloned();
+    let method = request
+        .as_object()
+        .filter(|request| request.get("jsonrpc") == Some(&json!("2.0")))
+        .and_then(|request| request.get("method"))
+        .and_then(serde_json::Value::as_str);
+    let Some(method) = method else {
+        return Some(rpc_error(
+            id.unwrap_or_default(),
+            RPC_INVALID_REQUEST,
+            "Invalid request: expected a JSON-RPC 2.0 request object".into(),
+        ));
+    };
+
+    let result = match method {
+        "calculate" => rpc_calculate(defaults, request.get("params")),
+        _ => Err((
+            RPC_METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+        Err((code, message)) => rpc_error(id, code, message),
+    })
+}
+
+/// Runs the `calculate` method, returning the summary or the JSON-RPC error code and message.
+fn rpc_calculate(
+    defaults: &InvestmentParams,
+    params: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, (i32, String)> {
+    let params = match params {
+        None => serde_json::Map::new(),
+        Some(serde_json::Value::Object(params)) => params.clone(),
+        Some(_) => {
+            return Err((
+                RPC_INVALID_PARAMS,
+                "Invalid params: expected an object of investment parameters".into(),
+            ))
+        }
+    };
+    summary_from_body(defaults, params)
+        .map(|summary| json!(summary))
+        .map_err(|e| {
+            let code = match e {
+                CicError::Validation(_) => RPC_INVALID_PARAMS,
+                CicError::Calculation(_) => RPC_CALCULATION_ERROR,
+                _ => RPC_INTERNAL_ERROR,
+            };
+            (code, e.to_string())
+        })
+}
+
+/// Builds a JSON-RPC 2.0 error response.
+fn rpc_error(id: serde_json::Value, code: i32, message: String) -> serde_json::Value {
+    json!({"jsonrpc": "2.0", "error": {"code": code, "message": message}, "id": id})
+}
+
+/// Handles HTTP GET requests to the `/compound-interests` endpoint.
+///
+/// This works like `calculate_investment`, but the investment parameters are read from the query
+/// string instead of the request body. Unknown query keys are ignored.
+///
+/// # Errors
+///
+/// Returns a `CicError::Validation` (`400 Bad Request`) if the query string is malformed or the
+/// parameters are invalid, or a `CicError::Calculation` (`422 Unprocessable Entity`) if the
+/// amounts overflow.
+pub async fn calculate_investment_query(
+    defaults: web::Data<InvestmentParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse, CicError> {
+    let (params, _) = defaults
+        .with_query(req.query_string())
+        .map_err(CicError::Validation)?;
+    let investment = Investment::from_params(params)?;
+
+    let summary = investment.yearly_summary()?;
+    check_finite(&summary)?;
+
+    Ok(HttpResponse::Ok().json(json!(summary)))
+}
+
+/// Maps every `CicError` to an HTTP status, so handlers can return it with `?`.
+///
+/// Invalid input is the client's fault (`400`), a valid input without a result is `422`, too many
+/// requests are `429` with a `Retry-After` header, and everything else is a server failure (`500`).
+impl ResponseError for CicError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CicError::Validation(_) => StatusCode::BAD_REQUEST,
+            CicError::Calculation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            CicError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            CicError::Plot(_) | CicError::Io(_) | CicError::Output(_) | CicError::Server(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        if let CicError::RateLimited(seconds) = self {
+            res.insert_header(("Retry-After", seconds.to_string()));
+        }
+        res.content_type("text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{
+        call_and_read_body_json, call_service, init_service, read_body, TestRequest,
+    };
+
+    #[test]
+    fn test_print_banner() {
+        let mut out = Vec::new();
+        print_banner(&mut out, 8080, false).unwrap();
+
+        let banner = String::from_utf8(out).unwrap();
+        assert!(banner.contains("Starting server, port: 8080"));
+        assert!(banner.contains("POST /compound-interests"));
+    }
+
+    #[test]
+    fn test_json_banner() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let _guard = tracing::subscriber::set_default(log_subscriber(LogFormat::Json, move || {
+            writer.clone()
+        }));
+        log_banner(8080);
+
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(logs.trim_end()).unwrap();
+        assert_eq!(line["message"], "Starting server");
+        assert_eq!(line["port"], 8080);
+        assert!(line["routes"]
+            .as_str()
+            .unwrap()
+            .contains("POST /compound-interests"));
+    }
+
+    #[test]
+    fn test_print_banner_quiet() {
+        let mut out = Vec::new();
+        print_banner(&mut out, 8080, true).unwrap();
+
+        assert!(out.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_omitted_field_falls_back_to_configured_default() {
+        let defaults = InvestmentParams {
+            rate: 7.0,
+            years: 3,
+            inflation: 10.0,
+            ..Default::default()
+        };
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(defaults))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"principal": 1000.0, "contribution": 100.0}))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+
+        let summary = body.as_array().unwrap();
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0]["principal"], 1000.0);
+        assert_eq!(summary[0]["annual_interest"], 70.0);
+        let total = summary[0]["total_amount"].as_f64().unwrap();
+        let real = summary[0]["real_total_amount"].as_f64().unwrap();
+        assert!((real - total / 1.1).abs() < 1e-9, "{}", real);
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"principal": 1000.0, "rate": 5.0, "years": 1}))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+
+        let summary = body.as_array().unwrap();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0]["annual_interest"], 50.0);
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"principal": 1000.0, "inflation": 0.0}))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["real_total_amount"], body[0]["total_amount"]);
+    }
+
+    #[actix_web::test]
+    async fn test_monthly_compound_frequency() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let mut totals = Vec::new();
+        for body in [
+            json!({"principal": 1000.0, "contribution": 0.0, "rate": 5.0, "years": 1}),
+            json!({"principal": 1000.0, "contribution": 0.0, "rate": 5.0, "years": 1,
+                "compound_frequency": "monthly"}),
+        ] {
+            let req = TestRequest::post()
+                .uri("/compound-interests")
+                .set_json(body)
+                .to_request();
+            let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+            totals.push(body[0]["total_amount"].as_f64().unwrap());
+        }
+        assert_eq!(totals[0], 1050.0);
+        assert!(totals[1] > totals[0]);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_field_type_is_bad_request() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"rate": "high"}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_tax_rate_out_of_range_is_bad_request() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"tax_rate": 500.0}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = TestRequest::get()
+            .uri("/compound-interests?tax_rate=-1")
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = TestRequest::post()
+            .uri("/rpc")
+            .set_json(json!({
+                "jsonrpc": "2.0",
+                "method": "calculate",
+                "params": {"tax_rate": 500.0},
+                "id": 1,
+            }))
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["code"], RPC_INVALID_PARAMS);
+    }
+
+    #[actix_web::test]
+    async fn test_overflow_is_unprocessable() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/compound-interests")
+            .set_json(json!({"principal": 1.0, "rate": 1e300, "years": 3}))
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_plot_svg() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/compound-interests/plot.svg")
+            .set_json(json!({"principal": 1000, "contribution": 100, "rate": 5, "years": 10}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("content-type").unwrap(), "image/svg+xml");
+        let body = read_body(resp).await;
+        let svg = std::str::from_utf8(&body).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.contains("Total Amount"));
+
+        let req = TestRequest::post()
+            .uri("/compound-interests/plot.svg")
+            .set_json(json!({"years": 0}))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_rpc() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+        let call =
+            |body: serde_json::Value| TestRequest::post().uri("/rpc").set_json(body).to_request();
+
+        let req = call(json!({
+            "jsonrpc": "2.0",
+            "method": "calculate",
+            "params": {"principal": 1000, "contribution": 0, "rate": 10, "years": 2},
+            "id": 7,
+        }));
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["jsonrpc"], "2.0");
+        assert_eq!(body["id"], 7);
+        assert!(body.get("error").is_none());
+        let result = body["result"].as_array().unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1]["total_amount"], 1210.0);
+
+        let req = call(json!({
+            "jsonrpc": "2.0",
+            "method": "calculate",
+            "params": {"principal": -1000},
+            "id": "bad",
+        }));
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["id"], "bad");
+        assert!(body.get("result").is_none());
+        assert_eq!(body["error"]["code"], RPC_INVALID_PARAMS);
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid input"));
+
+        let req = call(json!({"jsonrpc": "2.0", "method": "calculate", "params": [1], "id": 1}));
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["code"], RPC_INVALID_PARAMS);
+
+        let req = call(json!({"jsonrpc": "2.0", "method": "plot", "id": 1}));
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["code"], RPC_METHOD_NOT_FOUND);
+
+        let req = call(json!({"method": "calculate", "id": 1}));
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["code"], RPC_INVALID_REQUEST);
+
+        let req = TestRequest::post()
+            .uri("/rpc")
+            .set_payload("{not json")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"]["code"], RPC_PARSE_ERROR);
+        assert_eq!(body["id"], serde_json::Value::Null);
+
+        // A notification gets no response.
+        let req = call(json!({"jsonrpc": "2.0", "method": "calculate"}));
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn test_get_reads_query_string() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/compound-interests?principal=1000&contribution=0&rate=5&years=2")
+            .to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+
+        let summary = body.as_array().unwrap();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0]["annual_interest"], 50.0);
+    }
+
+    #[actix_web::test]
+    async fn test_options() {
+        let app = init_service(App::new().configure(configure)).await;
+
+        let req = TestRequest::get().uri("/options").to_request();
+        let body: serde_json::Value = call_and_read_body_json(&app, req).await;
+
+        assert_eq!(
+            body["compound_frequencies"],
+            json!(["annual", "semi-annual", "monthly"])
+        );
+        assert_eq!(body["ranges"]["years"]["max"], MAX_YEARS);
+
+        // Every advertised option is a parameter a request can send, and every parameter is
+        // advertised.
+        let keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            [
+                "account_types",
+                "booleans",
+                "compound_frequencies",
+                "ranges"
+            ]
+        );
+        let defaults = InvestmentParams::default();
+        let with = |overrides: serde_json::Value| {
+            defaults.with_overrides(overrides.as_object().unwrap().clone())
+        };
+        let mut covered = vec!["compound_frequency", "account_type"];
+        for frequency in body["compound_frequencies"].as_array().unwrap() {
+            let params = with(json!({"compound_frequency": frequency})).unwrap();
+            assert_eq!(json!(params.compound_frequency), *frequency);
+        }
+        for account_type in body["account_types"].as_array().unwrap() {
+            let params = with(json!({"account_type": account_type})).unwrap();
+            assert_eq!(json!(params.account_type), *account_type);
+        }
+        for key in body["booleans"].as_array().unwrap() {
+            let key = key.as_str().unwrap();
+            let params = with(json!({key: true})).unwrap();
+            assert_eq!(json!(params)[key], true, "{}", key);
+            covered.push(key);
+        }
+        for (key, range) in body["ranges"].as_object().unwrap() {
+            for bound in ["min", "max"] {
+                if let Some(value) = range.get(bound) {
+                    let params = with(json!({key: value})).unwrap();
+                    assert_eq!(json!(params)[key], *value, "{}", key);
+                    assert!(Investment::from_params(params).is_ok(), "{}", key);
+                }
+            }
+            if let Some(value) = range.get("exclusive_min") {
+                let params = with(json!({key: value})).unwrap();
+                assert!(Investment::from_params(params).is_err(), "{}", key);
+            }
+            covered.push(key);
+        }
+        let params = with(json!({"principal": -1000.0, "debt": true})).unwrap();
+        assert!(Investment::from_params(params).is_ok());
+
+        let fields = json!(defaults);
+        let mut fields: Vec<&str> = fields
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        fields.sort_unstable();
+        covered.sort_unstable();
+        assert_eq!(covered, fields);
+    }
+
+    #[actix_web::test]
+    async fn test_index_serves_html_form() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/").to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+        let content_type = resp.headers().get("content-type").unwrap();
+        assert!(content_type.to_str().unwrap().starts_with("text/html"));
+
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let html = std::str::from_utf8(&body).unwrap();
+        for field in ["principal", "contribution", "rate", "years"] {
+            assert!(html.contains(&format!("name=\"{}\"", field)));
+        }
+        assert!(html.contains("/compound-interests"));
+    }
+
+    /// A log writer that keeps everything written to it, shared with the test.
+    #[derive(Clone, Default)]
+    struct Capture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_json_request_log() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let _guard = tracing::subscriber::set_default(log_subscriber(LogFormat::Json, move || {
+            writer.clone()
+        }));
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(log_request))
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .configure(configure),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/compound-interests?years=1")
+            .to_request();
+        call_service(&app, req).await;
+
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(logs.lines().next().unwrap()).unwrap();
+        for key in ["timestamp", "method", "path", "status", "latency_ms"] {
+            assert!(line.get(key).is_some(), "missing {}: {}", key, line);
+        }
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["path"], "/compound-interests");
+        assert_eq!(line["status"], 200);
+    }
+
+    #[actix_web::test]
+    async fn test_rate_limit() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(limit_rate))
+                .app_data(web::Data::new(InvestmentParams::default()))
+                .app_data(web::Data::new(RateLimiter::new(
+                    NonZeroU32::new(3).unwrap(),
+                )))
+                .configure(configure),
+        )
+        .await;
+        let client = "203.0.113.7:50000".parse().unwrap();
+        let request = |peer| {
+            TestRequest::get()
+                .uri("/compound-interests?years=1")
+                .peer_addr(peer)
+                .to_request()
+        };
+
+        for _ in 0..3 {
+            assert_eq!(
+                call_service(&app, request(client)).await.status(),
+                StatusCode::OK
+            );
+        }
+        let res = call_service(&app, request(client)).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get("Retry-After").unwrap(), "20");
+
+        // Other clients have their own bucket.
+        let other = "203.0.113.8:50000".parse().unwrap();
+        assert_eq!(
+            call_service(&app, request(other)).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter_refills() {
+        let limiter = RateLimiter::new(NonZeroU32::new(60).unwrap());
+        let client = Some("203.0.113.7".parse().unwrap());
+        let start = Instant::now();
+        for _ in 0..60 {
+            limiter.check(client, start).unwrap();
+        }
+        assert!(matches!(
+            limiter.check(client, start),
+            Err(CicError::RateLimited(1))
+        ));
+        // One token comes back every second.
+        limiter
+            .check(client, start + Duration::from_secs(1))
+            .unwrap();
+        assert!(limiter
+            .check(client, start + Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_error_status_codes() {
+        let cases = [
+            (CicError::Validation("bad".into()), StatusCode::BAD_REQUEST),
+            (
+                CicError::Calculation("unreachable".into()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                CicError::Plot(crate::calculations::PlotError::EmptyData),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                CicError::Io(std::io::Error::other("disk")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                CicError::Output("xlsx".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                CicError::Server("oops".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (CicError::RateLimited(1), StatusCode::TOO_MANY_REQUESTS),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error.status_code(), status);
+            assert_eq!(error.error_response().status(), status);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! Links the calculations into a program built without the `server` feature, as a library user
+//! embedding them in a sync program would. Run with `cargo test --no-default-features`.
+#![cfg(not(feature = "server"))]
+
+use cic::calculations::Investment;
+use cic::params::InvestmentParams;
+
+#[test]
+fn test_calculations_link_without_the_server() {
+    let investment = Investment::from_params(InvestmentParams {
+        principal: 1000.0,
+        contribution: 100.0,
+        rate: 5.0,
+        years: 3,
+        ..Default::default()
+    })
+    .unwrap();
+    let summary = investment.yearly_summary().unwrap();
+    assert!((summary[2].total_amount - 4940.625).abs() < 1e-2);
+}